@@ -0,0 +1,214 @@
+use super::depends_on;
+use super::integral::replace;
+use crate::parser::{Function, Node};
+use crate::simplify::simplify;
+use crate::Number;
+
+fn number(value: f64) -> Node {
+    Node::Number(Number::real(value))
+}
+
+fn add(l: Node, r: Node) -> Node {
+    Node::Add(Box::new(l), Box::new(r))
+}
+
+fn sub(l: Node, r: Node) -> Node {
+    Node::Sub(Box::new(l), Box::new(r))
+}
+
+fn mul(l: Node, r: Node) -> Node {
+    Node::Mul(Box::new(l), Box::new(r))
+}
+
+fn div(l: Node, r: Node) -> Node {
+    Node::Div(Box::new(l), Box::new(r))
+}
+
+fn pow(l: Node, r: Node) -> Node {
+    Node::Pow(Box::new(l), Box::new(r))
+}
+
+/// The derivative of the node with respect to `var`, simplified so `d/dx x^2` is `2*x`
+///
+/// `floor` and `ceil` are piecewise constant, so their derivative is taken to be zero, and
+/// `mod(a, n)` is differentiated as `a - n*floor(a/n)`. The other integer functions are only
+/// defined at integers, so what's left of them after simplifying, which turns `n!/(n - 1)!` into
+/// `n`, has the undefined derivative `0/0`. Sums and products are differentiated term by term,
+/// treating their integer bounds as constants.
+pub fn diff(node: &Node, var: &str) -> Node {
+    simplify(&derivative(&simplify(node), var))
+}
+
+fn derivative(node: &Node, var: &str) -> Node {
+    if !depends_on(node, var) {
+        return number(0.0);
+    }
+    let d = |n: &Node| derivative(n, var);
+
+    match node {
+        Node::Variable(_) => number(1.0),
+        Node::Add(l, r) => add(d(l), d(r)),
+        Node::Sub(l, r) => sub(d(l), d(r)),
+        Node::Neg(n) => Node::Neg(Box::new(d(n))),
+        // Product rule
+        Node::Mul(l, r) => add(mul(d(l), *r.clone()), mul(*l.clone(), d(r))),
+        // Quotient rule
+        Node::Div(l, r) => div(
+            sub(mul(d(l), *r.clone()), mul(*l.clone(), d(r))),
+            pow(*r.clone(), number(2.0)),
+        ),
+        Node::Pow(base, exponent) => {
+            let (base, exponent) = (*base.clone(), *exponent.clone());
+            if !depends_on(&exponent, var) {
+                // Power rule, n * b^(n - 1) * b'
+                let power = pow(base.clone(), sub(exponent.clone(), number(1.0)));
+                mul(mul(exponent, power), d(&base))
+            } else if !depends_on(&base, var) {
+                // b^u = e^(u ln b)
+                let log = Node::Log(Box::new(base.clone()));
+                mul(mul(pow(base, exponent.clone()), log), d(&exponent))
+            } else {
+                // b^u * (u' ln b + u b' / b)
+                let log = Node::Log(Box::new(base.clone()));
+                let rate = add(
+                    mul(d(&exponent), log),
+                    div(mul(exponent.clone(), d(&base)), base.clone()),
+                );
+                mul(pow(base, exponent), rate)
+            }
+        }
+        Node::Exp(n) => mul(node.clone(), d(n)),
+        Node::Log(n) => div(d(n), *n.clone()),
+        Node::Sin(n) => mul(Node::Cos(n.clone()), d(n)),
+        Node::Cos(n) => Node::Neg(Box::new(mul(Node::Sin(n.clone()), d(n)))),
+        Node::Tan(n) => div(d(n), pow(Node::Cos(n.clone()), number(2.0))),
+        Node::Sqrt(n) => div(d(n), mul(number(2.0), node.clone())),
+        Node::Function(Function::Mod, arguments) => {
+            let (a, n) = (&arguments[0], &arguments[1]);
+            let quotient = div(a.clone(), n.clone());
+            let floor = Node::Function(Function::Floor, vec![quotient]);
+            sub(d(a), mul(d(n), floor))
+        }
+        // The bounds are integers, so only the terms change
+        Node::Function(Function::Sum, arguments)
+            if arguments[1] != Node::Variable(var.to_string()) =>
+        {
+            let mut arguments = arguments.clone();
+            arguments[0] = d(&arguments[0]);
+            Node::Function(Function::Sum, arguments)
+        }
+        // (f_a···f_b)' = Σ_j f_a···f_(j-1)·f_j'·f_(j+1)···f_b, which unlike the logarithmic
+        // derivative f_a···f_b·Σ f_j'/f_j is also defined where a factor is zero
+        Node::Function(Function::Product, arguments)
+            if arguments[1] != Node::Variable(var.to_string()) =>
+        {
+            let [f, index, a, b] = arguments.as_slice() else {
+                unreachable!("the parser checks the arity")
+            };
+            let j = Node::Variable(fresh_index(node));
+            let product = |a: Node, b: Node| {
+                Node::Function(Function::Product, vec![f.clone(), index.clone(), a, b])
+            };
+            let before = product(a.clone(), sub(j.clone(), number(1.0)));
+            let after = product(add(j.clone(), number(1.0)), b.clone());
+            let term = mul(mul(before, d(&replace(f, index, &j))), after);
+            Node::Function(Function::Sum, vec![term, j, a.clone(), b.clone()])
+        }
+        Node::Function(Function::Floor | Function::Ceil, _) => number(0.0),
+        Node::Function(..) => div(number(0.0), number(0.0)),
+        Node::Number(_) | Node::PiConstant | Node::EConstant | Node::IConstant => number(0.0),
+    }
+}
+
+/// A name for a new index that isn't used anywhere in the node, not even as the index of a sum
+/// or product inside it
+fn fresh_index(node: &Node) -> String {
+    let is_used = |name: &String| {
+        let variable = Node::Variable(name.clone());
+        replace(node, &variable, &number(0.0)) != *node
+    };
+    let letters = ('j'..='z')
+        .chain('a'..'j')
+        .filter(|c| !matches!(c, 'e' | 'i'));
+    letters
+        .map(String::from)
+        .chain((2..).map(|n| "j".repeat(n)))
+        .find(|name| !is_used(name))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::eval::evaluate;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    fn derived(input: &str) -> String {
+        diff(&parsed(input), "x").to_string()
+    }
+
+    #[test]
+    fn test_polynomials() {
+        assert_eq!(derived("x^2"), "2*x");
+        assert_eq!(derived("3*x^3 - 2*x + 7"), "9*x^2 - 2");
+        assert_eq!(derived("y^2 + 5"), "0");
+        assert_eq!(derived("1/x"), "-1/x^2");
+    }
+
+    #[test]
+    fn test_rules() {
+        assert_eq!(derived("x*sin(x)"), "x*cos(x) + sin(x)");
+        assert_eq!(derived("sin(x)/x"), "(x*cos(x) - sin(x))/x^2");
+        assert_eq!(derived("e^(2*x)"), "2*e^(2*x)");
+        assert_eq!(derived("ln(x^2 + 1)"), "2*x/(x^2 + 1)");
+        assert_eq!(derived("cos(x)"), "-sin(x)");
+        assert_eq!(derived("sqrt(x)"), "1/(2*sqrt(x))");
+        assert_eq!(derived("-tan(x)"), "-1/cos(x)^2");
+    }
+
+    #[test]
+    fn test_variable_exponents() {
+        assert_eq!(derived("2^x"), "2^x*ln(2)");
+        assert_eq!(derived("x^x"), "x^x*(ln(x) + 1)");
+        assert_eq!(derived("floor(x) + mod(x, 3)"), "1");
+    }
+
+    #[test]
+    fn test_integer_functions() {
+        assert_eq!(derived("x!/(x - 1)!"), "1");
+        assert_eq!(derived("binomial(x, 1)"), "1");
+        // x! only exists at integers, so it isn't a constant factor
+        let node = diff(&parsed("x!/x"), "x");
+        let variables = HashMap::from([("x".to_string(), Number::real(3.0))]);
+        assert!(evaluate(&node, &variables).is_err(), "{}", node);
+        assert_eq!(derived("gcd(x, 4)"), "0/0");
+    }
+
+    #[test]
+    fn test_sums_and_products() {
+        assert_eq!(
+            derived("sum(sin(k*x), k, 1, n)"),
+            "sum(k*cos(k*x), k, 1, n)"
+        );
+        assert_eq!(derived("sum(x^2, x, 1, n)"), "0");
+        assert_eq!(
+            derived("product(sin(k*x), k, 1, n)"),
+            "sum(j*cos(j*x)*product(sin(k*x), k, j + 1, n)*product(sin(k*x), k, 1, j - 1), j, 1, n)"
+        );
+
+        // x(x - 1)(x - 2) has the derivative 3x^2 - 6x + 2, also at its roots
+        let derivative = diff(&parsed("product(x - k, k, 0, 2)"), "x");
+        for (x, expected) in [(0.0, 2.0), (1.0, -1.0), (2.0, 2.0), (0.5, -0.25)] {
+            let variables = HashMap::from([("x".to_string(), Number::real(x))]);
+            let value = evaluate(&derivative, &variables).unwrap().value();
+            assert!((value - expected).abs() < 1e-12, "{} at x = {}", value, x);
+        }
+    }
+}
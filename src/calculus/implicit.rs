@@ -0,0 +1,159 @@
+use thiserror::Error;
+
+use super::integral::replace;
+use super::{depends_on, diff, variables};
+use crate::parser::{derivative_order, Equation, Node};
+use crate::simplify::coefficient::Coefficient;
+use crate::simplify::simplify;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ImplicitError {
+    #[error("The order of the derivative must be at least 1")]
+    InvalidOrder,
+    #[error("The equation already contains a derivative of {0}")]
+    ContainsDerivative(String),
+    #[error("The equation doesn't determine {0} as a function of {1}")]
+    Undetermined(String, String),
+}
+
+/// The `order`th derivative of `y` with respect to `x`, where `y` is defined implicitly by the
+/// equation, so `x^2 + y^2 = 25` gives `-x/y`
+///
+/// Both sides are differentiated with `y` as a function of `x`, which gives an equation that is
+/// linear in the derivative symbol `y'` and is solved for it. Higher derivatives differentiate
+/// the previous one the same way and substitute `y'`, so they only contain `x` and `y`.
+pub fn implicit_diff(
+    equation: &Equation,
+    x: &str,
+    y: &str,
+    order: usize,
+) -> Result<Node, ImplicitError> {
+    if order == 0 {
+        return Err(ImplicitError::InvalidOrder);
+    }
+    let node = Node::Sub(
+        Box::new(equation.lhs().clone()),
+        Box::new(equation.rhs().clone()),
+    );
+    if let Some(name) = variables(&node)
+        .into_iter()
+        .find(|name| matches!(derivative_order(name), (f, n) if f == y && n > 0))
+    {
+        return Err(ImplicitError::ContainsDerivative(name));
+    }
+
+    let prime = format!("{}'", y);
+    let first = solve_linear(&total_derivative(&node, x, y), &prime)
+        .ok_or_else(|| ImplicitError::Undetermined(y.to_string(), x.to_string()))?;
+    let symbol = Node::Variable(prime);
+    let mut result = first.clone();
+    for _ in 1..order {
+        result = simplify(&replace(&total_derivative(&result, x, y), &symbol, &first));
+    }
+    Ok(result)
+}
+
+/// The derivative with respect to `x` where `y` and its derivatives are functions of `x`, by the
+/// chain rule `d/dx f(x, y, y') = f_x + f_y*y' + f_y'*y''`
+fn total_derivative(node: &Node, x: &str, y: &str) -> Node {
+    let mut result = diff(node, x);
+    for name in variables(node) {
+        if derivative_order(&name).0 != y {
+            continue;
+        }
+        let next = Node::Variable(format!("{}'", name));
+        let term = Node::Mul(Box::new(diff(node, &name)), Box::new(next));
+        result = Node::Add(Box::new(result), Box::new(term));
+    }
+    simplify(&result)
+}
+
+/// Solves `node = 0` for the variable, None if the node isn't linear in it
+fn solve_linear(node: &Node, var: &str) -> Option<Node> {
+    let coefficient = diff(node, var);
+    let symbol = Node::Variable(var.to_string());
+    let rest = simplify(&Node::Sub(
+        Box::new(node.clone()),
+        Box::new(Node::Mul(Box::new(coefficient.clone()), Box::new(symbol))),
+    ));
+    let is_zero = Coefficient::from_node(&coefficient).is_some_and(Coefficient::is_zero);
+    if is_zero || depends_on(&coefficient, var) || depends_on(&rest, var) {
+        return None;
+    }
+    Some(simplify(&Node::Div(
+        Box::new(Node::Neg(Box::new(rest))),
+        Box::new(coefficient),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::eval::evaluate;
+    use crate::lexer::tokenize;
+    use crate::parser::parse_equation;
+    use crate::Number;
+
+    fn derived(input: &str, order: usize) -> Result<Node, ImplicitError> {
+        implicit_diff(
+            &parse_equation(&tokenize(input).unwrap()).unwrap(),
+            "x",
+            "y",
+            order,
+        )
+    }
+
+    fn evaluated(node: &Node, x: f64, y: f64) -> f64 {
+        let variables = HashMap::from([
+            ("x".to_string(), Number::real(x)),
+            ("y".to_string(), Number::real(y)),
+        ]);
+        evaluate(node, &variables).unwrap().value()
+    }
+
+    #[test]
+    fn test_first_derivative() {
+        assert_eq!(derived("x^2 + y^2 = 25", 1).unwrap().to_string(), "-x/y");
+        assert_eq!(derived("y = x^3", 1).unwrap().to_string(), "3*x^2");
+        assert_eq!(derived("x*y = 1", 1).unwrap().to_string(), "-y/x");
+
+        // x^3 + y^3 = 6xy at (3, 3), where the folium of Descartes has slope -1
+        let slope = derived("x^3 + y^3 = 6*x*y", 1).unwrap();
+        assert!((evaluated(&slope, 3.0, 3.0) + 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_higher_derivatives() {
+        // y'' = -(x^2 + y^2)/y^3 on the circle, -25/64 at (3, 4)
+        let second = derived("x^2 + y^2 = 25", 2).unwrap();
+        assert!(!depends_on(&second, "y'"));
+        assert!((evaluated(&second, 3.0, 4.0) + 25.0 / 64.0).abs() < 1e-12);
+
+        assert_eq!(derived("y = x^3", 3).unwrap().to_string(), "6");
+
+        // y = ln(x) from e^y = x, so y''' = 2/x^3
+        let third = derived("e^y = x", 3).unwrap();
+        assert!((evaluated(&third, 2.0, 2f64.ln()) - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            derived("x^2 + y^2 = 25", 0),
+            Err(ImplicitError::InvalidOrder)
+        );
+        assert_eq!(
+            derived("x^2 = 4", 1),
+            Err(ImplicitError::Undetermined(
+                "y".to_string(),
+                "x".to_string()
+            ))
+        );
+        assert_eq!(
+            derived("y' = y", 1),
+            Err(ImplicitError::ContainsDerivative("y'".to_string()))
+        );
+    }
+}
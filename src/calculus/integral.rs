@@ -0,0 +1,564 @@
+use std::fmt;
+
+use super::polynomial::Polynomial;
+use super::{depends_on, diff};
+use crate::parser::Node;
+use crate::simplify::coefficient::Coefficient;
+use crate::simplify::simplify;
+
+/// How many substitutions and integrations by parts may be nested
+const MAX_DEPTH: usize = 4;
+
+/// The variable that substitutions integrate over, the lexer can't produce this name so it never
+/// clashes with a variable of the integrand
+const SUBSTITUTE: &str = "_u";
+
+/// The result of [`integrate`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Integral {
+    /// An antiderivative, without the constant of integration
+    Evaluated(Node),
+    /// No antiderivative was found, the integral is kept as it was written
+    Unevaluated { integrand: Node, var: String },
+}
+
+impl Integral {
+    /// The antiderivative, if one was found
+    pub fn node(&self) -> Option<&Node> {
+        match self {
+            Integral::Evaluated(node) => Some(node),
+            Integral::Unevaluated { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Integral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Integral::Evaluated(node) => write!(f, "{}", node),
+            Integral::Unevaluated { integrand, var } => {
+                write!(f, "integral({}, {})", integrand, var)
+            }
+        }
+    }
+}
+
+fn number(value: i64) -> Node {
+    Coefficient::integer(value).to_node()
+}
+
+fn add(l: Node, r: Node) -> Node {
+    Node::Add(Box::new(l), Box::new(r))
+}
+
+fn sub(l: Node, r: Node) -> Node {
+    Node::Sub(Box::new(l), Box::new(r))
+}
+
+fn mul(l: Node, r: Node) -> Node {
+    Node::Mul(Box::new(l), Box::new(r))
+}
+
+fn div(l: Node, r: Node) -> Node {
+    Node::Div(Box::new(l), Box::new(r))
+}
+
+fn pow(l: Node, r: Node) -> Node {
+    Node::Pow(Box::new(l), Box::new(r))
+}
+
+fn ln(n: Node) -> Node {
+    Node::Log(Box::new(n))
+}
+
+/// Whether a simplified node is the number `value`
+fn is_number(node: &Node, value: i64) -> bool {
+    Coefficient::from_node(node) == Some(Coefficient::integer(value))
+}
+
+/// Finds an antiderivative of the node with respect to `var`
+///
+/// Besides linearity and a table of standard integrals this tries substitutions `u = g(x)` whose
+/// derivative appears in the integrand, integration by parts for a polynomial times an
+/// exponential, sine, cosine or logarithm, and partial fractions for rational functions whose
+/// denominator splits into rational roots. Logarithms are written without absolute values, so
+/// `integrate(1/x)` is `ln(x)`.
+pub fn integrate(node: &Node, var: &str) -> Integral {
+    match antiderivative(&simplify(node), var, MAX_DEPTH) {
+        Some(result) => Integral::Evaluated(simplify(&result)),
+        None => Integral::Unevaluated {
+            integrand: node.clone(),
+            var: var.to_string(),
+        },
+    }
+}
+
+fn antiderivative(node: &Node, var: &str, depth: usize) -> Option<Node> {
+    if !depends_on(node, var) {
+        return Some(mul(node.clone(), Node::Variable(var.to_string())));
+    }
+    match node {
+        Node::Add(l, r) => Some(add(
+            antiderivative(l, var, depth)?,
+            antiderivative(r, var, depth)?,
+        )),
+        Node::Sub(l, r) => Some(sub(
+            antiderivative(l, var, depth)?,
+            antiderivative(r, var, depth)?,
+        )),
+        Node::Neg(n) => Some(Node::Neg(Box::new(antiderivative(n, var, depth)?))),
+        _ => term(node, var, depth),
+    }
+}
+
+/// Splits a product into factors `(base, exponent)`, a quotient gives negative exponents
+pub(super) fn factors(node: &Node, inverted: bool, out: &mut Vec<(Node, Node)>) {
+    let sign = |exponent: Node| {
+        if inverted {
+            simplify(&Node::Neg(Box::new(exponent)))
+        } else {
+            exponent
+        }
+    };
+    match node {
+        Node::Mul(l, r) => {
+            factors(l, inverted, out);
+            factors(r, inverted, out);
+        }
+        Node::Div(l, r) => {
+            factors(l, inverted, out);
+            factors(r, !inverted, out);
+        }
+        Node::Neg(n) => {
+            out.push((number(-1), number(1)));
+            factors(n, inverted, out);
+        }
+        Node::Pow(base, exponent) => out.push((*base.clone(), sign(*exponent.clone()))),
+        Node::Sqrt(base) => out.push((*base.clone(), sign(Coefficient::rational(1, 2).to_node()))),
+        _ => out.push((node.clone(), sign(number(1)))),
+    }
+}
+
+pub(super) fn product(factors: &[(Node, Node)]) -> Node {
+    let nodes = factors
+        .iter()
+        .map(|(base, exponent)| pow(base.clone(), exponent.clone()));
+    simplify(&nodes.fold(number(1), mul))
+}
+
+/// Integrates a single term, which is a product of factors
+fn term(node: &Node, var: &str, depth: usize) -> Option<Node> {
+    let mut all = vec![];
+    factors(node, false, &mut all);
+    let (dependent, constant): (Vec<_>, Vec<_>) = all
+        .into_iter()
+        .partition(|(base, exponent)| depends_on(base, var) || depends_on(exponent, var));
+
+    let result = table(&dependent, var)
+        .or_else(|| exponential_trig(&dependent, var))
+        .or_else(|| rational(&dependent, var))
+        .or_else(|| {
+            let depth = depth.checked_sub(1)?;
+            substitution(&product(&dependent), var, depth)
+                .or_else(|| by_parts(&dependent, var, depth))
+        })?;
+    Some(mul(product(&constant), result))
+}
+
+/// The slope of a linear expression like `2*x + 1`
+fn slope(node: &Node, var: &str) -> Option<Node> {
+    let slope = diff(node, var);
+    (!depends_on(&slope, var) && !is_number(&slope, 0)).then_some(slope)
+}
+
+/// The standard integrals of a single factor whose argument is linear in `var`
+fn table(factors: &[(Node, Node)], var: &str) -> Option<Node> {
+    let [(base, exponent)] = factors else {
+        return None;
+    };
+
+    // c^u for a constant c is e^(u ln c)
+    if !depends_on(base, var) {
+        let a = slope(exponent, var)?;
+        return Some(div(
+            pow(base.clone(), exponent.clone()),
+            mul(ln(base.clone()), a),
+        ));
+    }
+    if depends_on(exponent, var) {
+        return None;
+    }
+
+    if let Some(a) = slope(base, var) {
+        if is_number(exponent, -1) {
+            return Some(div(ln(base.clone()), a));
+        }
+        let raised = simplify(&add(exponent.clone(), number(1)));
+        return Some(div(pow(base.clone(), raised.clone()), mul(raised, a)));
+    }
+
+    let (argument, a) = match base {
+        Node::Exp(u) | Node::Log(u) | Node::Sin(u) | Node::Cos(u) | Node::Tan(u) => {
+            (*u.clone(), slope(u, var)?)
+        }
+        _ => return None,
+    };
+    let u = || argument.clone();
+    let antiderivative = match (base, Coefficient::from_node(exponent)?.as_integer()?) {
+        (Node::Exp(_), 1) => base.clone(),
+        (Node::Log(_), 1) => sub(mul(u(), ln(u())), u()),
+        (Node::Sin(_), 1) => Node::Neg(Box::new(Node::Cos(Box::new(u())))),
+        (Node::Cos(_), 1) => Node::Sin(Box::new(u())),
+        (Node::Tan(_), 1) => Node::Neg(Box::new(ln(Node::Cos(Box::new(u()))))),
+        // sin^2 u = (1 - cos 2u) / 2 and cos^2 u = (1 + cos 2u) / 2
+        (Node::Sin(_) | Node::Cos(_), 2) => {
+            let double = Node::Sin(Box::new(mul(number(2), u())));
+            let half = div(u(), number(2));
+            let quarter = div(double, number(4));
+            match base {
+                Node::Sin(_) => sub(half, quarter),
+                _ => add(half, quarter),
+            }
+        }
+        (Node::Cos(_), -2) => Node::Tan(Box::new(u())),
+        _ => return None,
+    };
+    Some(div(antiderivative, a))
+}
+
+/// An exponential times `sin(v)` or `cos(v)` with both arguments linear, which integration by
+/// parts would go around in a circle on
+fn exponential_trig(factors: &[(Node, Node)], var: &str) -> Option<Node> {
+    let [first, second] = factors else {
+        return None;
+    };
+    let ((base, exponent), (function, power)) = match first.0 {
+        Node::Sin(_) | Node::Cos(_) => (second, first),
+        _ => (first, second),
+    };
+
+    // E'/E for the exponential E, which is e^u or c^u for a constant c
+    let a = match base {
+        Node::Exp(u) if is_number(exponent, 1) => slope(u, var)?,
+        _ if !depends_on(base, var) => mul(slope(exponent, var)?, ln(base.clone())),
+        _ => return None,
+    };
+    let (v, b) = match function {
+        Node::Sin(v) | Node::Cos(v) if is_number(power, 1) => (*v.clone(), slope(v, var)?),
+        _ => return None,
+    };
+
+    // ∫E·sin(v) = E·(a·sin(v) - b·cos(v))/(a² + b²)
+    // ∫E·cos(v) = E·(a·cos(v) + b·sin(v))/(a² + b²)
+    let sin = || Node::Sin(Box::new(v.clone()));
+    let cos = || Node::Cos(Box::new(v.clone()));
+    let numerator = match function {
+        Node::Sin(_) => sub(mul(a.clone(), sin()), mul(b.clone(), cos())),
+        _ => add(mul(a.clone(), cos()), mul(b.clone(), sin())),
+    };
+    let denominator = add(pow(a, number(2)), pow(b, number(2)));
+    Some(div(
+        mul(pow(base.clone(), exponent.clone()), numerator),
+        denominator,
+    ))
+}
+
+/// Rational functions, polynomial division and then partial fractions over the rational roots
+/// of the denominator
+fn rational(factors: &[(Node, Node)], var: &str) -> Option<Node> {
+    let one = || Polynomial::constant(Coefficient::integer(1));
+    let (mut numerator, mut denominator) = (one(), one());
+    for (base, exponent) in factors {
+        let n = Coefficient::from_node(exponent)?.as_integer()?;
+        let base = Polynomial::from_node(base, var)?;
+        let power = (0..n.unsigned_abs()).try_fold(one(), |acc, _| acc.mul(&base))?;
+        if n >= 0 {
+            numerator = numerator.mul(&power)?;
+        } else {
+            denominator = denominator.mul(&power)?;
+        }
+    }
+
+    let (quotient, remainder) = numerator.div_rem(&denominator)?;
+    let mut result = quotient.integral()?.to_node(var);
+    if remainder.is_zero() {
+        return Some(result);
+    }
+
+    let x = || Node::Variable(var.to_string());
+    for (root, multiplicity) in denominator.rational_roots()? {
+        // remainder / denominator = s(h) / h^m with x = root + h, where the first m coefficients
+        // of the series s are the numerators of the partial fractions of this root
+        let linear = Polynomial::linear(root);
+        let power = (0..multiplicity)
+            .try_fold(Polynomial::constant(Coefficient::integer(1)), |acc, _| {
+                acc.mul(&linear)
+            })?;
+        let (cofactor, _) = denominator.div_rem(&power)?;
+        let series = remainder
+            .shifted(root)?
+            .series_div(&cofactor.shifted(root)?, multiplicity)?;
+
+        let shifted = || sub(x(), root.to_node());
+        for (j, numerator) in series.into_iter().enumerate() {
+            let k = (multiplicity - j) as i64;
+            let fraction = if k == 1 {
+                ln(shifted())
+            } else {
+                // The integral of (x - r)^-k is -(x - r)^(1 - k) / (k - 1)
+                div(pow(shifted(), number(1 - k)), number(1 - k))
+            };
+            result = add(result, mul(numerator.to_node(), fraction));
+        }
+    }
+    Some(result)
+}
+
+/// Replaces every occurrence of `target` in the node
+pub(super) fn replace(node: &Node, target: &Node, replacement: &Node) -> Node {
+    if node == target {
+        return replacement.clone();
+    }
+    let r = |n: &Node| Box::new(replace(n, target, replacement));
+    match node {
+        Node::Add(a, b) => Node::Add(r(a), r(b)),
+        Node::Sub(a, b) => Node::Sub(r(a), r(b)),
+        Node::Mul(a, b) => Node::Mul(r(a), r(b)),
+        Node::Div(a, b) => Node::Div(r(a), r(b)),
+        Node::Pow(a, b) => Node::Pow(r(a), r(b)),
+        Node::Exp(n) => Node::Exp(r(n)),
+        Node::Log(n) => Node::Log(r(n)),
+        Node::Sin(n) => Node::Sin(r(n)),
+        Node::Cos(n) => Node::Cos(r(n)),
+        Node::Tan(n) => Node::Tan(r(n)),
+        Node::Sqrt(n) => Node::Sqrt(r(n)),
+        Node::Neg(n) => Node::Neg(r(n)),
+        Node::Function(function, arguments) => Node::Function(
+            *function,
+            arguments
+                .iter()
+                .map(|a| replace(a, target, replacement))
+                .collect(),
+        ),
+        Node::Number(_)
+        | Node::Variable(_)
+        | Node::PiConstant
+        | Node::EConstant
+        | Node::IConstant => node.clone(),
+    }
+}
+
+/// The subexpressions that could be substituted, everything that depends on `var` except `var`
+/// itself
+fn candidates(node: &Node, var: &str, out: &mut Vec<Node>) {
+    if !depends_on(node, var) || *node == Node::Variable(var.to_string()) {
+        return;
+    }
+    if !out.contains(node) {
+        out.push(node.clone());
+    }
+    match node {
+        Node::Add(a, b) | Node::Sub(a, b) | Node::Mul(a, b) | Node::Div(a, b) | Node::Pow(a, b) => {
+            candidates(a, var, out);
+            candidates(b, var, out);
+        }
+        Node::Exp(n)
+        | Node::Log(n)
+        | Node::Sin(n)
+        | Node::Cos(n)
+        | Node::Tan(n)
+        | Node::Sqrt(n)
+        | Node::Neg(n) => candidates(n, var, out),
+        Node::Function(_, arguments) => {
+            for argument in arguments {
+                candidates(argument, var, out);
+            }
+        }
+        Node::Number(_)
+        | Node::Variable(_)
+        | Node::PiConstant
+        | Node::EConstant
+        | Node::IConstant => {}
+    }
+}
+
+/// Substitutes `u = g(x)` when the integrand is `f(g(x)) * g'(x)` up to a constant
+fn substitution(node: &Node, var: &str, depth: usize) -> Option<Node> {
+    let mut found = vec![];
+    candidates(node, var, &mut found);
+    let substitute = Node::Variable(SUBSTITUTE.to_string());
+
+    found.iter().skip(1).find_map(|u| {
+        let du = diff(u, var);
+        if is_number(&du, 0) {
+            return None;
+        }
+        let rest = simplify(&div(node.clone(), du));
+        let rest = simplify(&replace(&rest, u, &substitute));
+        if depends_on(&rest, var) {
+            return None;
+        }
+        let integral = antiderivative(&rest, SUBSTITUTE, depth)?;
+        Some(replace(&simplify(&integral), &substitute, u))
+    })
+}
+
+/// Integration by parts for a polynomial times `e^u`, `sin(u)`, `cos(u)` or `ln(u)`, where the
+/// polynomial is differentiated until it vanishes or integrated to cancel the logarithm
+fn by_parts(factors: &[(Node, Node)], var: &str, depth: usize) -> Option<Node> {
+    let (polynomial, other): (Vec<_>, Vec<_>) =
+        factors.iter().cloned().partition(|(base, exponent)| {
+            Polynomial::from_node(base, var).is_some()
+                && Coefficient::from_node(exponent)
+                    .and_then(Coefficient::as_integer)
+                    .is_some_and(|n| n >= 0)
+        });
+    let [(function, exponent)] = other.as_slice() else {
+        return None;
+    };
+    if !is_number(exponent, 1) {
+        return None;
+    }
+    let p = Polynomial::from_node(&product(&polynomial), var)?;
+    if p.degree() == 0 {
+        return None;
+    }
+
+    match function {
+        Node::Exp(_) | Node::Sin(_) | Node::Cos(_) => {
+            // ∫p·g = p·G - ∫p'·G
+            let g = simplify(&table(&other, var)?);
+            let rest = simplify(&mul(p.derivative()?.to_node(var), g.clone()));
+            Some(sub(
+                mul(p.to_node(var), g),
+                antiderivative(&rest, var, depth)?,
+            ))
+        }
+        Node::Log(u) => {
+            // ∫p·ln(u) = P·ln(u) - ∫P·u'/u
+            let integral = p.integral()?.to_node(var);
+            let rest = simplify(&div(mul(integral.clone(), diff(u, var)), *u.clone()));
+            Some(sub(
+                mul(integral, function.clone()),
+                antiderivative(&rest, var, depth)?,
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::eval::evaluate;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+    use crate::Number;
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    fn integrated(input: &str) -> String {
+        integrate(&parsed(input), "x").to_string()
+    }
+
+    /// Checks that the derivative of the antiderivative is the integrand at a few points
+    fn assert_antiderivative(input: &str) {
+        let integrand = parsed(input);
+        let integral = integrate(&integrand, "x");
+        let antiderivative = integral
+            .node()
+            .unwrap_or_else(|| panic!("{} wasn't integrated", input));
+        let derivative = diff(antiderivative, "x");
+        for x in [0.3, 0.7, 1.9] {
+            let variables = HashMap::from([("x".to_string(), Number::real(x))]);
+            let expected = evaluate(&integrand, &variables).unwrap().value();
+            let actual = evaluate(&derivative, &variables).unwrap().value();
+            assert!(
+                (expected - actual).abs() < 1e-9 * expected.abs().max(1.0),
+                "d/dx {} = {} at {} but the integrand is {}",
+                antiderivative,
+                actual,
+                x,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(integrated("x^2"), "x^3/3");
+        assert_eq!(integrated("3"), "3*x");
+        assert_eq!(integrated("1/x"), "ln(x)");
+        assert_eq!(integrated("cos(2*x)"), "sin(2*x)/2");
+        assert_eq!(integrated("e^x + sin(x)"), "-cos(x) + e^x");
+        for input in [
+            "sqrt(x)",
+            "1/sqrt(2*x + 1)",
+            "(3*x - 1)^5",
+            "2^x",
+            "ln(x)",
+            "tan(x)",
+            "sin(x)^2",
+            "1/cos(x)^2",
+            "4*x^3 - x/2 + pi",
+        ] {
+            assert_antiderivative(input);
+        }
+    }
+
+    #[test]
+    fn test_substitution() {
+        assert_eq!(integrated("2*x*cos(x^2)"), "sin(x^2)");
+        for input in [
+            "x*e^(x^2)",
+            "sin(x)*cos(x)",
+            "ln(x)/x",
+            "x/(x^2 + 1)",
+            "cos(x)*e^sin(x)",
+            "x^2*sqrt(x^3 + 1)",
+        ] {
+            assert_antiderivative(input);
+        }
+    }
+
+    #[test]
+    fn test_by_parts() {
+        for input in [
+            "x*e^x",
+            "x^2*sin(x)",
+            "(x + 1)*cos(3*x)",
+            "x*ln(x)",
+            "x^2*ln(x)",
+            "e^x*sin(x)",
+            "cos(3*x)*e^(2*x)",
+            "2^x*sin(x)",
+            "e^(-x)*cos(x + 1)",
+        ] {
+            assert_antiderivative(input);
+        }
+    }
+
+    #[test]
+    fn test_partial_fractions() {
+        for input in [
+            "1/(x^2 - 1)",
+            "(x^3 + 2)/(x + 3)",
+            "1/(x*(x + 1)^2)",
+            "(2*x + 3)/(x^2 + 5*x + 6)",
+            "1/(4*x^2 - 1)",
+        ] {
+            assert_antiderivative(input);
+        }
+    }
+
+    #[test]
+    fn test_unevaluated() {
+        assert_eq!(integrated("e^(x^2)"), "integral(e^x^2, x)");
+        assert!(integrate(&parsed("1/(x^2 + 1)"), "x").node().is_none());
+        assert!(integrate(&parsed("sin(x)/x"), "x").node().is_none());
+    }
+}
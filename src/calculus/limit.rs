@@ -0,0 +1,722 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use thiserror::Error;
+
+use super::integral::replace;
+use super::series::leading_term;
+use super::{depends_on, diff};
+use crate::eval::evaluate;
+use crate::parser::{Function, Node};
+use crate::simplify::coefficient::Coefficient;
+use crate::simplify::simplify;
+use crate::Number;
+
+/// How many times L'Hôpital's rule may be applied along one path
+const MAX_DEPTH: usize = 8;
+
+/// Limits closer to zero than this are zero, unless they are a plain number like 1e-13
+const EPSILON: f64 = 1e-12;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LimitError {
+    #[error("The limit point must be a number")]
+    InvalidPoint,
+    #[error("The limit couldn't be determined")]
+    Undetermined,
+}
+
+/// Where the variable goes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Point {
+    At(Node),
+    PositiveInfinity,
+    NegativeInfinity,
+}
+
+/// Which side a finite point is approached from, limits at infinity ignore it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Both,
+    /// From below
+    Left,
+    /// From above
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Limit {
+    Value(Node),
+    PositiveInfinity,
+    NegativeInfinity,
+    DoesNotExist,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Value(node) => write!(f, "{}", node),
+            Limit::PositiveInfinity => write!(f, "infinity"),
+            Limit::NegativeInfinity => write!(f, "-infinity"),
+            Limit::DoesNotExist => write!(f, "does not exist"),
+        }
+    }
+}
+
+/// The limit of a subexpression
+#[derive(Debug, Clone)]
+enum Value {
+    /// A constant node and its numeric value
+    Finite(Node, f64),
+    PositiveInfinity,
+    NegativeInfinity,
+    /// Oscillates between finite bounds, like `sin(x)` at infinity
+    Bounded,
+    /// Doesn't exist and isn't bounded either
+    Undefined,
+}
+
+impl Value {
+    fn finite(node: Node) -> Result<Value, LimitError> {
+        let node = simplify(&node);
+        let value = numeric(&node).ok_or(LimitError::Undetermined)?;
+        Ok(Value::Finite(node, value))
+    }
+
+    fn infinity(sign: f64) -> Value {
+        if sign > 0.0 {
+            Value::PositiveInfinity
+        } else {
+            Value::NegativeInfinity
+        }
+    }
+
+    fn neg(self) -> Result<Value, LimitError> {
+        Ok(match self {
+            Value::Finite(node, _) => Value::finite(Node::Neg(Box::new(node)))?,
+            Value::PositiveInfinity => Value::NegativeInfinity,
+            Value::NegativeInfinity => Value::PositiveInfinity,
+            other => other,
+        })
+    }
+
+    /// Numbers and fractions are compared with 0 exactly, other constants like `sin(pi)` only up
+    /// to rounding errors
+    fn is_zero(&self) -> bool {
+        match self {
+            Value::Finite(node, value) if Coefficient::from_node(node).is_some() => *value == 0.0,
+            Value::Finite(_, value) => value.abs() < EPSILON,
+            _ => false,
+        }
+    }
+
+    /// The sign of an infinity or of a nonzero finite value
+    fn sign(&self) -> Option<f64> {
+        match self {
+            Value::Finite(_, value) if !self.is_zero() => Some(value.signum()),
+            Value::PositiveInfinity => Some(1.0),
+            Value::NegativeInfinity => Some(-1.0),
+            _ => None,
+        }
+    }
+
+    fn is_infinite(&self) -> bool {
+        matches!(self, Value::PositiveInfinity | Value::NegativeInfinity)
+    }
+
+    fn same(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Finite(_, a), Value::Finite(_, b)) => {
+                (a - b).abs() <= EPSILON * a.abs().max(1.0)
+            }
+            (Value::PositiveInfinity, Value::PositiveInfinity)
+            | (Value::NegativeInfinity, Value::NegativeInfinity) => true,
+            _ => false,
+        }
+    }
+
+    fn into_limit(self) -> Limit {
+        match self {
+            Value::Finite(node, _) => Limit::Value(node),
+            Value::PositiveInfinity => Limit::PositiveInfinity,
+            Value::NegativeInfinity => Limit::NegativeInfinity,
+            Value::Bounded | Value::Undefined => Limit::DoesNotExist,
+        }
+    }
+}
+
+/// The real value of a constant node
+fn numeric(node: &Node) -> Option<f64> {
+    let value = evaluate(node, &HashMap::new()).ok()?;
+    let real = value.re();
+    (real.is_finite() && value.im().abs() <= EPSILON * real.abs().max(1.0)).then_some(real)
+}
+
+fn div(l: Node, r: Node) -> Node {
+    Node::Div(Box::new(l), Box::new(r))
+}
+
+fn recip(node: &Node) -> Node {
+    simplify(&div(Node::Number(Number::real(1.0)), node.clone()))
+}
+
+/// The limit of the node as `var` approaches the point
+///
+/// Continuous expressions are evaluated at the point. Indeterminate forms are rewritten as a
+/// quotient and resolved with L'Hôpital's rule, `0*∞` as `0/(1/∞)`, `∞ - ∞` as
+/// `(1/a + 1/b)/(1/(a*b))` unless one side dominates, and powers like `1^∞` through
+/// `e^(b*ln(a))`, and `∞/∞` quotients whose derivatives have no limit are split into a sum of
+/// quotients. Whether `1/x` goes to positive or negative infinity is decided by the sign of
+/// `x` close to the point. What this doesn't resolve is taken from the leading term of the
+/// Laurent series, at infinity in `1/x`. A two sided limit exists when both one sided limits
+/// agree.
+pub fn limit(
+    node: &Node,
+    var: &str,
+    point: &Point,
+    direction: Direction,
+) -> Result<Limit, LimitError> {
+    let at = match point {
+        Point::At(at) if !depends_on(at, var) => numeric(at).ok_or(LimitError::InvalidPoint)?,
+        Point::At(_) => return Err(LimitError::InvalidPoint),
+        Point::PositiveInfinity => f64::INFINITY,
+        Point::NegativeInfinity => f64::NEG_INFINITY,
+    };
+    let node = simplify(node);
+    let one_sided = |side: f64| {
+        let approach = Approach {
+            var,
+            point,
+            at,
+            side,
+        };
+        approach
+            .limit(&node, MAX_DEPTH)
+            .or_else(|error| approach.leading_term(&node).ok_or(error))
+    };
+
+    let value = match (point, direction) {
+        (Point::PositiveInfinity, _) => one_sided(-1.0)?,
+        (Point::NegativeInfinity, _) => one_sided(1.0)?,
+        (Point::At(_), Direction::Right) => one_sided(1.0)?,
+        (Point::At(_), Direction::Left) => one_sided(-1.0)?,
+        (Point::At(_), Direction::Both) => {
+            let (right, left) = (one_sided(1.0)?, one_sided(-1.0)?);
+            if right.same(&left) {
+                right
+            } else {
+                Value::Undefined
+            }
+        }
+    };
+    Ok(value.into_limit())
+}
+
+/// A one sided approach to a point, `side` is 1 when coming from above and -1 from below
+struct Approach<'a> {
+    var: &'a str,
+    point: &'a Point,
+    at: f64,
+    side: f64,
+}
+
+impl Approach<'_> {
+    /// The sign the node has close to the point, None if it changes sign or can't be evaluated
+    fn sign_near(&self, node: &Node) -> Option<f64> {
+        let mut variables = HashMap::new();
+        let mut sign = None;
+        for h in [1e-3, 1e-4, 1e-5, 1e-6] {
+            let x = if self.at.is_infinite() {
+                self.at.signum() / h
+            } else {
+                self.at + self.side * h * self.at.abs().max(1.0)
+            };
+            variables.insert(self.var.to_string(), Number::real(x));
+            let value = evaluate(node, &variables).ok()?;
+            if value.im().abs() > EPSILON * value.re().abs() {
+                return None;
+            }
+            if value.re() != 0.0 {
+                let s = value.re().signum();
+                if sign.is_some_and(|sign| sign != s) {
+                    return None;
+                }
+                sign = Some(s);
+            }
+        }
+        sign
+    }
+
+    /// The limit from the leading term of the Laurent series, for quotients that L'Hôpital's
+    /// rule doesn't resolve within its depth
+    ///
+    /// At infinity `x` is replaced by `±1/x`, which goes to 0 from above, so the series of
+    /// `sqrt(x^2 + 1) - x` is the one of `sqrt(1/x^2 + 1) - 1/x` at 0.
+    fn leading_term(&self, node: &Node) -> Option<Value> {
+        let zero = Node::Number(Number::real(0.0));
+        let (node, at, side) = match self.point {
+            Point::At(at) => (node.clone(), at, self.side),
+            Point::PositiveInfinity | Point::NegativeInfinity => {
+                let x = Node::Variable(self.var.to_string());
+                let sign = Node::Number(Number::real(self.at.signum()));
+                (replace(node, &x, &div(sign, x.clone())), &zero, 1.0)
+            }
+        };
+        let (power, coefficient) = leading_term(&node, self.var, at, side > 0.0).ok()?;
+        match power.cmp(&0) {
+            Ordering::Greater => Value::finite(Node::Number(Number::real(0.0))).ok(),
+            Ordering::Equal => Value::finite(coefficient).ok(),
+            Ordering::Less => {
+                // An odd pole changes sign with the side
+                let side = if power % 2 == 0 { 1.0 } else { side };
+                Some(Value::infinity(numeric(&coefficient)?.signum() * side))
+            }
+        }
+    }
+
+    fn limit(&self, node: &Node, depth: usize) -> Result<Value, LimitError> {
+        if !depends_on(node, self.var) {
+            return Value::finite(node.clone());
+        }
+        match node {
+            Node::Variable(_) => match self.point {
+                Point::At(at) => Value::finite(at.clone()),
+                Point::PositiveInfinity => Ok(Value::PositiveInfinity),
+                Point::NegativeInfinity => Ok(Value::NegativeInfinity),
+            },
+            Node::Add(l, r) => self.sum(l, r, false, depth),
+            Node::Sub(l, r) => self.sum(l, r, true, depth),
+            Node::Neg(n) => self.limit(n, depth)?.neg(),
+            Node::Mul(l, r) => self.product(l, r, depth),
+            Node::Div(l, r) => self.quotient(l, r, depth),
+            Node::Pow(base, exponent) => self.power(base, exponent, depth),
+            Node::Exp(n) => {
+                let value = self.limit(n, depth)?;
+                Ok(exp(value)?)
+            }
+            Node::Log(n) => Ok(match self.limit(n, depth)? {
+                value if value.is_zero() => match self.sign_near(n) {
+                    Some(sign) if sign > 0.0 => Value::NegativeInfinity,
+                    _ => Value::Undefined,
+                },
+                Value::Finite(n, value) if value > 0.0 => Value::finite(Node::Log(Box::new(n)))?,
+                Value::PositiveInfinity => Value::PositiveInfinity,
+                Value::Finite(..) | Value::NegativeInfinity | Value::Undefined => Value::Undefined,
+                Value::Bounded => return Err(LimitError::Undetermined),
+            }),
+            Node::Sqrt(n) => Ok(match self.limit(n, depth)? {
+                value if value.is_zero() => match self.sign_near(n) {
+                    Some(sign) if sign < 0.0 => Value::Undefined,
+                    _ => Value::finite(Node::Number(Number::real(0.0)))?,
+                },
+                Value::Finite(n, value) if value > 0.0 => Value::finite(Node::Sqrt(Box::new(n)))?,
+                Value::PositiveInfinity => Value::PositiveInfinity,
+                Value::Finite(..) | Value::NegativeInfinity | Value::Undefined => Value::Undefined,
+                Value::Bounded => return Err(LimitError::Undetermined),
+            }),
+            Node::Sin(n) | Node::Cos(n) => Ok(match self.limit(n, depth)? {
+                Value::Finite(n, _) => Value::finite(match node {
+                    Node::Sin(_) => Node::Sin(Box::new(n)),
+                    _ => Node::Cos(Box::new(n)),
+                })?,
+                Value::PositiveInfinity | Value::NegativeInfinity | Value::Bounded => {
+                    Value::Bounded
+                }
+                Value::Undefined => Value::Undefined,
+            }),
+            Node::Tan(n) => match self.limit(n, depth)? {
+                Value::Finite(_, value) if value.cos().abs() < EPSILON => {
+                    self.quotient(&Node::Sin(n.clone()), &Node::Cos(n.clone()), depth)
+                }
+                Value::Finite(n, _) => Value::finite(Node::Tan(Box::new(n))),
+                Value::Bounded => Err(LimitError::Undetermined),
+                _ => Ok(Value::Undefined),
+            },
+            Node::Function(function, arguments) => self.function(*function, arguments, depth),
+            Node::Number(_) | Node::PiConstant | Node::EConstant | Node::IConstant => {
+                Value::finite(node.clone())
+            }
+        }
+    }
+
+    fn sum(&self, l: &Node, r: &Node, subtract: bool, depth: usize) -> Result<Value, LimitError> {
+        let a = self.limit(l, depth)?;
+        let b = self.limit(r, depth)?;
+        let b = if subtract { b.neg()? } else { b };
+        Ok(match (a, b) {
+            (Value::Undefined, _) | (_, Value::Undefined) => Value::Undefined,
+            (Value::Finite(a, _), Value::Finite(b, _)) => {
+                Value::finite(Node::Add(Box::new(a), Box::new(b)))?
+            }
+            (Value::PositiveInfinity, Value::NegativeInfinity)
+            | (Value::NegativeInfinity, Value::PositiveInfinity) => {
+                let r = if subtract {
+                    Node::Neg(Box::new(r.clone()))
+                } else {
+                    r.clone()
+                };
+                self.difference(l, &r, depth)?
+            }
+            (Value::PositiveInfinity, _) | (_, Value::PositiveInfinity) => Value::PositiveInfinity,
+            (Value::NegativeInfinity, _) | (_, Value::NegativeInfinity) => Value::NegativeInfinity,
+            (Value::Bounded, _) | (_, Value::Bounded) => Value::Bounded,
+        })
+    }
+
+    /// `a + b` where `a` and `b` go to infinities of opposite signs
+    fn difference(&self, a: &Node, b: &Node, depth: usize) -> Result<Value, LimitError> {
+        let depth = depth.checked_sub(1).ok_or(LimitError::Undetermined)?;
+        let sign = self
+            .limit(a, depth)?
+            .sign()
+            .ok_or(LimitError::Undetermined)?;
+
+        // a + b = a*(1 + b/a), which is infinite unless b/a goes to -1
+        let ratio = self.limit(&simplify(&div(b.clone(), a.clone())), depth)?;
+        match ratio {
+            Value::Finite(_, c) if (1.0 + c).abs() >= EPSILON => {
+                return Ok(Value::infinity(sign * (1.0 + c).signum()))
+            }
+            Value::PositiveInfinity | Value::NegativeInfinity => return Ok(Value::infinity(-sign)),
+            _ => {}
+        }
+
+        // a + b = (1/a + 1/b) / (1/(a*b)), which is 0/0
+        let numerator = simplify(&Node::Add(Box::new(recip(a)), Box::new(recip(b))));
+        let denominator = recip(&Node::Mul(Box::new(a.clone()), Box::new(b.clone())));
+        self.quotient(&numerator, &denominator, depth)
+    }
+
+    fn product(&self, l: &Node, r: &Node, depth: usize) -> Result<Value, LimitError> {
+        let a = self.limit(l, depth)?;
+        let b = self.limit(r, depth)?;
+        Ok(match (&a, &b) {
+            (Value::Undefined, _) | (_, Value::Undefined) => Value::Undefined,
+            (Value::Finite(a, _), Value::Finite(b, _)) => {
+                Value::finite(Node::Mul(Box::new(a.clone()), Box::new(b.clone())))?
+            }
+            _ if a.is_zero() && b.is_infinite() => self.zero_times_infinity(l, r, depth)?,
+            _ if b.is_zero() && a.is_infinite() => self.zero_times_infinity(r, l, depth)?,
+            (Value::Bounded, _) | (_, Value::Bounded) => {
+                if a.is_zero() || b.is_zero() {
+                    Value::finite(Node::Number(Number::real(0.0)))?
+                } else if a.is_infinite() || b.is_infinite() {
+                    Value::Undefined
+                } else {
+                    Value::Bounded
+                }
+            }
+            _ => match (a.sign(), b.sign()) {
+                (Some(x), Some(y)) => Value::infinity(x * y),
+                _ => return Err(LimitError::Undetermined),
+            },
+        })
+    }
+
+    /// `0*∞` as `0/(1/∞)` or, if that doesn't resolve, `∞/(1/0)`
+    fn zero_times_infinity(
+        &self,
+        zero: &Node,
+        infinite: &Node,
+        depth: usize,
+    ) -> Result<Value, LimitError> {
+        let depth = depth.checked_sub(1).ok_or(LimitError::Undetermined)?;
+        self.quotient(zero, &recip(infinite), depth)
+            .or_else(|_| self.quotient(infinite, &recip(zero), depth))
+    }
+
+    fn quotient(&self, l: &Node, r: &Node, depth: usize) -> Result<Value, LimitError> {
+        let a = self.limit(l, depth)?;
+        let b = self.limit(r, depth)?;
+        Ok(match (&a, &b) {
+            (Value::Undefined, _) | (_, Value::Undefined) => Value::Undefined,
+            _ if a.is_zero() && b.is_zero() => self.lhopital(l, r, depth)?,
+            _ if a.is_infinite() && b.is_infinite() => self
+                .lhopital(l, r, depth)
+                .or_else(|error| self.split(l, r, depth).map_err(|_| error))?,
+            (_, Value::Bounded) => return Err(LimitError::Undetermined),
+            _ if b.is_zero() => match (a.sign(), self.sign_near(r)) {
+                (Some(x), Some(y)) => Value::infinity(x * y),
+                (None, _) => Value::Undefined,
+                (_, None) => Value::Undefined,
+            },
+            (Value::Finite(a, _), Value::Finite(b, _)) => Value::finite(div(a.clone(), b.clone()))?,
+            (_, Value::PositiveInfinity | Value::NegativeInfinity) => {
+                Value::finite(Node::Number(Number::real(0.0)))?
+            }
+            (Value::Bounded, _) => Value::Bounded,
+            _ => match (a.sign(), b.sign()) {
+                (Some(x), Some(y)) => Value::infinity(x * y),
+                _ => return Err(LimitError::Undetermined),
+            },
+        })
+    }
+
+    /// L'Hôpital's rule, the limit of `l/r` for `0/0` and `∞/∞` is the limit of `l'/r'`
+    fn lhopital(&self, l: &Node, r: &Node, depth: usize) -> Result<Value, LimitError> {
+        let depth = depth.checked_sub(1).ok_or(LimitError::Undetermined)?;
+        let denominator = diff(r, self.var);
+        if numeric(&denominator).is_some_and(|value| value == 0.0) {
+            return Err(LimitError::Undetermined);
+        }
+        let ratio = simplify(&div(diff(l, self.var), denominator));
+        match self.limit(&ratio, depth)? {
+            // The rule only applies when the limit of l'/r' exists, (x + sin(x))/x goes to 1
+            // while 1 + cos(x) oscillates
+            Value::Bounded | Value::Undefined => Err(LimitError::Undetermined),
+            value => Ok(value),
+        }
+    }
+
+    /// `(a + b)/r` as `a/r + b/r`, for `∞/∞` quotients that L'Hôpital's rule doesn't resolve
+    fn split(&self, l: &Node, r: &Node, depth: usize) -> Result<Value, LimitError> {
+        let depth = depth.checked_sub(1).ok_or(LimitError::Undetermined)?;
+        let (a, b, subtract) = match l {
+            Node::Add(a, b) => (a, b, false),
+            Node::Sub(a, b) => (a, b, true),
+            _ => return Err(LimitError::Undetermined),
+        };
+        let a = simplify(&div(*a.clone(), r.clone()));
+        let b = simplify(&div(*b.clone(), r.clone()));
+        self.sum(&a, &b, subtract, depth)
+    }
+
+    fn power(&self, base: &Node, exponent: &Node, depth: usize) -> Result<Value, LimitError> {
+        let b = self.limit(base, depth)?;
+        let e = self.limit(exponent, depth)?;
+        match (&b, &e) {
+            (Value::Undefined, _) | (_, Value::Undefined) => Ok(Value::Undefined),
+            (Value::Finite(bn, _), Value::Finite(en, _)) if !b.is_zero() => {
+                Value::finite(Node::Pow(Box::new(bn.clone()), Box::new(en.clone())))
+            }
+            (Value::Finite(..), Value::Finite(_, ev)) if !e.is_zero() && *ev > 0.0 => {
+                Value::finite(Node::Number(Number::real(0.0)))
+            }
+            (Value::Finite(..), Value::Finite(_, ev)) if !e.is_zero() && *ev < 0.0 => {
+                // 0^-n = 1/0^n
+                let positive = Node::Neg(Box::new(exponent.clone()));
+                let denominator = Node::Pow(Box::new(base.clone()), Box::new(positive));
+                self.quotient(&Node::Number(Number::real(1.0)), &denominator, depth)
+            }
+            (Value::PositiveInfinity, Value::Finite(_, ev)) if !e.is_zero() => Ok(if *ev > 0.0 {
+                Value::PositiveInfinity
+            } else {
+                Value::finite(Node::Number(Number::real(0.0)))?
+            }),
+            (Value::NegativeInfinity, Value::Finite(_, ev)) if !e.is_zero() => {
+                if *ev < 0.0 {
+                    return Value::finite(Node::Number(Number::real(0.0)));
+                }
+                if ev.fract() != 0.0 {
+                    return Ok(Value::Undefined);
+                }
+                Ok(Value::infinity(if ev % 2.0 == 0.0 { 1.0 } else { -1.0 }))
+            }
+            _ => {
+                // b^e = e^(e ln b) for the forms 0^0, 1^∞ and ∞^0, the base has to be positive
+                if self.sign_near(base) != Some(1.0) {
+                    return if matches!(b, Value::Bounded) || matches!(e, Value::Bounded) {
+                        Err(LimitError::Undetermined)
+                    } else {
+                        Ok(Value::Undefined)
+                    };
+                }
+                let product = simplify(&Node::Mul(
+                    Box::new(exponent.clone()),
+                    Box::new(Node::Log(Box::new(base.clone()))),
+                ));
+                exp(self.limit(&product, depth)?)
+            }
+        }
+    }
+
+    fn function(
+        &self,
+        function: Function,
+        arguments: &[Node],
+        depth: usize,
+    ) -> Result<Value, LimitError> {
+        if function == Function::Mod {
+            // mod(a, n) = a - n*floor(a/n)
+            let (a, n) = (&arguments[0], &arguments[1]);
+            let quotient = div(a.clone(), n.clone());
+            let floor = Node::Function(Function::Floor, vec![quotient]);
+            let product = Node::Mul(Box::new(n.clone()), Box::new(floor));
+            return self.limit(&Node::Sub(Box::new(a.clone()), Box::new(product)), depth);
+        }
+
+        let values = arguments
+            .iter()
+            .map(|argument| self.limit(argument, depth))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let (Function::Floor | Function::Ceil, [value]) = (function, values.as_slice()) {
+            return match value {
+                Value::Finite(n, value) => {
+                    let k = value.round();
+                    if (value - k).abs() >= EPSILON {
+                        return Value::finite(Node::Function(function, vec![n.clone()]));
+                    }
+                    // At an integer the side the argument comes from decides
+                    let offset = Node::Sub(
+                        Box::new(arguments[0].clone()),
+                        Box::new(Node::Number(Number::real(k))),
+                    );
+                    let result = match (function, self.sign_near(&offset)) {
+                        (Function::Floor, Some(sign)) if sign < 0.0 => k - 1.0,
+                        (Function::Ceil, Some(sign)) if sign > 0.0 => k + 1.0,
+                        (_, Some(_)) => k,
+                        (_, None) => return Ok(Value::Undefined),
+                    };
+                    Value::finite(Node::Number(Number::real(result)))
+                }
+                Value::PositiveInfinity | Value::NegativeInfinity | Value::Undefined => {
+                    Ok(value.clone())
+                }
+                Value::Bounded => Err(LimitError::Undetermined),
+            };
+        }
+
+        let arguments = values
+            .into_iter()
+            .map(|value| match value {
+                Value::Finite(node, _) => Ok(node),
+                _ => Err(LimitError::Undetermined),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Value::finite(Node::Function(function, arguments))
+    }
+}
+
+/// The limit of `e^u` from the limit of `u`
+fn exp(value: Value) -> Result<Value, LimitError> {
+    Ok(match value {
+        Value::Finite(n, _) => Value::finite(Node::Pow(Box::new(Node::EConstant), Box::new(n)))?,
+        Value::PositiveInfinity => Value::PositiveInfinity,
+        Value::NegativeInfinity => Value::finite(Node::Number(Number::real(0.0)))?,
+        Value::Bounded => Value::Bounded,
+        Value::Undefined => Value::Undefined,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    fn limit_at(input: &str, at: &str, direction: Direction) -> String {
+        limit(&parsed(input), "x", &Point::At(parsed(at)), direction)
+            .unwrap_or_else(|error| panic!("{}: {}", input, error))
+            .to_string()
+    }
+
+    fn limit_at_infinity(input: &str, var: &str, point: Point) -> String {
+        limit(&parsed(input), var, &point, Direction::Both)
+            .unwrap_or_else(|error| panic!("{}: {}", input, error))
+            .to_string()
+    }
+
+    #[test]
+    fn test_finite_points() {
+        assert_eq!(limit_at("x^2 - 3", "2", Direction::Both), "1");
+        assert_eq!(limit_at("sin(x)/x", "0", Direction::Both), "1");
+        assert_eq!(limit_at("(1 - cos(x))/x^2", "0", Direction::Both), "1/2");
+        assert_eq!(limit_at("(x^2 - 1)/(x - 1)", "1", Direction::Both), "2");
+        assert_eq!(limit_at("1/x - 1/sin(x)", "0", Direction::Both), "0");
+        // Deeper than L'Hôpital's rule goes, so this comes from the series
+        assert_eq!(
+            limit_at(
+                "(cos(x) - 1 + x^2/2 - x^4/24 + x^6/720 - x^8/40320)/x^10",
+                "0",
+                Direction::Both
+            ),
+            "-1/3628800"
+        );
+    }
+
+    #[test]
+    fn test_one_sided() {
+        assert_eq!(limit_at("x*ln(x)", "0", Direction::Right), "0");
+        assert_eq!(limit_at("x^x", "0", Direction::Right), "1");
+        assert_eq!(limit_at("ln(x)", "0", Direction::Right), "-infinity");
+        assert_eq!(limit_at("1/x", "0", Direction::Right), "infinity");
+        assert_eq!(limit_at("1e-13/x", "0", Direction::Right), "infinity");
+        assert_eq!(limit_at("1/x", "0", Direction::Left), "-infinity");
+        assert_eq!(limit_at("1/x", "0", Direction::Both), "does not exist");
+        assert_eq!(limit_at("1/x^2", "0", Direction::Both), "infinity");
+        assert_eq!(limit_at("floor(x)", "2", Direction::Right), "2");
+        assert_eq!(limit_at("floor(x)", "2", Direction::Left), "1");
+        assert_eq!(limit_at("floor(x)", "2", Direction::Both), "does not exist");
+        assert_eq!(
+            limit_at("sin(1/x)", "0", Direction::Right),
+            "does not exist"
+        );
+    }
+
+    #[test]
+    fn test_infinity() {
+        assert_eq!(
+            limit_at_infinity("(1 + 1/n)^n", "n", Point::PositiveInfinity),
+            "e"
+        );
+        assert_eq!(
+            limit_at_infinity("(2*x^2 + 1)/(x^2 - x)", "x", Point::PositiveInfinity),
+            "2"
+        );
+        assert_eq!(
+            limit_at_infinity("e^x/x^3", "x", Point::PositiveInfinity),
+            "infinity"
+        );
+        assert_eq!(
+            limit_at_infinity("x*e^x", "x", Point::NegativeInfinity),
+            "0"
+        );
+        assert_eq!(
+            limit_at_infinity("x^3", "x", Point::NegativeInfinity),
+            "-infinity"
+        );
+        assert_eq!(
+            limit_at_infinity("1e-13*x", "x", Point::PositiveInfinity),
+            "infinity"
+        );
+        assert_eq!(
+            limit_at_infinity("sin(x)/x", "x", Point::PositiveInfinity),
+            "0"
+        );
+        assert_eq!(
+            limit_at_infinity("sin(x)", "x", Point::PositiveInfinity),
+            "does not exist"
+        );
+        // The ratio of the derivatives, 1 + cos(x), has no limit, but the quotient does
+        assert_eq!(
+            limit_at_infinity("(x + sin(x))/x", "x", Point::PositiveInfinity),
+            "1"
+        );
+        // ∞ - ∞ where L'Hôpital's rule keeps going back and forth, from the series in 1/x
+        assert_eq!(
+            limit_at_infinity("sqrt(x^2 + 1) - x", "x", Point::PositiveInfinity),
+            "0"
+        );
+        assert_eq!(
+            limit_at_infinity("x - sqrt(x^2 + x)", "x", Point::PositiveInfinity),
+            "-1/2"
+        );
+        assert_eq!(
+            limit_at_infinity("x + sqrt(x^2 + x)", "x", Point::NegativeInfinity),
+            "-1/2"
+        );
+        assert_eq!(
+            limit_at_infinity("sqrt(x^2 + x) - x + x^2", "x", Point::PositiveInfinity),
+            "infinity"
+        );
+        assert_eq!(
+            limit(&parsed("x"), "x", &Point::At(parsed("y")), Direction::Both),
+            Err(LimitError::InvalidPoint)
+        );
+    }
+}
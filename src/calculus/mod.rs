@@ -0,0 +1,68 @@
+use crate::parser::Node;
+
+mod derivative;
+mod implicit;
+mod integral;
+mod limit;
+mod ode;
+mod optimize;
+mod partial;
+mod polynomial;
+mod quadrature;
+mod roots;
+mod series;
+mod sum;
+
+pub use derivative::diff;
+pub use implicit::{implicit_diff, ImplicitError};
+pub use integral::{integrate, Integral};
+pub use limit::{limit, Direction, Limit, LimitError, Point};
+pub use ode::{Ode, OdeError, Sample};
+pub use optimize::{Algorithm, Derivatives, Minimizer, Minimum, OptimizeError, Status};
+pub use partial::{gradient, hessian, jacobian, partial, variables};
+pub use quadrature::{integrate_numeric, Quadrature, QuadratureError};
+pub use roots::{bisection, brent, find_root, find_roots, newton, Method, Root, RootError, Start};
+pub use series::{series, Series, SeriesError};
+pub use sum::{product, sum};
+
+/// Whether the variable appears anywhere in the node
+pub(crate) fn depends_on(node: &Node, var: &str) -> bool {
+    match node {
+        Node::Variable(name) => name == var,
+        Node::Number(_) | Node::PiConstant | Node::EConstant | Node::IConstant => false,
+        Node::Add(l, r) | Node::Sub(l, r) | Node::Mul(l, r) | Node::Div(l, r) | Node::Pow(l, r) => {
+            depends_on(l, var) || depends_on(r, var)
+        }
+        Node::Exp(n)
+        | Node::Log(n)
+        | Node::Sin(n)
+        | Node::Cos(n)
+        | Node::Tan(n)
+        | Node::Sqrt(n)
+        | Node::Neg(n) => depends_on(n, var),
+        // The index of a sum or product is bound inside it
+        Node::Function(function, arguments) if function.binds_index() => {
+            let is_index = arguments[1] == Node::Variable(var.to_string());
+            !is_index && depends_on(&arguments[0], var)
+                || arguments[2..].iter().any(|a| depends_on(a, var))
+        }
+        Node::Function(_, arguments) => arguments.iter().any(|a| depends_on(a, var)),
+    }
+}
+
+/// Splits a sum into its terms
+pub(crate) fn summands(node: &Node, negated: bool, out: &mut Vec<Node>) {
+    match node {
+        Node::Add(l, r) => {
+            summands(l, negated, out);
+            summands(r, negated, out);
+        }
+        Node::Sub(l, r) => {
+            summands(l, negated, out);
+            summands(r, !negated, out);
+        }
+        Node::Neg(n) => summands(n, !negated, out),
+        _ if negated => out.push(Node::Neg(Box::new(node.clone()))),
+        _ => out.push(node.clone()),
+    }
+}
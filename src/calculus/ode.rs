@@ -0,0 +1,617 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use thiserror::Error;
+
+use super::{depends_on, diff, integrate, summands, variables};
+use crate::eval::{evaluate, EvalError};
+use crate::parser::{derivative_order, Equation, Node};
+use crate::simplify::simplify;
+use crate::Number;
+
+/// The relative accuracy to aim for in every step
+const RELATIVE_TOLERANCE: f64 = 1e-8;
+
+/// The absolute accuracy to aim for, for components that are zero or close to it
+const ABSOLUTE_TOLERANCE: f64 = 1e-10;
+
+/// How many steps, accepted or rejected, the numeric solver may take
+const MAX_STEPS: usize = 100_000;
+
+/// The nodes of the Dormand-Prince method
+const C: [f64; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+
+/// The Runge-Kutta matrix of the Dormand-Prince method, the last row is also the fifth order
+/// weights
+const A: [[f64; 6]; 7] = [
+    [0.0; 6],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+        0.0,
+        0.0,
+    ],
+    [
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+        0.0,
+    ],
+    [
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+
+/// The embedded fourth order weights, which are only used to estimate the error
+const B: [f64; 7] = [
+    5179.0 / 57600.0,
+    0.0,
+    7571.0 / 16695.0,
+    393.0 / 640.0,
+    -92097.0 / 339200.0,
+    187.0 / 2100.0,
+    1.0 / 40.0,
+];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum OdeError {
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+    #[error("The equation isn't an ordinary differential equation like y' = f(t, y)")]
+    InvalidEquation,
+    #[error("Expected {0} initial values, found {1}")]
+    InitialValues(usize, usize),
+    #[error("The right hand side is complex at {0}")]
+    Complex(f64),
+    #[error("The step size became too small at {0}")]
+    StepSizeTooSmall(f64),
+    #[error("Took too many steps, the last one ended at {0}")]
+    TooManySteps(f64),
+    #[error("The equation isn't separable or linear, or an integral couldn't be done")]
+    NoClosedForm,
+}
+
+/// An explicit ordinary differential equation `y^(n) = f(t, y, y', ..., y^(n-1))`, where the
+/// right hand side is any node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ode {
+    function: String,
+    var: String,
+    order: usize,
+    rhs: Node,
+}
+
+/// The state of the solution at one point, `[y, y', ..., y^(n-1)]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    t: f64,
+    state: Vec<f64>,
+}
+
+impl Sample {
+    pub fn t(&self) -> f64 {
+        self.t
+    }
+
+    /// The value of the function itself
+    pub fn value(&self) -> f64 {
+        self.state[0]
+    }
+
+    /// The function and its derivatives up to one less than the order of the equation
+    pub fn state(&self) -> &[f64] {
+        &self.state
+    }
+}
+
+impl Ode {
+    /// The equation `function^(order) = rhs` with `var` as the independent variable
+    ///
+    /// Derivatives of the function are the variables `y'`, `y''` and so on, and the right hand
+    /// side may only use the ones below the order.
+    pub fn new(function: &str, var: &str, order: usize, rhs: Node) -> Result<Ode, OdeError> {
+        let is_valid = order > 0
+            && function != var
+            && variables(&rhs)
+                .iter()
+                .all(|name| match derivative_order(name) {
+                    (f, n) if f == var => n == 0,
+                    (f, n) => f != function || n < order,
+                });
+        if !is_valid {
+            return Err(OdeError::InvalidEquation);
+        }
+        Ok(Ode {
+            function: function.to_string(),
+            var: var.to_string(),
+            order,
+            rhs,
+        })
+    }
+
+    /// The equation with a derivative like `y''` alone on its left side
+    pub fn from_equation(equation: &Equation, var: &str) -> Result<Ode, OdeError> {
+        let Node::Variable(name) = equation.lhs() else {
+            return Err(OdeError::InvalidEquation);
+        };
+        let (function, order) = derivative_order(name);
+        Ode::new(function, var, order, equation.rhs().clone())
+    }
+
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    pub fn var(&self) -> &str {
+        &self.var
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn rhs(&self) -> &Node {
+        &self.rhs
+    }
+
+    /// The name of the `n`th derivative of the function
+    fn derivative(&self, n: usize) -> String {
+        format!("{}{}", self.function, "'".repeat(n))
+    }
+
+    /// Integrates the equation numerically from `t0` to `t1`, starting from the initial values
+    /// `[y, y', ..., y^(n-1)]` at `t0`
+    ///
+    /// This is the adaptive Dormand-Prince RK45 method. Every accepted step gives a sample, so
+    /// the samples are dense where the solution changes quickly, and the last one is at `t1`.
+    /// Any other variables in the right hand side are taken from `parameters`.
+    pub fn solve_numeric(
+        &self,
+        t0: f64,
+        initial: &[f64],
+        t1: f64,
+        parameters: &HashMap<String, f64>,
+    ) -> Result<Vec<Sample>, OdeError> {
+        if initial.len() != self.order {
+            return Err(OdeError::InitialValues(self.order, initial.len()));
+        }
+        if !t0.is_finite() || !t1.is_finite() || initial.iter().any(|y| !y.is_finite()) {
+            return Err(EvalError::NaN.into());
+        }
+
+        let mut variables: HashMap<String, Number> = parameters
+            .iter()
+            .map(|(name, value)| (name.clone(), Number::real(*value)))
+            .collect();
+        let mut f = |t: f64, state: &[f64]| -> Result<Vec<f64>, OdeError> {
+            variables.insert(self.var.clone(), Number::real(t));
+            for (n, value) in state.iter().enumerate() {
+                variables.insert(self.derivative(n), Number::real(*value));
+            }
+            let value = evaluate(&self.rhs, &variables)?;
+            if value.im().abs() > 1e-12 * value.re().abs().max(1.0) {
+                return Err(OdeError::Complex(t));
+            }
+            let mut derivatives = state[1..].to_vec();
+            derivatives.push(value.re());
+            Ok(derivatives)
+        };
+
+        let mut t = t0;
+        let mut state = initial.to_vec();
+        let mut samples = vec![Sample {
+            t,
+            state: state.clone(),
+        }];
+        if t0 == t1 {
+            return Ok(samples);
+        }
+
+        let direction = (t1 - t0).signum();
+        let mut h = (t1 - t0) / 100.0;
+        let mut k = vec![f(t, &state)?];
+        for _ in 0..MAX_STEPS {
+            h = h.abs().min((t1 - t).abs()) * direction;
+
+            k.truncate(1);
+            for i in 1..7 {
+                let stage: Vec<f64> = (0..state.len())
+                    .map(|j| state[j] + h * (0..i).map(|m| A[i][m] * k[m][j]).sum::<f64>())
+                    .collect();
+                k.push(f(t + C[i] * h, &stage)?);
+            }
+            // The last stage is evaluated at the fifth order solution, so it is also the first
+            // stage of the next step
+            let next: Vec<f64> = (0..state.len())
+                .map(|j| state[j] + h * (0..6).map(|m| A[6][m] * k[m][j]).sum::<f64>())
+                .collect();
+
+            let error = (0..state.len())
+                .map(|j| {
+                    let estimate = h * (0..6).map(|m| (A[6][m] - B[m]) * k[m][j]).sum::<f64>()
+                        - h * B[6] * k[6][j];
+                    let scale =
+                        ABSOLUTE_TOLERANCE + RELATIVE_TOLERANCE * state[j].abs().max(next[j].abs());
+                    (estimate / scale).powi(2)
+                })
+                .sum::<f64>()
+                / state.len() as f64;
+            let error = error.sqrt();
+
+            if error <= 1.0 && next.iter().all(|y| y.is_finite()) {
+                let is_last = (t + h - t1) * direction >= 0.0;
+                t = if is_last { t1 } else { t + h };
+                state = next;
+                samples.push(Sample {
+                    t,
+                    state: state.clone(),
+                });
+                if is_last {
+                    return Ok(samples);
+                }
+                k.swap(0, 6);
+            }
+
+            let factor = if error.is_finite() {
+                (0.9 * error.powf(-0.2)).clamp(0.2, 5.0)
+            } else {
+                0.2
+            };
+            h *= factor;
+            // The step is too small once it no longer moves t or is negligible next to the span
+            if t + h == t || h.abs() <= 1e-14 * (t1 - t0).abs() {
+                return Err(OdeError::StepSizeTooSmall(t));
+            }
+        }
+        Err(OdeError::TooManySteps(t))
+    }
+
+    /// The general solution of a first order equation that is linear or separable, with an
+    /// arbitrary constant `C`
+    ///
+    /// The left side of the returned equation is the function when the solution could be solved
+    /// for it, otherwise the solution is implicit like `-1/y + ln(y) = t + C`. Logarithms from
+    /// separating the variables are written `ln(y)` rather than `ln(|y|)`.
+    pub fn solve(&self) -> Result<Equation, OdeError> {
+        if self.order != 1 {
+            return Err(OdeError::NoClosedForm);
+        }
+        let constant = self.constant();
+        self.linear(&constant)
+            .or_else(|| self.separable(&constant))
+            .ok_or(OdeError::NoClosedForm)
+    }
+
+    /// A name for the arbitrary constant that isn't used in the equation
+    fn constant(&self) -> Node {
+        let names = variables(&self.rhs);
+        let name = (0..)
+            .map(|i| match i {
+                0 => "C".to_string(),
+                i => format!("C{}", i),
+            })
+            .find(|name| !names.contains(name) && *name != self.var && *name != self.function)
+            .expect("there are infinitely many names");
+        Node::Variable(name)
+    }
+
+    /// `y' = p(t)·y + q(t)` is `y = e^P·(∫q·e^-P + C)` with `P = ∫p`
+    fn linear(&self, constant: &Node) -> Option<Equation> {
+        let y = Node::Variable(self.function.clone());
+        let p = simplify(&diff(&self.rhs, &self.function));
+        let q = simplify(&Node::Sub(
+            Box::new(self.rhs.clone()),
+            Box::new(Node::Mul(Box::new(p.clone()), Box::new(y.clone()))),
+        ));
+        if depends_on(&p, &self.function) || depends_on(&q, &self.function) {
+            return None;
+        }
+
+        let integral = integrate(&p, &self.var).node()?.clone();
+        let factor = exponential(&integral);
+        let inverse = exponential(&simplify(&Node::Neg(Box::new(integral))));
+        let rest = integrate(&Node::Mul(Box::new(q), Box::new(inverse)), &self.var);
+
+        // The factor is multiplied into every term so that it cancels with the inverse
+        let mut terms = vec![];
+        summands(rest.node()?, false, &mut terms);
+        terms.push(constant.clone());
+        let solution = terms
+            .into_iter()
+            .map(|term| simplify(&Node::Mul(Box::new(factor.clone()), Box::new(term))))
+            .reduce(|l, r| Node::Add(Box::new(l), Box::new(r)))
+            .expect("there is at least the constant");
+        Some(Equation::new(y, simplify(&solution)))
+    }
+
+    /// `y' = g(t)·h(y)` is `∫1/h(y) dy = ∫g(t) dt + C`, which is solved for `y` when the left
+    /// side can be inverted
+    fn separable(&self, constant: &Node) -> Option<Equation> {
+        let (g, h) = separate(&self.rhs, &self.var, &self.function)?;
+        let reciprocal = Node::Div(Box::new(Node::Number(Number::real(1.0))), Box::new(h));
+        let lhs = integrate(&reciprocal, &self.function).node()?.clone();
+        let rhs = Node::Add(
+            Box::new(integrate(&g, &self.var).node()?.clone()),
+            Box::new(constant.clone()),
+        );
+
+        Some(match isolate(&lhs, rhs.clone(), &self.function) {
+            Some(solution) => {
+                Equation::new(Node::Variable(self.function.clone()), simplify(&solution))
+            }
+            None => Equation::new(lhs, simplify(&rhs)),
+        })
+    }
+}
+
+impl fmt::Display for Ode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}", self.derivative(self.order), self.rhs)
+    }
+}
+
+/// `e^node`, where `e^(c·ln(u))` is written `u^c`
+fn exponential(node: &Node) -> Node {
+    match log_power(node) {
+        Some((c, u)) => simplify(&Node::Pow(Box::new(u), Box::new(c))),
+        None => simplify(&Node::Exp(Box::new(node.clone()))),
+    }
+}
+
+/// Splits `c·ln(u)` for a number `c` into `(c, u)`
+fn log_power(node: &Node) -> Option<(Node, Node)> {
+    match node {
+        Node::Log(u) => Some((Node::Number(Number::real(1.0)), u.as_ref().clone())),
+        Node::Neg(n) => {
+            let (c, u) = log_power(n)?;
+            Some((simplify(&Node::Neg(Box::new(c))), u))
+        }
+        Node::Mul(c, n) if matches!(**c, Node::Number(_)) => {
+            let (d, u) = log_power(n)?;
+            Some((simplify(&Node::Mul(c.clone(), Box::new(d))), u))
+        }
+        _ => None,
+    }
+}
+
+/// Splits the node into `g(t)·h(y)`
+fn separate(node: &Node, t: &str, y: &str) -> Option<(Node, Node)> {
+    let one = || Node::Number(Number::real(1.0));
+    if !depends_on(node, y) {
+        return Some((node.clone(), one()));
+    }
+    if !depends_on(node, t) {
+        return Some((one(), node.clone()));
+    }
+    let mul = |l: Node, r: Node| Node::Mul(Box::new(l), Box::new(r));
+    let div = |l: Node, r: Node| Node::Div(Box::new(l), Box::new(r));
+    match node {
+        Node::Mul(l, r) => {
+            let ((gl, hl), (gr, hr)) = (separate(l, t, y)?, separate(r, t, y)?);
+            Some((mul(gl, gr), mul(hl, hr)))
+        }
+        Node::Div(l, r) => {
+            let ((gl, hl), (gr, hr)) = (separate(l, t, y)?, separate(r, t, y)?);
+            Some((div(gl, gr), div(hl, hr)))
+        }
+        Node::Neg(n) => {
+            let (g, h) = separate(n, t, y)?;
+            Some((Node::Neg(Box::new(g)), h))
+        }
+        // e^(a + b) = e^a·e^b
+        Node::Exp(n) => match n.as_ref() {
+            Node::Add(l, r) => separate(&mul(Node::Exp(l.clone()), Node::Exp(r.clone())), t, y),
+            Node::Sub(l, r) => separate(&div(Node::Exp(l.clone()), Node::Exp(r.clone())), t, y),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Solves `lhs = rhs` for `var` by undoing the operations around its only occurrence in `lhs`
+fn isolate(lhs: &Node, rhs: Node, var: &str) -> Option<Node> {
+    let b = Box::new;
+    let constant = |node: &Node| !depends_on(node, var);
+    match lhs {
+        Node::Variable(name) if name == var => Some(rhs),
+        Node::Neg(n) => isolate(n, Node::Neg(b(rhs)), var),
+        Node::Add(l, r) if constant(l) => isolate(r, Node::Sub(b(rhs), l.clone()), var),
+        Node::Add(l, r) if constant(r) => isolate(l, Node::Sub(b(rhs), r.clone()), var),
+        Node::Sub(l, r) if constant(l) => isolate(r, Node::Sub(l.clone(), b(rhs)), var),
+        Node::Sub(l, r) if constant(r) => isolate(l, Node::Add(b(rhs), r.clone()), var),
+        Node::Mul(l, r) if constant(l) => isolate(r, Node::Div(b(rhs), l.clone()), var),
+        Node::Mul(l, r) if constant(r) => isolate(l, Node::Div(b(rhs), r.clone()), var),
+        Node::Div(l, r) if constant(l) => isolate(r, Node::Div(l.clone(), b(rhs)), var),
+        Node::Div(l, r) if constant(r) => isolate(l, Node::Mul(b(rhs), r.clone()), var),
+        // The principal root, so y^2 = c gives only the positive branch
+        Node::Pow(base, exponent) if constant(exponent) => {
+            let root = Node::Div(b(Node::Number(Number::real(1.0))), exponent.clone());
+            isolate(base, Node::Pow(b(rhs), b(root)), var)
+        }
+        Node::Pow(base, exponent) if constant(base) => isolate(
+            exponent,
+            Node::Div(b(Node::Log(b(rhs))), b(Node::Log(base.clone()))),
+            var,
+        ),
+        Node::Sqrt(n) => isolate(
+            n,
+            Node::Pow(b(rhs), b(Node::Number(Number::real(2.0)))),
+            var,
+        ),
+        Node::Exp(n) => isolate(n, Node::Log(b(rhs)), var),
+        Node::Log(n) => isolate(n, Node::Exp(b(rhs)), var),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse_equation;
+
+    fn ode(input: &str) -> Ode {
+        Ode::from_equation(&parse_equation(&tokenize(input).unwrap()).unwrap(), "t").unwrap()
+    }
+
+    /// Checks that the explicit solution satisfies the equation for a few values of `C`, which are
+    /// positive since the solutions take principal roots and logarithms
+    fn assert_solution(input: &str) {
+        let ode = ode(input);
+        let solution = ode.solve().unwrap();
+        assert_eq!(
+            solution.lhs(),
+            &Node::Variable("y".to_string()),
+            "{}",
+            solution
+        );
+        let derivative = diff(solution.rhs(), "t");
+        for (t, c) in [(0.3, 1.0), (0.7, 2.0), (1.9, 0.5)] {
+            let mut variables = HashMap::from([
+                ("t".to_string(), Number::real(t)),
+                ("C".to_string(), Number::real(c)),
+                ("k".to_string(), Number::real(2.0)),
+            ]);
+            let y = evaluate(solution.rhs(), &variables).unwrap();
+            let actual = evaluate(&derivative, &variables).unwrap().value();
+            variables.insert("y".to_string(), y);
+            let expected = evaluate(ode.rhs(), &variables).unwrap().value();
+            assert!(
+                (expected - actual).abs() < 1e-9 * expected.abs().max(1.0),
+                "{} doesn't solve {}, y' is {} at {} but should be {}",
+                solution,
+                input,
+                actual,
+                t,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_equation() {
+        let equation = ode("y'' = -y - y'/10");
+        assert_eq!(equation.function(), "y");
+        assert_eq!(equation.var(), "t");
+        assert_eq!(equation.order(), 2);
+        assert_eq!(equation.to_string(), "y'' = -y - y'/10");
+
+        for input in ["y = t", "y' = y''", "2*y' = y", "t' = t"] {
+            assert_eq!(
+                Ode::from_equation(&parse_equation(&tokenize(input).unwrap()).unwrap(), "t"),
+                Err(OdeError::InvalidEquation),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_solve_numeric() {
+        let parameters = HashMap::from([("k".to_string(), 0.5)]);
+        let samples = ode("y' = -k*y")
+            .solve_numeric(0.0, &[2.0], 4.0, &parameters)
+            .unwrap();
+        let last = samples.last().unwrap();
+        assert_eq!(last.t(), 4.0);
+        assert!((last.value() - 2.0 * (-2f64).exp()).abs() < 1e-7);
+        assert!(samples.windows(2).all(|pair| pair[0].t() < pair[1].t()));
+
+        // y = sin(t) solves y'' = -y, and the samples also carry y' = cos(t)
+        let samples = ode("y'' = -y")
+            .solve_numeric(0.0, &[0.0, 1.0], 10.0, &HashMap::new())
+            .unwrap();
+        for sample in &samples {
+            assert!((sample.value() - sample.t().sin()).abs() < 1e-6);
+            assert!((sample.state()[1] - sample.t().cos()).abs() < 1e-6);
+        }
+
+        let samples = ode("y' = t")
+            .solve_numeric(2.0, &[0.0], -2.0, &HashMap::new())
+            .unwrap();
+        assert!(samples.last().unwrap().value().abs() < 1e-9);
+
+        // A short span only needs steps that are short next to it
+        let samples = ode("y' = t")
+            .solve_numeric(0.0, &[0.0], 1e-20, &HashMap::new())
+            .unwrap();
+        let last = samples.last().unwrap();
+        assert_eq!(last.t(), 1e-20);
+        assert!((last.value() - 5e-41).abs() < 1e-9 * 5e-41);
+    }
+
+    #[test]
+    fn test_solve_numeric_errors() {
+        let equation = ode("y' = -k*y + sin(t)");
+        assert_eq!(
+            equation.solve_numeric(0.0, &[1.0], 1.0, &HashMap::new()),
+            Err(EvalError::UnboundVariable("k".to_string()).into())
+        );
+        assert_eq!(
+            equation.solve_numeric(0.0, &[1.0, 0.0], 1.0, &HashMap::new()),
+            Err(OdeError::InitialValues(1, 2))
+        );
+        // y = 1/(1 - t) blows up at t = 1
+        assert!(matches!(
+            ode("y' = y^2").solve_numeric(0.0, &[1.0], 2.0, &HashMap::new()),
+            Err(OdeError::StepSizeTooSmall(t)) if (t - 1.0).abs() < 1e-3
+        ));
+    }
+
+    #[test]
+    fn test_linear() {
+        assert_eq!(ode("y' = y").solve().unwrap().to_string(), "y = e^t*C");
+        assert_eq!(
+            ode("y' = -k*y + sin(t)").solve().unwrap().to_string(),
+            "y = e^(-k*t)*C + (k*sin(t) - cos(t))/(k^2 + 1)"
+        );
+        assert_eq!(
+            ode("y' = y/t + t^2").solve().unwrap().to_string(),
+            "y = C*t + t^3/2"
+        );
+        for input in [
+            "y' = -k*y + sin(t)",
+            "y' = -k*y",
+            "y' = 2*y + t",
+            "y' = cos(t)",
+            "y' = -2*y/t",
+            "y' = y/t + t^2",
+        ] {
+            assert_solution(input);
+        }
+    }
+
+    #[test]
+    fn test_separable() {
+        for input in [
+            "y' = t*y^2",
+            "y' = e^(t + y)",
+            "y' = t/y",
+            "y' = sqrt(y)*cos(t)",
+            "y' = cos(t)/e^y",
+        ] {
+            assert_solution(input);
+        }
+        assert_eq!(
+            ode("y' = t*y^2").solve().unwrap().to_string(),
+            "y = 1/(-C - t^2/2)"
+        );
+        assert_eq!(
+            ode("y' = t/(y + e^y)").solve().unwrap().to_string(),
+            "y^2/2 + e^y = C + t^2/2"
+        );
+        assert_eq!(ode("y' = sin(t*y)").solve(), Err(OdeError::NoClosedForm));
+    }
+}
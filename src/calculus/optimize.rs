@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::{diff, variables};
+use crate::eval::gradient::gradient;
+use crate::eval::{evaluate, EvalError};
+use crate::parser::Node;
+use crate::Number;
+
+/// How many halvings the line search tries before giving up on a direction
+const MAX_HALVINGS: usize = 60;
+
+/// The sufficient decrease constant of the Armijo condition
+const ARMIJO: f64 = 1e-4;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum OptimizeError {
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+    #[error("The expression isn't a finite real number at the starting point")]
+    InvalidStart,
+    #[error("The lower bound of {0} is above its upper bound or a bound is NaN")]
+    InvalidBounds(String),
+}
+
+/// How BFGS gets its gradients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Derivatives {
+    /// Partial derivatives computed once with [`diff`] and evaluated at every point
+    Symbolic,
+    /// Reverse mode automatic differentiation with [`gradient`]
+    Automatic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// The quasi-Newton method, projected onto the bounds
+    Bfgs(Derivatives),
+    /// The derivative-free simplex method, with trial points clamped to the bounds
+    NelderMead,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Converged,
+    /// Stopped after the maximum number of iterations
+    MaxIterations,
+    /// The line search found no lower point along the search direction, or the gradient isn't
+    /// finite at the last point
+    Stalled,
+}
+
+/// The result of [`Minimizer::minimize`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimum {
+    point: HashMap<String, f64>,
+    value: f64,
+    iterations: usize,
+    status: Status,
+}
+
+impl Minimum {
+    /// The minimiser, the value of every variable
+    pub fn point(&self) -> &HashMap<String, f64> {
+        &self.point
+    }
+
+    /// The value of the expression at the minimiser
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+}
+
+/// Options for minimising an expression over its free variables
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimizer {
+    algorithm: Algorithm,
+    bounds: HashMap<String, (f64, f64)>,
+    max_iterations: usize,
+    tolerance: f64,
+}
+
+impl Default for Minimizer {
+    fn default() -> Minimizer {
+        Minimizer::new(Algorithm::Bfgs(Derivatives::Automatic))
+    }
+}
+
+impl Minimizer {
+    pub fn new(algorithm: Algorithm) -> Minimizer {
+        Minimizer {
+            algorithm,
+            bounds: HashMap::new(),
+            max_iterations: 1000,
+            tolerance: 1e-10,
+        }
+    }
+
+    /// Keeps `var` in [lower, upper], either bound may be infinite
+    pub fn with_bounds(mut self, var: &str, lower: f64, upper: f64) -> Minimizer {
+        self.bounds.insert(var.to_string(), (lower, upper));
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Minimizer {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Convergence is reached when the gradient, or the spread of the simplex values for
+    /// Nelder-Mead, is below this
+    pub fn with_tolerance(mut self, tolerance: f64) -> Minimizer {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Looks for a local minimum of the node starting from `start`
+    ///
+    /// Variables missing from `start` begin in the middle of their bounds, or at the finite
+    /// bound or 0 when they aren't bounded on both sides. Points where the expression isn't a
+    /// finite real number, like `ln(x)` for negative `x`, are treated as infinitely high so the
+    /// search stays out of them, but the starting point has to be a valid one.
+    pub fn minimize(
+        &self,
+        node: &Node,
+        start: &HashMap<String, f64>,
+    ) -> Result<Minimum, OptimizeError> {
+        let names = variables(node);
+        let bounds = names
+            .iter()
+            .map(|name| {
+                let (lower, upper) = self
+                    .bounds
+                    .get(name)
+                    .copied()
+                    .unwrap_or((f64::NEG_INFINITY, f64::INFINITY));
+                if lower.is_nan() || upper.is_nan() || lower > upper {
+                    return Err(OptimizeError::InvalidBounds(name.clone()));
+                }
+                Ok((lower, upper))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let x: Vec<f64> = names
+            .iter()
+            .zip(&bounds)
+            .map(|(name, &(lower, upper))| {
+                let x = start.get(name).copied().unwrap_or_else(|| {
+                    match (lower.is_finite(), upper.is_finite()) {
+                        (true, true) => lower + (upper - lower) / 2.0,
+                        (true, false) => lower,
+                        (false, true) => upper,
+                        (false, false) => 0.0,
+                    }
+                });
+                x.clamp(lower, upper)
+            })
+            .collect();
+
+        let problem = Problem {
+            node,
+            names: &names,
+            bounds: &bounds,
+            partials: match self.algorithm {
+                Algorithm::Bfgs(Derivatives::Symbolic) => {
+                    names.iter().map(|name| diff(node, name)).collect()
+                }
+                _ => vec![],
+            },
+        };
+        if !problem.value(&x).is_finite() {
+            problem.try_value(&x)?;
+            return Err(OptimizeError::InvalidStart);
+        }
+
+        let (x, value, iterations, status) = match self.algorithm {
+            Algorithm::Bfgs(derivatives) => self.bfgs(&problem, x, derivatives),
+            Algorithm::NelderMead => self.nelder_mead(&problem, x),
+        };
+        Ok(Minimum {
+            point: names.iter().cloned().zip(x).collect(),
+            value,
+            iterations,
+            status,
+        })
+    }
+
+    /// Projected BFGS, variables held at a bound by the gradient are left out of the step
+    ///
+    /// The search stalls at a point where the gradient isn't finite, the value there is still
+    /// finite, so it's the best point found.
+    fn bfgs(
+        &self,
+        problem: &Problem,
+        mut x: Vec<f64>,
+        derivatives: Derivatives,
+    ) -> (Vec<f64>, f64, usize, Status) {
+        let n = x.len();
+        let identity = || -> Vec<Vec<f64>> {
+            (0..n)
+                .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+                .collect()
+        };
+        let mut h = identity();
+        let mut fx = problem.value(&x);
+        let Some(mut g) = problem.gradient(&x, derivatives) else {
+            return (x, fx, 0, Status::Stalled);
+        };
+
+        for iteration in 0..self.max_iterations {
+            let free: Vec<bool> = (0..n)
+                .map(|i| {
+                    let (lower, upper) = problem.bounds[i];
+                    !((x[i] <= lower && g[i] > 0.0) || (x[i] >= upper && g[i] < 0.0))
+                })
+                .collect();
+            let projected: Vec<f64> = (0..n).map(|i| if free[i] { g[i] } else { 0.0 }).collect();
+            if norm(&projected) <= self.tolerance * (1.0 + fx.abs()) {
+                return (x, fx, iteration, Status::Converged);
+            }
+
+            let mut direction = direction(&h, &projected, &free);
+            if dot(&direction, &projected) >= 0.0 {
+                h = identity();
+                direction = projected.iter().map(|g| -g).collect();
+            }
+
+            let Some((next, f_next)) = problem.line_search(&x, fx, &g, &direction) else {
+                if h != identity() {
+                    h = identity();
+                    continue;
+                }
+                return (x, fx, iteration, Status::Stalled);
+            };
+            let Some(g_next) = problem.gradient(&next, derivatives) else {
+                return (next, f_next, iteration + 1, Status::Stalled);
+            };
+            let s: Vec<f64> = (0..n).map(|i| next[i] - x[i]).collect();
+            let y: Vec<f64> = (0..n).map(|i| g_next[i] - g[i]).collect();
+            update(&mut h, &s, &y);
+
+            let small_step = (0..n).all(|i| s[i].abs() <= 1e-14 * (1.0 + x[i].abs()));
+            (x, fx, g) = (next, f_next, g_next);
+            if small_step {
+                return (x, fx, iteration + 1, Status::Converged);
+            }
+        }
+        (x, fx, self.max_iterations, Status::MaxIterations)
+    }
+
+    /// Nelder-Mead with the standard coefficients, every trial point is clamped into the bounds
+    fn nelder_mead(&self, problem: &Problem, x: Vec<f64>) -> (Vec<f64>, f64, usize, Status) {
+        let n = x.len();
+        let mut simplex: Vec<(Vec<f64>, f64)> = vec![(x.clone(), problem.value(&x))];
+        for i in 0..n {
+            let mut vertex = x.clone();
+            let step = 0.1 * x[i].abs().max(1.0);
+            vertex[i] = problem.clamp(i, x[i] + step);
+            if vertex[i] == x[i] {
+                vertex[i] = problem.clamp(i, x[i] - step);
+            }
+            let value = problem.value(&vertex);
+            simplex.push((vertex, value));
+        }
+
+        let point = |from: &[f64], to: &[f64], t: f64| -> Vec<f64> {
+            (0..n)
+                .map(|i| problem.clamp(i, from[i] + t * (to[i] - from[i])))
+                .collect()
+        };
+        for iteration in 0..self.max_iterations {
+            simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+            let (best, worst) = (simplex[0].1, simplex[n].1);
+            let size = simplex
+                .iter()
+                .flat_map(|(vertex, _)| (0..n).map(|i| (vertex[i] - simplex[0].0[i]).abs()))
+                .fold(0.0, f64::max);
+            if (worst - best).abs() <= self.tolerance * (1.0 + best.abs())
+                && size <= 1e-8 * (1.0 + norm(&simplex[0].0))
+            {
+                let (x, value) = simplex.swap_remove(0);
+                return (x, value, iteration, Status::Converged);
+            }
+
+            let centroid: Vec<f64> = (0..n)
+                .map(|i| simplex[..n].iter().map(|(v, _)| v[i]).sum::<f64>() / n as f64)
+                .collect();
+            let reflected = point(&centroid, &simplex[n].0, -1.0);
+            let f_reflected = problem.value(&reflected);
+            if f_reflected < best {
+                let expanded = point(&centroid, &simplex[n].0, -2.0);
+                let f_expanded = problem.value(&expanded);
+                simplex[n] = if f_expanded < f_reflected {
+                    (expanded, f_expanded)
+                } else {
+                    (reflected, f_reflected)
+                };
+            } else if f_reflected < simplex[n - 1].1 {
+                simplex[n] = (reflected, f_reflected);
+            } else {
+                // Contract towards the better of the worst and the reflected point
+                let (outside, f_outside) = if f_reflected < worst {
+                    (&reflected, f_reflected)
+                } else {
+                    (&simplex[n].0, worst)
+                };
+                let contracted = point(&centroid, outside, 0.5);
+                let f_contracted = problem.value(&contracted);
+                if f_contracted < f_outside {
+                    simplex[n] = (contracted, f_contracted);
+                } else {
+                    let best = simplex[0].0.clone();
+                    for (vertex, value) in simplex.iter_mut().skip(1) {
+                        *vertex = point(&best, vertex, 0.5);
+                        *value = problem.value(vertex);
+                    }
+                }
+            }
+        }
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (x, value) = simplex.swap_remove(0);
+        (x, value, self.max_iterations, Status::MaxIterations)
+    }
+}
+
+/// The expression with its variables in a fixed order
+struct Problem<'a> {
+    node: &'a Node,
+    names: &'a [String],
+    bounds: &'a [(f64, f64)],
+    /// The symbolic partial derivatives, empty unless they are used
+    partials: Vec<Node>,
+}
+
+impl Problem<'_> {
+    fn variables(&self, x: &[f64]) -> HashMap<String, Number> {
+        self.names
+            .iter()
+            .zip(x)
+            .map(|(name, &x)| (name.clone(), Number::real(x)))
+            .collect()
+    }
+
+    fn try_value(&self, x: &[f64]) -> Result<f64, EvalError> {
+        let value = evaluate(self.node, &self.variables(x))?;
+        Ok(if value.is_real() {
+            value.re()
+        } else {
+            f64::NAN
+        })
+    }
+
+    /// The value at a point, infinite where the expression isn't a finite real number
+    fn value(&self, x: &[f64]) -> f64 {
+        match self.try_value(x) {
+            Ok(value) if value.is_finite() => value,
+            _ => f64::INFINITY,
+        }
+    }
+
+    /// The gradient at a point, None where it isn't finite, like that of `sqrt(x)` at 0
+    fn gradient(&self, x: &[f64], derivatives: Derivatives) -> Option<Vec<f64>> {
+        let g: Vec<f64> = match derivatives {
+            Derivatives::Symbolic => {
+                let variables = self.variables(x);
+                self.partials
+                    .iter()
+                    .map(|partial| Some(evaluate(partial, &variables).ok()?.re()))
+                    .collect::<Option<_>>()?
+            }
+            Derivatives::Automatic => {
+                let point = self.names.iter().cloned().zip(x.iter().copied()).collect();
+                let gradient = gradient(self.node, &point).ok()?;
+                self.names
+                    .iter()
+                    .map(|name| gradient.partial(name))
+                    .collect()
+            }
+        };
+        g.iter().all(|g| g.is_finite()).then_some(g)
+    }
+
+    fn clamp(&self, i: usize, x: f64) -> f64 {
+        let (lower, upper) = self.bounds[i];
+        x.clamp(lower, upper)
+    }
+
+    /// Backtracks along the projected path `clamp(x + t*direction)` until the Armijo condition
+    /// holds
+    fn line_search(
+        &self,
+        x: &[f64],
+        fx: f64,
+        g: &[f64],
+        direction: &[f64],
+    ) -> Option<(Vec<f64>, f64)> {
+        let mut t = 1.0;
+        for _ in 0..MAX_HALVINGS {
+            let next: Vec<f64> = (0..x.len())
+                .map(|i| self.clamp(i, x[i] + t * direction[i]))
+                .collect();
+            let step: Vec<f64> = (0..x.len()).map(|i| next[i] - x[i]).collect();
+            let f_next = self.value(&next);
+            let decrease = dot(g, &step);
+            if decrease < 0.0 && f_next <= fx + ARMIJO * decrease {
+                return Some((next, f_next));
+            }
+            t /= 2.0;
+        }
+        None
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// `-H g` restricted to the free variables
+fn direction(h: &[Vec<f64>], g: &[f64], free: &[bool]) -> Vec<f64> {
+    (0..g.len())
+        .map(|i| {
+            if !free[i] {
+                return 0.0;
+            }
+            -(0..g.len())
+                .filter(|&j| free[j])
+                .map(|j| h[i][j] * g[j])
+                .sum::<f64>()
+        })
+        .collect()
+}
+
+/// The BFGS update of the inverse Hessian approximation, skipped when the curvature condition
+/// fails
+fn update(h: &mut [Vec<f64>], s: &[f64], y: &[f64]) {
+    let sy = dot(s, y);
+    if sy <= 1e-12 * norm(s) * norm(y) {
+        return;
+    }
+    let n = s.len();
+    let hy: Vec<f64> = (0..n).map(|i| dot(&h[i], y)).collect();
+    let yhy = dot(y, &hy);
+    // H + (sy + yHy) ss^T / (sy)^2 - (Hy s^T + s y^T H) / sy
+    for i in 0..n {
+        for j in 0..n {
+            h[i][j] += (sy + yhy) * s[i] * s[j] / (sy * sy) - (hy[i] * s[j] + s[i] * hy[j]) / sy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    const ROSENBROCK: &str = "(1 - x)^2 + 100*(y - x^2)^2";
+
+    fn minimized(minimizer: &Minimizer, input: &str, start: &[(&str, f64)]) -> Minimum {
+        let node = parse(&tokenize(input).unwrap()).unwrap();
+        let start = start
+            .iter()
+            .map(|(name, x)| (name.to_string(), *x))
+            .collect();
+        minimizer.minimize(&node, &start).unwrap()
+    }
+
+    fn assert_minimum(minimum: &Minimum, point: &[(&str, f64)], value: f64, tolerance: f64) {
+        assert_eq!(minimum.status(), Status::Converged, "{:?}", minimum);
+        assert!((minimum.value() - value).abs() < tolerance, "{:?}", minimum);
+        for (name, x) in point {
+            assert!(
+                (minimum.point()[*name] - x).abs() < tolerance,
+                "{:?}",
+                minimum
+            );
+        }
+    }
+
+    #[test]
+    fn test_rosenbrock() {
+        let start = [("x", -1.2), ("y", 1.0)];
+        for algorithm in [
+            Algorithm::Bfgs(Derivatives::Symbolic),
+            Algorithm::Bfgs(Derivatives::Automatic),
+            Algorithm::NelderMead,
+        ] {
+            let minimum = minimized(&Minimizer::new(algorithm), ROSENBROCK, &start);
+            assert_minimum(&minimum, &[("x", 1.0), ("y", 1.0)], 0.0, 1e-4);
+        }
+        let bfgs = minimized(&Minimizer::default(), ROSENBROCK, &start);
+        let nelder_mead = minimized(&Minimizer::new(Algorithm::NelderMead), ROSENBROCK, &start);
+        assert!(bfgs.iterations() < nelder_mead.iterations());
+    }
+
+    #[test]
+    fn test_sums() {
+        for algorithm in [
+            Algorithm::Bfgs(Derivatives::Symbolic),
+            Algorithm::Bfgs(Derivatives::Automatic),
+            Algorithm::NelderMead,
+        ] {
+            let minimum = minimized(&Minimizer::new(algorithm), "sum(x^k, k, 1, 2)", &[]);
+            assert_minimum(&minimum, &[("x", -0.5)], -0.25, 1e-6);
+            assert!(minimum.point().get("k").is_none());
+        }
+    }
+
+    #[test]
+    fn test_bounds() {
+        for algorithm in [
+            Algorithm::Bfgs(Derivatives::Symbolic),
+            Algorithm::NelderMead,
+        ] {
+            let minimizer = Minimizer::new(algorithm)
+                .with_bounds("x", 0.0, 2.0)
+                .with_bounds("y", 0.0, f64::INFINITY);
+            let minimum = minimized(&minimizer, "(x - 3)^2 + (y + 1)^2 + x*y", &[]);
+            assert_minimum(&minimum, &[("x", 2.0), ("y", 0.0)], 2.0, 1e-6);
+        }
+
+        // The gradient of sqrt(x) is infinite at the minimum on the bound, where BFGS stalls
+        for (algorithm, status) in [
+            (Algorithm::Bfgs(Derivatives::Symbolic), Status::Stalled),
+            (Algorithm::Bfgs(Derivatives::Automatic), Status::Stalled),
+            (Algorithm::NelderMead, Status::Converged),
+        ] {
+            let minimizer = Minimizer::new(algorithm).with_bounds("x", 0.0, 4.0);
+            let minimum = minimized(&minimizer, "sqrt(x)", &[]);
+            assert_eq!(minimum.status(), status, "{:?}", minimum);
+            assert!(minimum.point()["x"] < 1e-12, "{:?}", minimum);
+            assert!(minimum.value() < 1e-6, "{:?}", minimum);
+        }
+
+        let node = parse(&tokenize("x^2").unwrap()).unwrap();
+        assert_eq!(
+            Minimizer::default()
+                .with_bounds("x", 1.0, -1.0)
+                .minimize(&node, &HashMap::new()),
+            Err(OptimizeError::InvalidBounds("x".to_string()))
+        );
+        for algorithm in [
+            Algorithm::Bfgs(Derivatives::Symbolic),
+            Algorithm::NelderMead,
+        ] {
+            assert_eq!(
+                Minimizer::new(algorithm)
+                    .with_bounds("x", f64::NAN, 4.0)
+                    .minimize(&node, &HashMap::new()),
+                Err(OptimizeError::InvalidBounds("x".to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        // The search has to stay where ln is real
+        let minimum = minimized(&Minimizer::default(), "x - ln(x)", &[("x", 3.0)]);
+        assert_minimum(&minimum, &[("x", 1.0)], 1.0, 1e-8);
+
+        let minimizer = Minimizer::new(Algorithm::NelderMead).with_max_iterations(5);
+        let minimum = minimized(&minimizer, ROSENBROCK, &[("x", -1.2), ("y", 1.0)]);
+        assert_eq!(minimum.status(), Status::MaxIterations);
+        assert_eq!(minimum.iterations(), 5);
+
+        let node = parse(&tokenize("ln(x)").unwrap()).unwrap();
+        let start = HashMap::from([("x".to_string(), -1.0)]);
+        assert_eq!(
+            Minimizer::default().minimize(&node, &start),
+            Err(OptimizeError::InvalidStart)
+        );
+    }
+}
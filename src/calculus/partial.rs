@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use super::diff;
+use crate::parser::Node;
+
+/// The names of the variables in the node, sorted and without duplicates
+pub fn variables(node: &Node) -> Vec<String> {
+    let mut names = vec![];
+    collect_variables(node, &mut names);
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn collect_variables(node: &Node, names: &mut Vec<String>) {
+    match node {
+        Node::Variable(name) => names.push(name.clone()),
+        Node::Number(_) | Node::PiConstant | Node::EConstant | Node::IConstant => {}
+        Node::Add(l, r) | Node::Sub(l, r) | Node::Mul(l, r) | Node::Div(l, r) | Node::Pow(l, r) => {
+            collect_variables(l, names);
+            collect_variables(r, names);
+        }
+        Node::Exp(n)
+        | Node::Log(n)
+        | Node::Sin(n)
+        | Node::Cos(n)
+        | Node::Tan(n)
+        | Node::Sqrt(n)
+        | Node::Neg(n) => collect_variables(n, names),
+        Node::Function(function, arguments) if function.binds_index() => {
+            let mut summand = vec![];
+            collect_variables(&arguments[0], &mut summand);
+            names.extend(
+                summand
+                    .into_iter()
+                    .filter(|name| arguments[1] != Node::Variable(name.clone())),
+            );
+            for argument in &arguments[2..] {
+                collect_variables(argument, names);
+            }
+        }
+        Node::Function(_, arguments) => {
+            for argument in arguments {
+                collect_variables(argument, names);
+            }
+        }
+    }
+}
+
+/// A higher order or mixed partial derivative, differentiating with respect to each variable in
+/// turn, `d^3/dx^2dy` is `partial(node, &["x", "x", "y"])`
+pub fn partial(node: &Node, vars: &[&str]) -> Node {
+    vars.iter().fold(node.clone(), |node, var| diff(&node, var))
+}
+
+/// The partial derivatives with respect to every variable in the node
+pub fn gradient(node: &Node) -> HashMap<String, Node> {
+    variables(node)
+        .into_iter()
+        .map(|var| {
+            let derivative = diff(node, &var);
+            (var, derivative)
+        })
+        .collect()
+}
+
+/// One row of partial derivatives per expression, every row has the variables of all the
+/// expressions so a constraint that doesn't use `y` still has a zero `y` entry
+pub fn jacobian(nodes: &[Node]) -> Vec<HashMap<String, Node>> {
+    let mut names: Vec<String> = nodes.iter().flat_map(variables).collect();
+    names.sort();
+    names.dedup();
+
+    nodes
+        .iter()
+        .map(|node| {
+            names
+                .iter()
+                .map(|var| (var.clone(), diff(node, var)))
+                .collect()
+        })
+        .collect()
+}
+
+/// The second partial derivatives, `hessian(f)["x"]["y"]` is `d^2f/dxdy`
+///
+/// Mixed partials of the smooth functions an expression is made of don't depend on the order, so
+/// only one of each pair is computed.
+pub fn hessian(node: &Node) -> HashMap<String, HashMap<String, Node>> {
+    let names = variables(node);
+    let first: Vec<Node> = names.iter().map(|var| diff(node, var)).collect();
+
+    let mut hessian: HashMap<String, HashMap<String, Node>> = HashMap::new();
+    for (i, x) in names.iter().enumerate() {
+        for y in &names[i..] {
+            let second = diff(&first[i], y);
+            hessian
+                .entry(y.clone())
+                .or_default()
+                .insert(x.clone(), second.clone());
+            hessian
+                .entry(x.clone())
+                .or_default()
+                .insert(y.clone(), second);
+        }
+    }
+    hessian
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_mixed_partials() {
+        let node = parsed("x^3*y^2 + sin(y)");
+        assert_eq!(partial(&node, &["x", "x", "y"]).to_string(), "12*x*y");
+        assert_eq!(partial(&node, &["y", "y"]).to_string(), "2*x^3 - sin(y)");
+        assert_eq!(partial(&node, &[]), node);
+    }
+
+    #[test]
+    fn test_bound_index() {
+        assert_eq!(variables(&parsed("sum(k*x, k, 1, n) + k")), ["k", "n", "x"]);
+        assert_eq!(variables(&parsed("product(k, k, 1, n)")), ["n"]);
+    }
+
+    #[test]
+    fn test_gradient_and_jacobian() {
+        let gradient = gradient(&parsed("x^2 + x*y"));
+        assert_eq!(gradient.len(), 2);
+        assert_eq!(gradient["x"].to_string(), "2*x + y");
+        assert_eq!(gradient["y"].to_string(), "x");
+
+        let jacobian = jacobian(&[parsed("x*y"), parsed("x + z")]);
+        assert_eq!(jacobian.len(), 2);
+        assert_eq!(jacobian[0]["z"].to_string(), "0");
+        assert_eq!(jacobian[0]["y"].to_string(), "x");
+        assert_eq!(jacobian[1]["z"].to_string(), "1");
+    }
+
+    #[test]
+    fn test_hessian() {
+        let hessian = hessian(&parsed("x^2*y + y^3"));
+        assert_eq!(hessian["x"]["x"].to_string(), "2*y");
+        assert_eq!(hessian["x"]["y"].to_string(), "2*x");
+        assert_eq!(hessian["y"]["x"], hessian["x"]["y"]);
+        assert_eq!(hessian["y"]["y"].to_string(), "6*y");
+    }
+}
@@ -0,0 +1,375 @@
+use super::depends_on;
+use crate::parser::Node;
+use crate::simplify::coefficient::Coefficient;
+use crate::simplify::simplify;
+
+/// Divisors of the constant and leading coefficients are only searched for roots up to this size
+const MAX_ROOT_SEARCH: i64 = 1_000_000;
+
+/// A polynomial in one variable with numeric coefficients, `coefficients[i]` belongs to `x^i`
+///
+/// Arithmetic that overflows or can't be done exactly fails with None rather than losing digits.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Polynomial {
+    coefficients: Vec<Coefficient>,
+}
+
+impl Polynomial {
+    fn new(mut coefficients: Vec<Coefficient>) -> Polynomial {
+        while coefficients.last().is_some_and(|c| c.is_zero()) {
+            coefficients.pop();
+        }
+        Polynomial { coefficients }
+    }
+
+    pub fn constant(value: Coefficient) -> Polynomial {
+        Polynomial::new(vec![value])
+    }
+
+    /// `x - root`
+    pub fn linear(root: Coefficient) -> Polynomial {
+        Polynomial::new(vec![root.neg(), Coefficient::integer(1)])
+    }
+
+    /// Reads a polynomial in `var`, None if the node isn't one or a coefficient isn't a number
+    pub fn from_node(node: &Node, var: &str) -> Option<Polynomial> {
+        if !depends_on(node, var) {
+            return Some(Polynomial::constant(Coefficient::from_node(&simplify(
+                node,
+            ))?));
+        }
+        match node {
+            Node::Variable(_) => Some(Polynomial::new(vec![
+                Coefficient::integer(0),
+                Coefficient::integer(1),
+            ])),
+            Node::Add(l, r) => Polynomial::from_node(l, var)?.add(&Polynomial::from_node(r, var)?),
+            Node::Sub(l, r) => Polynomial::from_node(l, var)?.sub(&Polynomial::from_node(r, var)?),
+            Node::Neg(n) => Some(Polynomial::from_node(n, var)?.neg()),
+            Node::Mul(l, r) => Polynomial::from_node(l, var)?.mul(&Polynomial::from_node(r, var)?),
+            Node::Div(l, r) if !depends_on(r, var) => {
+                let divisor = Coefficient::from_node(&simplify(r))?.recip().ok()?;
+                Polynomial::from_node(l, var)?.scale(divisor)
+            }
+            Node::Pow(base, exponent) => {
+                let n = Coefficient::from_node(&simplify(exponent))?.as_integer()?;
+                if !(0..=64).contains(&n) {
+                    return None;
+                }
+                let base = Polynomial::from_node(base, var)?;
+                (0..n).try_fold(Polynomial::constant(Coefficient::integer(1)), |acc, _| {
+                    acc.mul(&base)
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_node(&self, var: &str) -> Node {
+        let terms = self.coefficients.iter().enumerate().map(|(i, c)| {
+            Node::Mul(
+                Box::new(c.to_node()),
+                Box::new(Node::Pow(
+                    Box::new(Node::Variable(var.to_string())),
+                    Box::new(Coefficient::integer(i as i64).to_node()),
+                )),
+            )
+        });
+        let sum = terms.reduce(|acc, term| Node::Add(Box::new(acc), Box::new(term)));
+        simplify(&sum.unwrap_or_else(|| Coefficient::integer(0).to_node()))
+    }
+
+    /// The degree, the zero polynomial has degree 0 as well
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    fn coefficient(&self, i: usize) -> Coefficient {
+        self.coefficients
+            .get(i)
+            .copied()
+            .unwrap_or(Coefficient::integer(0))
+    }
+
+    fn leading(&self) -> Coefficient {
+        self.coefficient(self.degree())
+    }
+
+    pub fn add(&self, other: &Polynomial) -> Option<Polynomial> {
+        let len = self.coefficients.len().max(other.coefficients.len());
+        let coefficients = (0..len)
+            .map(|i| self.coefficient(i).add(other.coefficient(i)).ok())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Polynomial::new(coefficients))
+    }
+
+    pub fn neg(&self) -> Polynomial {
+        Polynomial::new(self.coefficients.iter().map(|c| c.neg()).collect())
+    }
+
+    pub fn sub(&self, other: &Polynomial) -> Option<Polynomial> {
+        self.add(&other.neg())
+    }
+
+    pub fn scale(&self, factor: Coefficient) -> Option<Polynomial> {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .map(|c| c.mul(factor).ok())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Polynomial::new(coefficients))
+    }
+
+    pub fn mul(&self, other: &Polynomial) -> Option<Polynomial> {
+        if self.is_zero() || other.is_zero() {
+            return Some(Polynomial::new(vec![]));
+        }
+        let mut coefficients = vec![Coefficient::integer(0); self.degree() + other.degree() + 1];
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in other.coefficients.iter().enumerate() {
+                coefficients[i + j] = coefficients[i + j].add(a.mul(*b).ok()?).ok()?;
+            }
+        }
+        Some(Polynomial::new(coefficients))
+    }
+
+    /// Long division, None when dividing by zero
+    pub fn div_rem(&self, divisor: &Polynomial) -> Option<(Polynomial, Polynomial)> {
+        let lead = divisor.leading().recip().ok()?;
+        let mut remainder = self.clone();
+        let mut quotient = vec![Coefficient::integer(0); self.coefficients.len()];
+        while !remainder.is_zero() && remainder.degree() >= divisor.degree() {
+            let shift = remainder.degree() - divisor.degree();
+            let factor = remainder.leading().mul(lead).ok()?;
+            quotient[shift] = factor;
+            let mut term = vec![Coefficient::integer(0); shift];
+            term.push(factor);
+            let subtracted = remainder.sub(&divisor.mul(&Polynomial::new(term))?)?;
+            // The leading term cancels exactly, even if the arithmetic fell back to floats
+            let mut coefficients = subtracted.coefficients;
+            coefficients.truncate(remainder.degree());
+            remainder = Polynomial::new(coefficients);
+        }
+        Some((Polynomial::new(quotient), remainder))
+    }
+
+    pub fn evaluate(&self, x: Coefficient) -> Option<Coefficient> {
+        self.coefficients
+            .iter()
+            .rev()
+            .try_fold(Coefficient::integer(0), |acc, c| {
+                acc.mul(x).ok()?.add(*c).ok()
+            })
+    }
+
+    /// The antiderivative without a constant term
+    pub fn integral(&self) -> Option<Polynomial> {
+        let mut coefficients = vec![Coefficient::integer(0)];
+        for (i, c) in self.coefficients.iter().enumerate() {
+            coefficients.push(c.mul(Coefficient::rational(1, i as i64 + 1)).ok()?);
+        }
+        Some(Polynomial::new(coefficients))
+    }
+
+    /// The polynomial `P` with `P(n) = p(0) + p(1) + ... + p(n - 1)`, from Faulhaber's formula
+    /// for the sums of powers
+    pub fn antidifference(&self) -> Option<Polynomial> {
+        let degree = self.coefficients.len();
+
+        // Row m + 1 of Pascal's triangle and the Bernoulli numbers with B_1 = -1/2
+        let mut binomials = vec![vec![Coefficient::integer(1)]];
+        for m in 1..=degree + 1 {
+            let previous: &Vec<Coefficient> = &binomials[m - 1];
+            let mut row = vec![Coefficient::integer(1)];
+            for j in 1..m {
+                row.push(previous[j - 1].add(previous[j]).ok()?);
+            }
+            row.push(Coefficient::integer(1));
+            binomials.push(row);
+        }
+        let mut bernoulli = vec![Coefficient::integer(1)];
+        for m in 1..degree {
+            let mut sum = Coefficient::integer(0);
+            for (j, b) in bernoulli.iter().enumerate() {
+                sum = sum.add(binomials[m + 1][j].mul(*b).ok()?).ok()?;
+            }
+            let factor = Coefficient::rational(-1, m as i64 + 1);
+            bernoulli.push(sum.mul(factor).ok()?);
+        }
+
+        // 0^m + ... + (n - 1)^m = (C(m + 1, 0) B_0 n^(m + 1) + ... + C(m + 1, m) B_m n) / (m + 1)
+        let mut result = Polynomial::new(vec![]);
+        for (m, c) in self.coefficients.iter().enumerate() {
+            let mut coefficients = vec![Coefficient::integer(0); m + 2];
+            for (j, b) in bernoulli.iter().take(m + 1).enumerate() {
+                coefficients[m + 1 - j] = binomials[m + 1][j].mul(*b).ok()?;
+            }
+            let factor = c.mul(Coefficient::rational(1, m as i64 + 1)).ok()?;
+            result = result.add(&Polynomial::new(coefficients).scale(factor)?)?;
+        }
+        Some(result)
+    }
+
+    pub fn derivative(&self) -> Option<Polynomial> {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, c)| c.mul(Coefficient::integer(i as i64)).ok())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Polynomial::new(coefficients))
+    }
+
+    /// The polynomial in `h` with `p(x) = q(h)` for `x = at + h`, the Taylor coefficients at `at`
+    pub fn shifted(&self, at: Coefficient) -> Option<Polynomial> {
+        let shift = Polynomial::new(vec![at, Coefficient::integer(1)]);
+        self.coefficients
+            .iter()
+            .rev()
+            .try_fold(Polynomial::new(vec![]), |acc, c| {
+                acc.mul(&shift)?.add(&Polynomial::constant(*c))
+            })
+    }
+
+    /// The first `n` coefficients of the power series of `self / other` around 0
+    pub fn series_div(&self, other: &Polynomial, n: usize) -> Option<Vec<Coefficient>> {
+        let first = other.coefficient(0).recip().ok()?;
+        let mut series: Vec<Coefficient> = vec![];
+        for j in 0..n {
+            let mut value = self.coefficient(j);
+            for (i, s) in series.iter().enumerate() {
+                value = value
+                    .add(other.coefficient(j - i).mul(*s).ok()?.neg())
+                    .ok()?;
+            }
+            series.push(value.mul(first).ok()?);
+        }
+        Some(series)
+    }
+
+    /// The rational roots with their multiplicities, if they are all of the roots
+    pub fn rational_roots(&self) -> Option<Vec<(Coefficient, usize)>> {
+        let mut rest = self.clone();
+        let mut roots: Vec<(Coefficient, usize)> = vec![];
+        while rest.degree() > 0 {
+            let root = rest.rational_root()?;
+            rest = rest.div_rem(&Polynomial::linear(root))?.0;
+            match roots.iter_mut().find(|(r, _)| *r == root) {
+                Some((_, multiplicity)) => *multiplicity += 1,
+                None => roots.push((root, 1)),
+            }
+        }
+        Some(roots)
+    }
+
+    /// A root `p/q` from the rational root theorem, with `p` dividing the constant and `q` the
+    /// leading coefficient once the coefficients are scaled to integers
+    fn rational_root(&self) -> Option<Coefficient> {
+        if self.coefficient(0).is_zero() {
+            return Some(Coefficient::integer(0));
+        }
+        let mut denominators = self.coefficients.iter().map(|c| match c {
+            Coefficient::Rational(_, den) => Some(*den),
+            Coefficient::Real(_) => None,
+        });
+        let scale = denominators.try_fold(1i64, |acc, den| {
+            let den = den?;
+            acc.checked_mul(den / gcd(acc, den))
+        })?;
+        let integers = self.scale(Coefficient::integer(scale))?;
+        let constant = integers.coefficient(0).as_integer()?.checked_abs()?;
+        let leading = integers.leading().as_integer()?.checked_abs()?;
+        if constant > MAX_ROOT_SEARCH || leading > MAX_ROOT_SEARCH {
+            return None;
+        }
+
+        for p in divisors(constant) {
+            for q in divisors(leading) {
+                for candidate in [Coefficient::rational(p, q), Coefficient::rational(-p, q)] {
+                    if self.evaluate(candidate)?.is_zero() {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn divisors(n: i64) -> Vec<i64> {
+    (1..=n)
+        .take_while(|d| d * d <= n)
+        .fold(vec![], |mut divisors, d| {
+            if n % d == 0 {
+                divisors.push(d);
+                if d * d != n {
+                    divisors.push(n / d);
+                }
+            }
+            divisors
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn polynomial(input: &str) -> Polynomial {
+        Polynomial::from_node(&parse(&tokenize(input).unwrap()).unwrap(), "x").unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let p = polynomial("(x + 1)^2 * (2*x - 3)/2");
+        assert_eq!(p.degree(), 3);
+        assert_eq!(p.to_node("x").to_string(), "x^3 + x^2/2 - 2*x - 3/2");
+        let (quotient, remainder) = polynomial("x^3 - 1").div_rem(&polynomial("x - 1")).unwrap();
+        assert_eq!(quotient, polynomial("x^2 + x + 1"));
+        assert!(remainder.is_zero());
+        assert!(Polynomial::from_node(&parse(&tokenize("x^-1").unwrap()).unwrap(), "x").is_none());
+    }
+
+    #[test]
+    fn test_rational_roots() {
+        let roots = polynomial("2*x^3 - 3*x^2 + 1").rational_roots().unwrap();
+        assert_eq!(
+            roots,
+            vec![
+                (Coefficient::integer(1), 2),
+                (Coefficient::rational(-1, 2), 1)
+            ]
+        );
+        assert!(polynomial("x^2 + 1").rational_roots().is_none());
+    }
+
+    #[test]
+    fn test_antidifference() {
+        assert_eq!(
+            polynomial("x").antidifference().unwrap(),
+            polynomial("x^2/2 - x/2")
+        );
+        let p = polynomial("x^5 - 2*x^2 + 7");
+        let sum = p.antidifference().unwrap();
+        for n in 0..10 {
+            let expected = (0..n).try_fold(Coefficient::integer(0), |acc, j| {
+                acc.add(p.evaluate(Coefficient::integer(j)).unwrap()).ok()
+            });
+            assert_eq!(sum.evaluate(Coefficient::integer(n)), expected);
+        }
+    }
+}
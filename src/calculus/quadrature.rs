@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::eval::{evaluate, EvalError};
+use crate::parser::Node;
+use crate::Number;
+
+/// The relative accuracy to aim for
+const RELATIVE_TOLERANCE: f64 = 1e-10;
+
+/// The absolute accuracy to aim for, for integrals that are zero or close to it
+const ABSOLUTE_TOLERANCE: f64 = 1e-12;
+
+/// How many subintervals the adaptive quadrature may split the interval into
+const MAX_INTERVALS: usize = 2000;
+
+/// The nodes of the 15 point Kronrod rule on [-1, 1], the odd ones are the 7 point Gauss nodes
+const KRONROD_NODES: [f64; 8] = [
+    0.991_455_371_120_812_6,
+    0.949_107_912_342_758_5,
+    0.864_864_423_359_769_1,
+    0.741_531_185_599_394_4,
+    0.586_087_235_467_691_1,
+    0.405_845_151_377_397_2,
+    0.207_784_955_007_898_5,
+    0.0,
+];
+
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022_935_322_010_529_22,
+    0.063_092_092_629_978_55,
+    0.104_790_010_322_250_18,
+    0.140_653_259_715_525_92,
+    0.169_004_726_639_267_9,
+    0.190_350_578_064_785_4,
+    0.204_432_940_075_298_9,
+    0.209_482_141_084_727_83,
+];
+
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.129_484_966_168_869_7,
+    0.279_705_391_489_276_7,
+    0.381_830_050_505_118_9,
+    0.417_959_183_673_469_4,
+];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum QuadratureError {
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+    #[error("The integrand is complex at {0}")]
+    Complex(f64),
+    #[error("The integral doesn't converge, the last estimate was {0} ± {1}")]
+    NotConverged(f64, f64),
+}
+
+/// The value of a definite integral together with an estimate of its absolute error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quadrature {
+    value: f64,
+    error: f64,
+}
+
+impl Quadrature {
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// An estimate of the absolute error of the value
+    pub fn error(&self) -> f64 {
+        self.error
+    }
+}
+
+/// Integrates the node numerically with respect to `var` from `a` to `b`
+///
+/// This is adaptive Gauss-Kronrod quadrature, which never evaluates the integrand at the ends of
+/// an interval. Infinite bounds are mapped to a finite interval with `x = a + t/(1 - t)`, the
+/// whole real line is split at 0 and both halves have to converge on their own. An end where the
+/// integrand can't be evaluated is treated as a singularity and smoothed with
+/// `x = a + (b - a)*t^2`, so integrals like `1/sqrt(x)` on [0, 1] converge quickly. Use it for
+/// definite integrals that [`integrate`](super::integrate) can't do symbolically.
+pub fn integrate_numeric(
+    node: &Node,
+    var: &str,
+    a: f64,
+    b: f64,
+) -> Result<Quadrature, QuadratureError> {
+    if a.is_nan() || b.is_nan() {
+        return Err(EvalError::NaN.into());
+    }
+    if a == b {
+        return Ok(Quadrature {
+            value: 0.0,
+            error: 0.0,
+        });
+    }
+    if a > b {
+        let result = integrate_numeric(node, var, b, a)?;
+        return Ok(Quadrature {
+            value: -result.value,
+            ..result
+        });
+    }
+
+    let mut variables = HashMap::new();
+    let mut f = |x: f64| -> Result<f64, QuadratureError> {
+        variables.insert(var.to_string(), Number::real(x));
+        let value = evaluate(node, &variables)?;
+        if value.im().abs() > 1e-12 * value.re().abs().max(1.0) {
+            return Err(QuadratureError::Complex(x));
+        }
+        Ok(value.re())
+    };
+
+    match (a.is_finite(), b.is_finite()) {
+        (true, true) => finite(&mut f, a, b),
+        (true, false) => {
+            // x = a + t/(1 - t), dx = dt/(1 - t)^2
+            let left = is_singular(&mut f, a);
+            let mut g = |t: f64| Ok(unbounded(f(a + t / (1.0 - t)))? / ((1.0 - t) * (1.0 - t)));
+            singular(&mut g, 0.0, 1.0, left, false)
+        }
+        (false, true) => {
+            let left = is_singular(&mut f, b);
+            let mut g = |t: f64| Ok(unbounded(f(b - t / (1.0 - t)))? / ((1.0 - t) * (1.0 - t)));
+            singular(&mut g, 0.0, 1.0, left, false)
+        }
+        (false, false) => {
+            // Both halves have to converge on their own, integrating f(x) + f(-x) would give the
+            // divergent x a principal value of 0
+            let left = integrate_numeric(node, var, a, 0.0)?;
+            let right = integrate_numeric(node, var, 0.0, b)?;
+            Ok(Quadrature {
+                value: left.value + right.value,
+                error: left.error + right.error,
+            })
+        }
+    }
+}
+
+/// An integrand that overflows far out on an infinite interval grows too fast to converge, like
+/// `x^3`
+fn unbounded(value: Result<f64, QuadratureError>) -> Result<f64, QuadratureError> {
+    value.map_err(|error| match error {
+        QuadratureError::Eval(EvalError::Overflow) => {
+            QuadratureError::NotConverged(f64::INFINITY, f64::INFINITY)
+        }
+        error => error,
+    })
+}
+
+fn is_singular(f: &mut impl FnMut(f64) -> Result<f64, QuadratureError>, x: f64) -> bool {
+    !f(x).is_ok_and(f64::is_finite)
+}
+
+fn finite(
+    f: &mut impl FnMut(f64) -> Result<f64, QuadratureError>,
+    a: f64,
+    b: f64,
+) -> Result<Quadrature, QuadratureError> {
+    let (left, right) = (is_singular(f, a), is_singular(f, b));
+    singular(f, a, b, left, right)
+}
+
+/// Removes integrable singularities at the ends with `x = a + (b - a)*t^2`, splitting the interval
+/// in the middle when both ends are singular
+fn singular(
+    f: &mut impl FnMut(f64) -> Result<f64, QuadratureError>,
+    a: f64,
+    b: f64,
+    left: bool,
+    right: bool,
+) -> Result<Quadrature, QuadratureError> {
+    match (left, right) {
+        (false, false) => adaptive(f, a, b),
+        (true, false) => {
+            let width = b - a;
+            adaptive(
+                &mut |t: f64| Ok(f(a + width * t * t)? * 2.0 * width * t),
+                0.0,
+                1.0,
+            )
+        }
+        (false, true) => {
+            let width = b - a;
+            adaptive(
+                &mut |t: f64| Ok(f(b - width * t * t)? * 2.0 * width * t),
+                0.0,
+                1.0,
+            )
+        }
+        (true, true) => {
+            let middle = a + (b - a) / 2.0;
+            let l = singular(f, a, middle, true, false)?;
+            let r = singular(f, middle, b, false, true)?;
+            Ok(Quadrature {
+                value: l.value + r.value,
+                error: l.error + r.error,
+            })
+        }
+    }
+}
+
+/// The 15 point Kronrod estimate of the integral over [a, b] and its error estimate, which
+/// compares it with the 7 point Gauss rule the same way QUADPACK does
+fn kronrod(
+    f: &mut impl FnMut(f64) -> Result<f64, QuadratureError>,
+    a: f64,
+    b: f64,
+) -> Result<Quadrature, QuadratureError> {
+    let center = (a + b) / 2.0;
+    let radius = (b - a) / 2.0;
+
+    let mut values = [0.0; 15];
+    for (i, node) in KRONROD_NODES.iter().enumerate() {
+        values[i] = f(center - radius * node)?;
+        values[14 - i] = f(center + radius * node)?;
+    }
+
+    let weight = |i: usize| KRONROD_WEIGHTS[i.min(14 - i)];
+    let kronrod: f64 = (0..15).map(|i| weight(i) * values[i]).sum();
+    let gauss: f64 = (0..15)
+        .filter(|i| i % 2 == 1)
+        .map(|i| GAUSS_WEIGHTS[i.min(14 - i) / 2] * values[i])
+        .sum();
+
+    let mean = kronrod / 2.0;
+    let spread: f64 = (0..15).map(|i| weight(i) * (values[i] - mean).abs()).sum();
+    let spread = spread * radius.abs();
+    let mut error = ((kronrod - gauss) * radius).abs();
+    if spread != 0.0 && error != 0.0 {
+        error = spread * (200.0 * error / spread).powf(1.5).min(1.0);
+    }
+    Ok(Quadrature {
+        value: kronrod * radius,
+        error,
+    })
+}
+
+/// Repeatedly bisects the subinterval with the largest error until the total error is small enough
+fn adaptive(
+    f: &mut impl FnMut(f64) -> Result<f64, QuadratureError>,
+    a: f64,
+    b: f64,
+) -> Result<Quadrature, QuadratureError> {
+    let mut intervals = vec![(a, b, kronrod(f, a, b)?)];
+    loop {
+        let value: f64 = intervals.iter().map(|(_, _, q)| q.value).sum();
+        let error: f64 = intervals.iter().map(|(_, _, q)| q.error).sum();
+        if !value.is_finite() {
+            return Err(QuadratureError::NotConverged(value, error));
+        }
+        if error <= ABSOLUTE_TOLERANCE.max(RELATIVE_TOLERANCE * value.abs()) {
+            return Ok(Quadrature { value, error });
+        }
+
+        let (worst, _) = intervals
+            .iter()
+            .enumerate()
+            .max_by(|(_, (_, _, p)), (_, (_, _, q))| p.error.total_cmp(&q.error))
+            .expect("there is always an interval");
+        let (lo, hi, _) = intervals.swap_remove(worst);
+        let middle = lo + (hi - lo) / 2.0;
+        if intervals.len() + 2 > MAX_INTERVALS || middle <= lo || middle >= hi {
+            return Err(QuadratureError::NotConverged(value, error));
+        }
+        intervals.push((lo, middle, kronrod(f, lo, middle)?));
+        intervals.push((middle, hi, kronrod(f, middle, hi)?));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn integrated(input: &str, a: f64, b: f64) -> Result<Quadrature, QuadratureError> {
+        integrate_numeric(&parse(&tokenize(input).unwrap()).unwrap(), "x", a, b)
+    }
+
+    fn assert_integral(input: &str, a: f64, b: f64, expected: f64) {
+        let result = integrated(input, a, b).unwrap();
+        let actual_error = (result.value() - expected).abs();
+        assert!(
+            actual_error < 1e-9 * expected.abs().max(1.0),
+            "{} from {} to {} is {} but got {}",
+            input,
+            a,
+            b,
+            expected,
+            result.value()
+        );
+        assert!(
+            result.error() < 1e-8,
+            "{} has error estimate {}",
+            input,
+            result.error()
+        );
+    }
+
+    #[test]
+    fn test_finite_intervals() {
+        assert_integral("x^2", 0.0, 1.0, 1.0 / 3.0);
+        assert_integral("sin(x)", 0.0, PI, 2.0);
+        assert_integral("e^x", 0.0, 2.0, 2f64.exp() - 1.0);
+        assert_integral("x^2", 1.0, 0.0, -1.0 / 3.0);
+        assert_integral("sin(x)/x", 0.0, 1.0, 0.946_083_070_367_183);
+        assert_eq!(integrated("y", 3.0, 3.0).unwrap().value(), 0.0);
+    }
+
+    #[test]
+    fn test_infinite_bounds() {
+        let infinity = f64::INFINITY;
+        assert_integral("e^-x", 0.0, infinity, 1.0);
+        assert_integral("e^(-x^2)", -infinity, infinity, PI.sqrt());
+        assert_integral("1/x^2", -infinity, -1.0, 1.0);
+        assert_integral("e^-x/sqrt(x)", 0.0, infinity, PI.sqrt());
+        assert_integral("1/(1 + x^2)", -infinity, infinity, PI);
+        for odd in ["x", "x^3"] {
+            assert!(matches!(
+                integrated(odd, -infinity, infinity),
+                Err(QuadratureError::NotConverged(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_endpoint_singularities() {
+        assert_integral("1/sqrt(x)", 0.0, 1.0, 2.0);
+        assert_integral("ln(x)", 0.0, 1.0, -1.0);
+        assert_integral("1/sqrt(1 - x^2)", -1.0, 1.0, PI);
+        assert!(matches!(
+            integrated("1/x", 0.0, 1.0),
+            Err(QuadratureError::NotConverged(..))
+        ));
+        assert!(matches!(
+            integrated("sqrt(x)", -1.0, 1.0),
+            Err(QuadratureError::Complex(_))
+        ));
+        assert_eq!(
+            integrated("x*y", 0.0, 1.0),
+            Err(EvalError::UnboundVariable("y".to_string()).into())
+        );
+    }
+}
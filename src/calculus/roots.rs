@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::diff;
+use crate::eval::{evaluate, EvalError};
+use crate::parser::Node;
+use crate::Number;
+
+/// Iterations before a method gives up
+const MAX_ITERATIONS: usize = 200;
+
+/// Roots are located to within this relative distance
+const TOLERANCE: f64 = 1e-12;
+
+/// How many pieces [`find_roots`] splits the interval into when looking for sign changes
+const SCAN_STEPS: usize = 1000;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RootError {
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+    #[error("The expression is complex at {0}")]
+    Complex(f64),
+    #[error("The expression doesn't change sign between {0} and {1}")]
+    NoSignChange(f64, f64),
+    #[error("The interval from {0} to {1} isn't finite")]
+    InfiniteInterval(f64, f64),
+    #[error("{0:?} didn't converge")]
+    NotConverged(Method),
+}
+
+/// Where to look for a root
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Start {
+    /// An interval, ideally with a sign change
+    Interval(f64, f64),
+    Guess(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Brent,
+    Newton,
+    Bisection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Root {
+    value: f64,
+    iterations: usize,
+    method: Method,
+}
+
+impl Root {
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// The method that found the root
+    pub fn method(&self) -> Method {
+        self.method
+    }
+}
+
+/// Evaluates a single variable expression at real numbers
+struct Function<'a> {
+    node: &'a Node,
+    var: &'a str,
+    variables: HashMap<String, Number>,
+}
+
+impl<'a> Function<'a> {
+    fn new(node: &'a Node, var: &'a str) -> Function<'a> {
+        Function {
+            node,
+            var,
+            variables: HashMap::new(),
+        }
+    }
+
+    fn at(&mut self, x: f64) -> Result<f64, RootError> {
+        self.variables.insert(self.var.to_string(), Number::real(x));
+        let value = evaluate(self.node, &self.variables)?;
+        if value.im().abs() > 1e-12 * value.re().abs().max(1.0) {
+            return Err(RootError::Complex(x));
+        }
+        Ok(value.re())
+    }
+}
+
+/// Finds a root of the node, a function of `var`
+///
+/// An interval with a sign change is solved with Brent's method, falling back to bisection. An
+/// interval without a sign change starts Newton's method from its middle, and only a root inside
+/// the interval is accepted. From a guess Newton's method is tried first, and if it fails the
+/// guess is widened into an interval with a sign change for Brent's method.
+pub fn find_root(node: &Node, var: &str, start: Start) -> Result<Root, RootError> {
+    match start {
+        Start::Interval(a, b) => match brent(node, var, a, b) {
+            Err(RootError::NoSignChange(..)) => {
+                let root = newton(node, var, a + (b - a) / 2.0)
+                    .map_err(|_| RootError::NoSignChange(a, b))?;
+                if root.value < a.min(b) || root.value > a.max(b) {
+                    return Err(RootError::NoSignChange(a, b));
+                }
+                Ok(root)
+            }
+            Err(RootError::NotConverged(_)) => bisection(node, var, a, b),
+            result => result,
+        },
+        Start::Guess(x) => newton(node, var, x).or_else(|error| {
+            let (a, b) = bracket(node, var, x).ok_or(error)?;
+            brent(node, var, a, b).or_else(|_| bisection(node, var, a, b))
+        }),
+    }
+}
+
+/// Widens the guess on both sides until the node changes sign
+fn bracket(node: &Node, var: &str, x: f64) -> Option<(f64, f64)> {
+    let mut f = Function::new(node, var);
+    let fx = f.at(x).ok()?;
+    let mut step = 0.01 * x.abs().max(1.0);
+    for _ in 0..60 {
+        for end in [x - step, x + step] {
+            if f.at(end).is_ok_and(|value| value * fx <= 0.0) {
+                return Some((x.min(end), x.max(end)));
+            }
+        }
+        step *= 1.6;
+    }
+    None
+}
+
+/// Brent's method, inverse quadratic interpolation safeguarded by bisection, the interval has
+/// to contain a sign change
+pub fn brent(node: &Node, var: &str, a: f64, b: f64) -> Result<Root, RootError> {
+    let mut f = Function::new(node, var);
+    let root = |value, iterations| Root {
+        value,
+        iterations,
+        method: Method::Brent,
+    };
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f.at(a)?, f.at(b)?);
+    if fa == 0.0 {
+        return Ok(root(a, 0));
+    }
+    if fb == 0.0 {
+        return Ok(root(b, 0));
+    }
+    if fa.signum() == fb.signum() {
+        return Err(RootError::NoSignChange(a, b));
+    }
+
+    let (mut c, mut fc) = (b, fb);
+    let (mut d, mut e) = (b - a, b - a);
+    for iteration in 1..=MAX_ITERATIONS {
+        if fb.signum() == fc.signum() {
+            // The root is between a and b again
+            (c, fc) = (a, fa);
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            (a, b, c) = (b, c, b);
+            (fa, fb, fc) = (fb, fc, fb);
+        }
+
+        let tolerance = 2.0 * f64::EPSILON * b.abs() + 0.5 * TOLERANCE * b.abs().max(1.0);
+        let middle = 0.5 * (c - b);
+        if middle.abs() <= tolerance || fb == 0.0 {
+            return Ok(root(b, iteration));
+        }
+
+        if e.abs() >= tolerance && fa.abs() > fb.abs() {
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                // Secant step
+                (2.0 * middle * s, 1.0 - s)
+            } else {
+                // Inverse quadratic interpolation
+                let q = fa / fc;
+                let r = fb / fc;
+                (
+                    s * (2.0 * middle * q * (q - r) - (b - a) * (r - 1.0)),
+                    (q - 1.0) * (r - 1.0) * (s - 1.0),
+                )
+            };
+            if p > 0.0 {
+                q = -q;
+            } else {
+                p = -p;
+            }
+            if 2.0 * p < (3.0 * middle * q - (tolerance * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = middle;
+                e = middle;
+            }
+        } else {
+            d = middle;
+            e = middle;
+        }
+
+        (a, fa) = (b, fb);
+        b += if d.abs() > tolerance {
+            d
+        } else {
+            tolerance.copysign(middle)
+        };
+        fb = f.at(b)?;
+    }
+    Err(RootError::NotConverged(Method::Brent))
+}
+
+/// Newton's method from a guess, with the derivative computed symbolically
+pub fn newton(node: &Node, var: &str, x: f64) -> Result<Root, RootError> {
+    let derivative = diff(node, var);
+    let mut f = Function::new(node, var);
+    let mut df = Function::new(&derivative, var);
+    let mut x = x;
+    for iteration in 1..=MAX_ITERATIONS {
+        let slope = df.at(x)?;
+        let step = f.at(x)? / slope;
+        if !step.is_finite() {
+            return Err(RootError::NotConverged(Method::Newton));
+        }
+        x -= step;
+        if step.abs() <= TOLERANCE * x.abs().max(1.0) {
+            return Ok(Root {
+                value: x,
+                iterations: iteration,
+                method: Method::Newton,
+            });
+        }
+    }
+    Err(RootError::NotConverged(Method::Newton))
+}
+
+/// Bisection, slow but certain for an interval with a sign change
+pub fn bisection(node: &Node, var: &str, a: f64, b: f64) -> Result<Root, RootError> {
+    let mut f = Function::new(node, var);
+    let root = |value, iterations| Root {
+        value,
+        iterations,
+        method: Method::Bisection,
+    };
+    let (mut a, mut b) = (a, b);
+    let (fa, fb) = (f.at(a)?, f.at(b)?);
+    if fa == 0.0 {
+        return Ok(root(a, 0));
+    }
+    if fb == 0.0 {
+        return Ok(root(b, 0));
+    }
+    if fa.signum() == fb.signum() {
+        return Err(RootError::NoSignChange(a, b));
+    }
+    for iteration in 1..=MAX_ITERATIONS {
+        let middle = a + (b - a) / 2.0;
+        let fm = f.at(middle)?;
+        if fm == 0.0 || (b - a).abs() <= TOLERANCE * middle.abs().max(1.0) {
+            return Ok(root(middle, iteration));
+        }
+        if fm.signum() == fa.signum() {
+            a = middle;
+        } else {
+            b = middle;
+        }
+    }
+    Err(RootError::NotConverged(Method::Bisection))
+}
+
+/// All roots in [a, b], in ascending order
+///
+/// The interval is scanned for sign changes, which are refined with Brent's method, and for
+/// local minima of `|f|` that Newton's method refines into roots that touch zero without
+/// crossing it, like the double root of `(x - 1)^2`. A sign change at a pole like the one of
+/// `1/x` isn't a root, and points where the node can't be evaluated are skipped. Roots closer
+/// together than the scan step may be missed. Both ends have to be finite.
+pub fn find_roots(node: &Node, var: &str, a: f64, b: f64) -> Result<Vec<f64>, RootError> {
+    if !a.is_finite() || !b.is_finite() {
+        return Err(RootError::InfiniteInterval(a, b));
+    }
+    let (a, b) = (a.min(b), a.max(b));
+    let mut f = Function::new(node, var);
+    let step = (b - a) / SCAN_STEPS as f64;
+    let samples: Vec<(f64, Option<f64>)> = (0..=SCAN_STEPS)
+        .map(|i| {
+            let x = if i == SCAN_STEPS {
+                b
+            } else {
+                a + step * i as f64
+            };
+            (x, f.at(x).ok().filter(|value| value.is_finite()))
+        })
+        .collect();
+    // The residual is measured against the samples around the candidate, a pole next to a
+    // steep e^x would pass against the largest value of the whole scan
+    let is_root = |f: &mut Function, x: f64, nearby: f64| {
+        f.at(x)
+            .is_ok_and(|value| value.abs() <= 1e-9 * nearby.max(1.0))
+    };
+
+    let mut roots: Vec<f64> = vec![];
+    for (i, window) in samples.windows(2).enumerate() {
+        let [(x0, Some(f0)), (x1, Some(f1))] = *window else {
+            continue;
+        };
+        if f0 == 0.0 {
+            roots.push(x0);
+        } else if f0.signum() != f1.signum() && f1 != 0.0 {
+            if let Ok(root) = brent(node, var, x0, x1) {
+                if is_root(&mut f, root.value, f0.abs().max(f1.abs())) {
+                    roots.push(root.value);
+                }
+            }
+        } else if let Some((_, Some(f2))) = samples.get(i + 2) {
+            // A local minimum of |f| may be a root that doesn't cross zero
+            if f1.abs() < f0.abs() && f1.abs() < f2.abs() && f0.signum() == f2.signum() {
+                if let Ok(root) = newton(node, var, x1) {
+                    let nearby = f0.abs().max(f2.abs());
+                    if (root.value - x1).abs() <= step && is_root(&mut f, root.value, nearby) {
+                        roots.push(root.value);
+                    }
+                }
+            }
+        }
+    }
+    if let Some((x, Some(value))) = samples.last() {
+        if *value == 0.0 {
+            roots.push(*x);
+        }
+    }
+
+    roots.sort_by(f64::total_cmp);
+    roots.dedup_by(|x, y| (*x - *y).abs() <= step / 2.0);
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    fn root(input: &str, start: Start) -> Root {
+        find_root(&parsed(input), "x", start).unwrap()
+    }
+
+    fn assert_roots(input: &str, a: f64, b: f64, expected: &[f64]) {
+        let roots = find_roots(&parsed(input), "x", a, b).unwrap();
+        assert_eq!(roots.len(), expected.len(), "{}: {:?}", input, roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-7, "{}: {:?}", input, roots);
+        }
+    }
+
+    #[test]
+    fn test_find_root() {
+        let sqrt2 = root("x^2 - 2", Start::Interval(0.0, 2.0));
+        assert!((sqrt2.value() - 2f64.sqrt()).abs() < 1e-12);
+        assert_eq!(sqrt2.method(), Method::Brent);
+
+        let dottie = root("cos(x) - x", Start::Guess(1.0));
+        assert!((dottie.value() - 0.739_085_133_215_160_6).abs() < 1e-12);
+        assert_eq!(dottie.method(), Method::Newton);
+
+        // Newton's method cycles between 0 and 1 here
+        let cycle = root("x^3 - 2*x + 2", Start::Guess(0.0));
+        assert!((cycle.value() + 1.769_292_354_238_631).abs() < 1e-10);
+        assert_eq!(cycle.method(), Method::Brent);
+
+        // No sign change, but a double root in the interval
+        let double = root("(x - 1)^2", Start::Interval(0.0, 3.0));
+        assert!((double.value() - 1.0).abs() < 1e-6);
+        assert!(matches!(
+            find_root(&parsed("x^2 + 1"), "x", Start::Interval(-1.0, 1.0)),
+            Err(RootError::NoSignChange(..))
+        ));
+    }
+
+    #[test]
+    fn test_methods() {
+        let node = parsed("e^x - 3");
+        let expected = 3f64.ln();
+        for root in [
+            brent(&node, "x", 0.0, 2.0).unwrap(),
+            newton(&node, "x", 0.0).unwrap(),
+            bisection(&node, "x", 0.0, 2.0).unwrap(),
+        ] {
+            assert!((root.value() - expected).abs() < 1e-11, "{:?}", root);
+        }
+        assert!(
+            brent(&node, "x", 0.0, 2.0).unwrap().iterations()
+                < bisection(&node, "x", 0.0, 2.0).unwrap().iterations()
+        );
+
+        // A root at either end is found without a sign change
+        let node = parsed("x");
+        for (a, b) in [(0.0, 1.0), (-1.0, 0.0)] {
+            for root in [
+                brent(&node, "x", a, b).unwrap(),
+                bisection(&node, "x", a, b).unwrap(),
+            ] {
+                assert_eq!(root.value(), 0.0, "{:?}", root);
+                assert_eq!(root.iterations(), 0, "{:?}", root);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_roots() {
+        assert_roots("sin(x)", -1.0, 10.0, &[0.0, PI, 2.0 * PI, 3.0 * PI]);
+        assert_roots("x^3 - x", -2.0, 2.0, &[-1.0, 0.0, 1.0]);
+        assert_roots("(x - 1)^2*(x + 1)", -3.0, 3.0, &[-1.0, 1.0]);
+        assert_roots("1/x", -1.0, 1.0, &[]);
+        // e^60 is far larger than anything near the pole
+        assert_roots("e^x + 1/(x - 0.5)", 0.0, 60.0, &[]);
+        assert_roots("e^x*(x - 50)", 0.0, 60.0, &[50.0]);
+        assert_roots("x^2 + 1", -5.0, 5.0, &[]);
+
+        let node = parsed("x - 1");
+        assert_eq!(
+            find_roots(&node, "x", 0.0, f64::INFINITY),
+            Err(RootError::InfiniteInterval(0.0, f64::INFINITY))
+        );
+        assert!(matches!(
+            find_roots(&node, "x", f64::NAN, 1.0),
+            Err(RootError::InfiniteInterval(a, 1.0)) if a.is_nan()
+        ));
+    }
+}
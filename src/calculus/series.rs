@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use thiserror::Error;
+
+use super::depends_on;
+use crate::eval::evaluate;
+use crate::parser::Node;
+use crate::simplify::coefficient::Coefficient;
+use crate::simplify::simplify;
+
+/// How many terms beyond the requested order are computed, for cancellations like `sin(x) - x`
+/// and for the precision a pole in a denominator costs
+const EXTRA_TERMS: [i64; 4] = [2, 6, 14, 30];
+
+/// Terms closer to zero than this are zero
+const EPSILON: f64 = 1e-12;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SeriesError {
+    #[error("The expansion point can't depend on the variable")]
+    InvalidPoint,
+    #[error("The expression has no Laurent series at this point")]
+    NoSeries,
+}
+
+/// A truncated Taylor or Laurent series, `polynomial + O(remainder)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    polynomial: Node,
+    remainder: Node,
+}
+
+impl Series {
+    /// The terms up to the requested order, in powers of `x - point`
+    pub fn polynomial(&self) -> &Node {
+        &self.polynomial
+    }
+
+    /// The power of `x - point` in the big-O term
+    pub fn remainder(&self) -> &Node {
+        &self.remainder
+    }
+}
+
+impl fmt::Display for Series {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if Coefficient::from_node(&self.polynomial).is_some_and(Coefficient::is_zero) {
+            write!(f, "O({})", self.remainder)
+        } else {
+            write!(f, "{} + O({})", self.polynomial, self.remainder)
+        }
+    }
+}
+
+fn number(value: i64) -> Node {
+    Coefficient::integer(value).to_node()
+}
+
+fn fraction(num: i64, den: i64) -> Node {
+    Coefficient::rational(num, den).to_node()
+}
+
+fn add(l: Node, r: Node) -> Node {
+    simplify(&Node::Add(Box::new(l), Box::new(r)))
+}
+
+fn sub(l: Node, r: Node) -> Node {
+    simplify(&Node::Sub(Box::new(l), Box::new(r)))
+}
+
+fn mul(l: Node, r: Node) -> Node {
+    simplify(&Node::Mul(Box::new(l), Box::new(r)))
+}
+
+fn div(l: Node, r: Node) -> Node {
+    simplify(&Node::Div(Box::new(l), Box::new(r)))
+}
+
+/// The real value of a constant node
+fn numeric(node: &Node) -> Option<f64> {
+    let value = evaluate(node, &HashMap::new()).ok()?;
+    (value.im().abs() < EPSILON).then_some(value.re())
+}
+
+fn is_zero(node: &Node) -> bool {
+    if let Some(coefficient) = Coefficient::from_node(node) {
+        return coefficient.is_zero();
+    }
+    numeric(node).is_some_and(|value| value.abs() < EPSILON)
+}
+
+/// The Taylor or Laurent series of the node around `var = point`, up to `(var - point)^order`
+///
+/// Series are built from the series of the subexpressions, so compositions like `e^sin(x)`,
+/// products, quotients and powers work, and a denominator that vanishes at the point gives
+/// negative powers. Coefficients are kept symbolic, around 1 the series of `e^x` starts with `e`.
+/// Expressions whose expansion needs fractional powers or logarithms, like `sqrt(x)` or `ln(x)`
+/// around 0, have no Laurent series, and neither does `sqrt(x^2)` which is `|x|`.
+pub fn series(node: &Node, var: &str, point: &Node, order: i64) -> Result<Series, SeriesError> {
+    if depends_on(point, var) {
+        return Err(SeriesError::InvalidPoint);
+    }
+    let point = simplify(point);
+    let node = simplify(node);
+
+    let mut result = Err(SeriesError::NoSeries);
+    for extra in EXTRA_TERMS {
+        let expansion = Expansion {
+            var,
+            point: &point,
+            precision: order + 1 + extra,
+            from_above: false,
+        };
+        result = expansion.expand(&node);
+        if let Ok(truncated) = &result {
+            if truncated.precision() > order {
+                break;
+            }
+        }
+    }
+    let truncated = result?;
+    if truncated.precision() <= order {
+        return Err(SeriesError::NoSeries);
+    }
+
+    let h = if is_zero(&point) {
+        Node::Variable(var.to_string())
+    } else {
+        Node::Sub(
+            Box::new(Node::Variable(var.to_string())),
+            Box::new(point.clone()),
+        )
+    };
+    let power = |k: i64| Node::Pow(Box::new(h.clone()), Box::new(number(k)));
+    // The terms are simplified one at a time so they stay in powers of h, in ascending order
+    let mut polynomial: Option<Node> = None;
+    for k in truncated.valuation..=order {
+        let c = truncated.coefficient(k);
+        if is_zero(&c) {
+            continue;
+        }
+        let negative = numeric(&c).is_some_and(|value| value < 0.0);
+        let c = if negative && polynomial.is_some() {
+            Node::Neg(Box::new(c))
+        } else {
+            c
+        };
+        let term = simplify(&Node::Mul(Box::new(c), Box::new(power(k))));
+        polynomial = Some(match polynomial {
+            Some(sum) if negative => Node::Sub(Box::new(sum), Box::new(term)),
+            Some(sum) => Node::Add(Box::new(sum), Box::new(term)),
+            None => term,
+        });
+    }
+    Ok(Series {
+        polynomial: polynomial.unwrap_or_else(|| number(0)),
+        remainder: simplify(&power(order + 1)),
+    })
+}
+
+/// The leading term `c*(var - point)^k` of the series, as `(k, c)`
+///
+/// When `from_above` is set only `var > point` matters, like for a one sided limit, so
+/// `sqrt(x^2)` has the leading term `x` even though it isn't a Laurent series on both sides.
+pub(super) fn leading_term(
+    node: &Node,
+    var: &str,
+    point: &Node,
+    from_above: bool,
+) -> Result<(i64, Node), SeriesError> {
+    let node = simplify(node);
+    for extra in EXTRA_TERMS {
+        let expansion = Expansion {
+            var,
+            point,
+            precision: extra,
+            from_above,
+        };
+        if let Ok(truncated) = expansion.expand(&node) {
+            if let Some(first) = truncated.coefficients.first() {
+                return Ok((truncated.valuation, first.clone()));
+            }
+        }
+    }
+    Err(SeriesError::NoSeries)
+}
+
+/// A series `sum c_i h^(valuation + i)` where only the powers below `precision()` are known
+#[derive(Debug, Clone)]
+struct Truncated {
+    valuation: i64,
+    coefficients: Vec<Node>,
+}
+
+impl Truncated {
+    /// Drops leading zero terms so the first coefficient is the leading one
+    fn new(mut valuation: i64, mut coefficients: Vec<Node>) -> Truncated {
+        let zeros = coefficients.iter().take_while(|c| is_zero(c)).count();
+        coefficients.drain(..zeros);
+        valuation += zeros as i64;
+        Truncated {
+            valuation,
+            coefficients,
+        }
+    }
+
+    fn constant(value: Node, precision: i64) -> Truncated {
+        let mut coefficients = vec![value];
+        coefficients.resize(precision.max(1) as usize, number(0));
+        Truncated::new(0, coefficients)
+    }
+
+    fn precision(&self) -> i64 {
+        self.valuation + self.coefficients.len() as i64
+    }
+
+    /// The coefficient of `h^k`, which has to be below the precision
+    fn coefficient(&self, k: i64) -> Node {
+        match usize::try_from(k - self.valuation) {
+            Ok(i) => self
+                .coefficients
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| number(0)),
+            Err(_) => number(0),
+        }
+    }
+
+    fn add(&self, other: &Truncated) -> Truncated {
+        let valuation = self.valuation.min(other.valuation);
+        let precision = self.precision().min(other.precision());
+        let coefficients = (valuation..precision)
+            .map(|k| add(self.coefficient(k), other.coefficient(k)))
+            .collect();
+        Truncated::new(valuation, coefficients)
+    }
+
+    fn neg(&self) -> Truncated {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .map(|c| simplify(&Node::Neg(Box::new(c.clone()))))
+            .collect();
+        Truncated::new(self.valuation, coefficients)
+    }
+
+    fn mul(&self, other: &Truncated) -> Truncated {
+        let valuation = self.valuation + other.valuation;
+        let precision =
+            (self.valuation + other.precision()).min(other.valuation + self.precision());
+        let coefficients = (0..precision - valuation)
+            .map(|k| {
+                (0..=k).fold(number(0), |acc, i| {
+                    match (
+                        self.coefficients.get(i as usize),
+                        other.coefficients.get((k - i) as usize),
+                    ) {
+                        (Some(a), Some(b)) => add(acc, mul(a.clone(), b.clone())),
+                        _ => acc,
+                    }
+                })
+            })
+            .collect();
+        Truncated::new(valuation, coefficients)
+    }
+
+    fn recip(&self) -> Result<Truncated, SeriesError> {
+        let c = &self.coefficients;
+        let first = div(number(1), c.first().ok_or(SeriesError::NoSeries)?.clone());
+        let mut b: Vec<Node> = vec![first.clone()];
+        for k in 1..c.len() {
+            let sum = (1..=k).fold(number(0), |acc, j| {
+                add(acc, mul(c[j].clone(), b[k - j].clone()))
+            });
+            b.push(simplify(&Node::Neg(Box::new(mul(first.clone(), sum)))));
+        }
+        Ok(Truncated::new(-self.valuation, b))
+    }
+
+    /// Splits the series into its constant term and the coefficients of `h^k` for
+    /// `0 <= k < precision` without it, for the functions that are expanded around the constant
+    fn split(&self) -> Result<(Node, Vec<Node>), SeriesError> {
+        if self.valuation < 0 || self.precision() <= 0 {
+            return Err(SeriesError::NoSeries);
+        }
+        let mut rest: Vec<Node> = (0..self.precision()).map(|k| self.coefficient(k)).collect();
+        let constant = std::mem::replace(&mut rest[0], number(0));
+        Ok((constant, rest))
+    }
+
+    fn exp(&self) -> Result<Truncated, SeriesError> {
+        let (constant, u) = self.split()?;
+        // a_k = 1/k sum j u_j a_(k-j)
+        let mut a = vec![number(1)];
+        for k in 1..u.len() {
+            let sum = (1..=k).fold(number(0), |acc, j| {
+                add(
+                    acc,
+                    mul(mul(number(j as i64), u[j].clone()), a[k - j].clone()),
+                )
+            });
+            a.push(mul(fraction(1, k as i64), sum));
+        }
+        let factor = Node::Pow(Box::new(Node::EConstant), Box::new(constant));
+        Ok(Truncated::new(0, a).mul(&Truncated::constant(simplify(&factor), u.len() as i64)))
+    }
+
+    fn log(&self) -> Result<Truncated, SeriesError> {
+        if self.valuation != 0 {
+            return Err(SeriesError::NoSeries);
+        }
+        let g = &self.coefficients;
+        // a_k = (g_k - 1/k sum_(j<k) j a_j g_(k-j)) / g_0
+        let mut a = vec![simplify(&Node::Log(Box::new(g[0].clone())))];
+        for k in 1..g.len() {
+            let sum = (1..k).fold(number(0), |acc, j| {
+                add(
+                    acc,
+                    mul(mul(number(j as i64), a[j].clone()), g[k - j].clone()),
+                )
+            });
+            let term = sub(g[k].clone(), mul(fraction(1, k as i64), sum));
+            a.push(div(term, g[0].clone()));
+        }
+        Ok(Truncated::new(0, a))
+    }
+
+    /// `(sin(g), cos(g))`
+    fn sin_cos(&self) -> Result<(Truncated, Truncated), SeriesError> {
+        let (constant, u) = self.split()?;
+        let (mut s, mut c) = (vec![number(0)], vec![number(1)]);
+        for k in 1..u.len() {
+            let term = |other: &[Node], j: usize| {
+                mul(mul(number(j as i64), u[j].clone()), other[k - j].clone())
+            };
+            let sin = (1..=k).fold(number(0), |acc, j| add(acc, term(&c, j)));
+            let cos = (1..=k).fold(number(0), |acc, j| add(acc, term(&s, j)));
+            s.push(mul(fraction(1, k as i64), sin));
+            c.push(mul(fraction(-1, k as i64), cos));
+        }
+
+        let precision = u.len() as i64;
+        let (s, c) = (Truncated::new(0, s), Truncated::new(0, c));
+        let sin0 = Truncated::constant(simplify(&Node::Sin(Box::new(constant.clone()))), precision);
+        let cos0 = Truncated::constant(simplify(&Node::Cos(Box::new(constant))), precision);
+        // sin(a + u) = sin(a)cos(u) + cos(a)sin(u) and cos(a + u) = cos(a)cos(u) - sin(a)sin(u)
+        let sin = sin0.mul(&c).add(&cos0.mul(&s));
+        let cos = cos0.mul(&c).add(&sin0.mul(&s).neg());
+        Ok((sin, cos))
+    }
+
+    /// Raises the series to a constant power, a fractional power of a series that starts with
+    /// `h^v` for a nonzero v is only `h^(v r)` for positive h, `sqrt(x^2)` is `|x|`
+    fn pow(
+        &self,
+        exponent: &Node,
+        precision: i64,
+        from_above: bool,
+    ) -> Result<Truncated, SeriesError> {
+        let integer = Coefficient::from_node(exponent).and_then(Coefficient::as_integer);
+        if let Some(n) = integer.filter(|n| n.abs() <= 64) {
+            let power = (0..n.abs()).fold(Truncated::constant(number(1), precision), |acc, _| {
+                acc.mul(self)
+            });
+            return if n < 0 { power.recip() } else { Ok(power) };
+        }
+
+        // g^r = c^r h^(v r) (1 + w)^r, where v r has to be an integer
+        let valuation = match Coefficient::from_node(exponent) {
+            _ if self.valuation == 0 => 0,
+            Some(Coefficient::Rational(num, den)) if from_above || den == 1 => {
+                let product = self
+                    .valuation
+                    .checked_mul(num)
+                    .ok_or(SeriesError::NoSeries)?;
+                if product % den != 0 {
+                    return Err(SeriesError::NoSeries);
+                }
+                product / den
+            }
+            _ => return Err(SeriesError::NoSeries),
+        };
+        let g = &self.coefficients;
+        let first = g.first().ok_or(SeriesError::NoSeries)?;
+        // a_k = 1/(k g_0) sum ((r + 1) j - k) g_j a_(k-j)
+        let mut a = vec![simplify(&Node::Pow(
+            Box::new(first.clone()),
+            Box::new(exponent.clone()),
+        ))];
+        let raised = add(exponent.clone(), number(1));
+        for k in 1..g.len() {
+            let sum = (1..=k).fold(number(0), |acc, j| {
+                let factor = sub(mul(raised.clone(), number(j as i64)), number(k as i64));
+                add(acc, mul(mul(factor, g[j].clone()), a[k - j].clone()))
+            });
+            a.push(div(sum, mul(number(k as i64), first.clone())));
+        }
+        Ok(Truncated::new(valuation, a))
+    }
+}
+
+/// Expands expressions in powers of `h = var - point`
+struct Expansion<'a> {
+    var: &'a str,
+    point: &'a Node,
+    precision: i64,
+    /// Whether h only takes positive values
+    from_above: bool,
+}
+
+impl Expansion<'_> {
+    fn expand(&self, node: &Node) -> Result<Truncated, SeriesError> {
+        if !depends_on(node, self.var) {
+            return Ok(Truncated::constant(node.clone(), self.precision));
+        }
+        let expand = |n: &Node| self.expand(n);
+        match node {
+            Node::Variable(_) => {
+                let mut coefficients = vec![self.point.clone(), number(1)];
+                coefficients.resize(self.precision.max(2) as usize, number(0));
+                Ok(Truncated::new(0, coefficients))
+            }
+            Node::Add(l, r) => Ok(expand(l)?.add(&expand(r)?)),
+            Node::Sub(l, r) => Ok(expand(l)?.add(&expand(r)?.neg())),
+            Node::Neg(n) => Ok(expand(n)?.neg()),
+            Node::Mul(l, r) => Ok(expand(l)?.mul(&expand(r)?)),
+            Node::Div(l, r) => Ok(expand(l)?.mul(&expand(r)?.recip()?)),
+            Node::Pow(base, exponent) if !depends_on(exponent, self.var) => {
+                expand(base)?.pow(&simplify(exponent), self.precision, self.from_above)
+            }
+            // b^e = e^(e ln b)
+            Node::Pow(base, exponent) => expand(exponent)?.mul(&expand(base)?.log()?).exp(),
+            Node::Exp(n) => expand(n)?.exp(),
+            Node::Log(n) => expand(n)?.log(),
+            Node::Sin(n) => Ok(expand(n)?.sin_cos()?.0),
+            Node::Cos(n) => Ok(expand(n)?.sin_cos()?.1),
+            Node::Tan(n) => {
+                let (sin, cos) = expand(n)?.sin_cos()?;
+                Ok(sin.mul(&cos.recip()?))
+            }
+            Node::Sqrt(n) => expand(n)?.pow(&fraction(1, 2), self.precision, self.from_above),
+            Node::Function(..) => Err(SeriesError::NoSeries),
+            Node::Number(_) | Node::PiConstant | Node::EConstant | Node::IConstant => {
+                Ok(Truncated::constant(node.clone(), self.precision))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    fn expanded(input: &str, point: &str, order: i64) -> String {
+        series(&parsed(input), "x", &parsed(point), order)
+            .unwrap_or_else(|error| panic!("{}: {}", input, error))
+            .to_string()
+    }
+
+    #[test]
+    fn test_taylor() {
+        assert_eq!(expanded("e^x", "0", 3), "1 + x + x^2/2 + x^3/6 + O(x^4)");
+        assert_eq!(expanded("sin(x)", "0", 5), "x - x^3/6 + x^5/120 + O(x^6)");
+        assert_eq!(expanded("x^2 + 1", "0", 5), "1 + x^2 + O(x^6)");
+        assert_eq!(
+            expanded("ln(x)", "1", 3),
+            "x - 1 - (x - 1)^2/2 + (x - 1)^3/3 + O((x - 1)^4)"
+        );
+        assert_eq!(
+            expanded("e^x", "1", 2),
+            "e + e*(x - 1) + e*(x - 1)^2/2 + O((x - 1)^3)"
+        );
+        assert_eq!(expanded("sqrt(1 + x)", "0", 2), "1 + x/2 - x^2/8 + O(x^3)");
+    }
+
+    #[test]
+    fn test_composition() {
+        assert_eq!(
+            expanded("e^sin(x)", "0", 4),
+            "1 + x + x^2/2 - x^4/8 + O(x^5)"
+        );
+        assert_eq!(expanded("tan(x)", "0", 5), "x + x^3/3 + 2*x^5/15 + O(x^6)");
+        assert_eq!(expanded("sin(x)/x", "0", 4), "1 - x^2/6 + x^4/120 + O(x^5)");
+        assert_eq!(
+            expanded("x^x", "1", 2),
+            "1 + (x - 1) + (x - 1)^2 + O((x - 1)^3)"
+        );
+    }
+
+    #[test]
+    fn test_laurent() {
+        assert_eq!(expanded("1/sin(x)", "0", 1), "1/x + x/6 + O(x^2)");
+        assert_eq!(
+            expanded("e^x/x^2", "0", 1),
+            "1/x^2 + 1/x + 1/2 + x/6 + O(x^2)"
+        );
+        assert_eq!(expanded("1/(x - 1)", "1", 2), "1/(x - 1) + O((x - 1)^3)");
+        assert_eq!(
+            series(&parsed("sqrt(x)"), "x", &parsed("0"), 2),
+            Err(SeriesError::NoSeries)
+        );
+        assert_eq!(
+            series(&parsed("ln(x)"), "x", &parsed("0"), 2),
+            Err(SeriesError::NoSeries)
+        );
+        // sqrt(x^2) is |x|, and the principal cube root of x^3 is only x for positive x
+        for input in ["sqrt(x^2)", "sqrt(x^2 + x^3)", "(x^3)^(1/3)"] {
+            assert_eq!(
+                series(&parsed(input), "x", &parsed("0"), 3),
+                Err(SeriesError::NoSeries)
+            );
+        }
+        assert_eq!(
+            leading_term(&parsed("sqrt(x^2 + x^3)"), "x", &parsed("0"), true),
+            Ok((1, number(1)))
+        );
+    }
+}
@@ -0,0 +1,601 @@
+use super::integral::{factors, product as factors_product, replace};
+use super::polynomial::Polynomial;
+use super::{depends_on, diff, summands};
+use crate::integer;
+use crate::parser::{Function, Node};
+use crate::simplify::coefficient::Coefficient;
+use crate::simplify::simplify;
+
+/// Sums and products with numeric bounds are written out term by term up to this many terms
+const MAX_TERMS: i64 = 1000;
+
+/// How far apart the poles of a telescoping rational sum may be
+const MAX_SHIFT: i64 = 16;
+
+fn number(value: i64) -> Node {
+    Coefficient::integer(value).to_node()
+}
+
+fn add(l: Node, r: Node) -> Node {
+    Node::Add(Box::new(l), Box::new(r))
+}
+
+fn sub(l: Node, r: Node) -> Node {
+    Node::Sub(Box::new(l), Box::new(r))
+}
+
+fn mul(l: Node, r: Node) -> Node {
+    Node::Mul(Box::new(l), Box::new(r))
+}
+
+fn div(l: Node, r: Node) -> Node {
+    Node::Div(Box::new(l), Box::new(r))
+}
+
+fn pow(l: Node, r: Node) -> Node {
+    Node::Pow(Box::new(l), Box::new(r))
+}
+
+fn factorial(n: Node) -> Node {
+    Node::Function(Function::Factorial, vec![n])
+}
+
+fn is_zero(node: &Node) -> bool {
+    Coefficient::from_node(node).is_some_and(Coefficient::is_zero)
+}
+
+/// The sum of the node over the integers `var` from `a` to `b`
+///
+/// Small numeric ranges are added up term by term. Otherwise polynomials are summed with
+/// Faulhaber's formula, geometric terms as geometric series, and rational functions whose partial
+/// fractions cancel, like `1/(k*(k + 1))`, as well as `g(k) - g(k + 1)` telescope. Sums without a
+/// closed form are left as `sum(f, k, a, b)`.
+///
+/// Every closed form is `F(b + 1) - F(a)`, so it holds for `b < a - 1` too because the evaluators
+/// take the sum over such a reversed range to be minus the sum from `b + 1` to `a - 1`, and
+/// `sum(k, k, n, 1)` is `-n^2/2 + n/2 + 1` for every `n`.
+pub fn sum(node: &Node, var: &str, a: &Node, b: &Node) -> Node {
+    match closed_sum(node, var, a, b) {
+        Some(result) => simplify(&result),
+        None => unevaluated(Function::Sum, node, var, a, b),
+    }
+}
+
+/// The product of the node over the integers `var` from `a` to `b`
+///
+/// Small numeric ranges are multiplied out. Otherwise polynomials with integer roots become
+/// ratios of factorials, powers with a varying exponent are summed in the exponent, and
+/// `g(k + 1)/g(k)` telescopes. Products without a closed form are left as `product(f, k, a, b)`.
+/// A reversed range with `b < a - 1` gives the inverse of the product from `b + 1` to `a - 1`.
+pub fn product(node: &Node, var: &str, a: &Node, b: &Node) -> Node {
+    match closed_product(node, var, a, b) {
+        Some(result) => simplify(&result),
+        None => unevaluated(Function::Product, node, var, a, b),
+    }
+}
+
+fn unevaluated(function: Function, node: &Node, var: &str, a: &Node, b: &Node) -> Node {
+    let index = Node::Variable(var.to_string());
+    Node::Function(function, vec![node.clone(), index, a.clone(), b.clone()])
+}
+
+/// The terms from `a` to `b` when both are integers and there aren't too many of them, and
+/// whether the range is reversed, see [`integer::range`]
+fn expanded(node: &Node, var: &str, a: &Node, b: &Node) -> Option<(Vec<Node>, bool)> {
+    let integer = |n: &Node| Coefficient::from_node(&simplify(n))?.as_integer();
+    let (range, reversed) = integer::range(integer(a)?, integer(b)?).ok()?;
+    if range.end() - range.start() >= MAX_TERMS {
+        return None;
+    }
+    let index = Node::Variable(var.to_string());
+    let terms = range.map(|k| replace(node, &index, &number(k))).collect();
+    Some((terms, reversed))
+}
+
+/// Whether either bound is a number that isn't an integer, so the sum has no value
+fn is_fractional(a: &Node, b: &Node) -> bool {
+    [a, b]
+        .into_iter()
+        .any(|n| Coefficient::from_node(&simplify(n)).is_some_and(|c| c.to_f64().fract() != 0.0))
+}
+
+/// The number of integers from `a` to `b`
+fn count(a: &Node, b: &Node) -> Node {
+    simplify(&add(sub(b.clone(), a.clone()), number(1)))
+}
+
+fn closed_sum(node: &Node, var: &str, a: &Node, b: &Node) -> Option<Node> {
+    if is_fractional(a, b) {
+        return None;
+    }
+    if let Some((terms, reversed)) = expanded(node, var, a, b) {
+        let sum = terms.into_iter().fold(number(0), add);
+        return Some(if reversed {
+            Node::Neg(Box::new(sum))
+        } else {
+            sum
+        });
+    }
+    let node = simplify(node);
+    if !depends_on(&node, var) {
+        return Some(mul(node, count(a, b)));
+    }
+
+    fraction(&node, var)
+        .and_then(|(numerator, denominator)| rational(&numerator, &denominator, var, a, b))
+        .or_else(|| telescoping(&node, var, a, b))
+        .or_else(|| {
+            let mut terms = vec![];
+            summands(&node, false, &mut terms);
+            let sums = terms.iter().map(|term| {
+                let mut all = vec![];
+                factors(term, false, &mut all);
+                let (dependent, constant): (Vec<_>, Vec<_>) =
+                    all.into_iter().partition(|(base, exponent)| {
+                        depends_on(base, var) || depends_on(exponent, var)
+                    });
+                let result = fraction(&factors_product(&dependent), var)
+                    .and_then(|(n, d)| rational(&n, &d, var, a, b))
+                    .or_else(|| geometric(&dependent, var, a, b))?;
+                Some(mul(factors_product(&constant), result))
+            });
+            sums.collect::<Option<Vec<_>>>()?.into_iter().reduce(add)
+        })
+}
+
+/// Reads a rational function of `var` with numeric coefficients as a numerator and denominator
+fn fraction(node: &Node, var: &str) -> Option<(Polynomial, Polynomial)> {
+    let one = || Polynomial::constant(Coefficient::integer(1));
+    if !depends_on(node, var) {
+        return Some((Polynomial::from_node(node, var)?, one()));
+    }
+    match node {
+        Node::Add(l, r) | Node::Sub(l, r) => {
+            let ((ln, ld), (rn, rd)) = (fraction(l, var)?, fraction(r, var)?);
+            let (l, r) = (ln.mul(&rd)?, rn.mul(&ld)?);
+            let numerator = match node {
+                Node::Add(..) => l.add(&r)?,
+                _ => l.sub(&r)?,
+            };
+            Some((numerator, ld.mul(&rd)?))
+        }
+        Node::Mul(l, r) => {
+            let ((ln, ld), (rn, rd)) = (fraction(l, var)?, fraction(r, var)?);
+            Some((ln.mul(&rn)?, ld.mul(&rd)?))
+        }
+        Node::Div(l, r) => {
+            let ((ln, ld), (rn, rd)) = (fraction(l, var)?, fraction(r, var)?);
+            Some((ln.mul(&rd)?, ld.mul(&rn)?))
+        }
+        Node::Neg(n) => {
+            let (n, d) = fraction(n, var)?;
+            Some((n.neg(), d))
+        }
+        Node::Pow(base, exponent) => {
+            let e = Coefficient::from_node(&simplify(exponent))?.as_integer()?;
+            if e.abs() > 64 {
+                return None;
+            }
+            let (n, d) = fraction(base, var)?;
+            let power = |p: &Polynomial| (0..e.abs()).try_fold(one(), |acc, _| acc.mul(p));
+            if e >= 0 {
+                Some((power(&n)?, power(&d)?))
+            } else {
+                Some((power(&d)?, power(&n)?))
+            }
+        }
+        _ => Some((Polynomial::from_node(node, var)?, one())),
+    }
+}
+
+/// Sums a rational function, the polynomial part with Faulhaber's formula and the rest when its
+/// partial fractions are `c/(k + s)` with integer shifts `s` and coefficients that add up to 0
+fn rational(
+    numerator: &Polynomial,
+    denominator: &Polynomial,
+    var: &str,
+    a: &Node,
+    b: &Node,
+) -> Option<Node> {
+    let (quotient, remainder) = numerator.div_rem(denominator)?;
+    let polynomial = polynomial(&quotient, var, a, b)?;
+    if remainder.is_zero() {
+        return Some(polynomial);
+    }
+
+    // The coefficients of 1/(k - root), higher powers of (k - root) don't telescope
+    let mut fractions: Vec<(Coefficient, Coefficient)> = vec![];
+    for (root, multiplicity) in denominator.rational_roots()? {
+        let power = (0..multiplicity)
+            .try_fold(Polynomial::constant(Coefficient::integer(1)), |acc, _| {
+                acc.mul(&Polynomial::linear(root))
+            })?;
+        let (cofactor, _) = denominator.div_rem(&power)?;
+        let series = remainder
+            .shifted(root)?
+            .series_div(&cofactor.shifted(root)?, multiplicity)?;
+        let (simple, higher) = series.split_last()?;
+        if !higher.iter().all(|c| c.is_zero()) {
+            return None;
+        }
+        if !simple.is_zero() {
+            fractions.push((root, *simple));
+        }
+    }
+    let total = fractions
+        .iter()
+        .try_fold(Coefficient::integer(0), |acc, (_, c)| acc.add(*c).ok())?;
+    if !total.is_zero() {
+        return None;
+    }
+
+    // With k + s0 the smallest shift, the sum of 1/(k + s0 + d) over a..b is the sum of
+    // 1/(k + s0) plus 1/(b + s0 + j) for j in 1..=d minus 1/(a + s0 + j) for j in 0..d, and the
+    // sums of 1/(k + s0) cancel because the coefficients add up to 0
+    let largest = fractions
+        .iter()
+        .map(|(root, _)| *root)
+        .max_by(|p, q| p.to_f64().total_cmp(&q.to_f64()))?;
+    let mut result = polynomial;
+    for (root, c) in fractions {
+        let d = largest.add(root.neg()).ok()?.as_integer()?;
+        if !(0..=MAX_SHIFT).contains(&d) {
+            return None;
+        }
+        let shift = |n: &Node, j: i64| {
+            let offset = Coefficient::integer(j).add(largest.neg()).ok()?;
+            Some(div(c.to_node(), add(n.clone(), offset.to_node())))
+        };
+        for j in 1..=d {
+            result = add(result, shift(b, j)?);
+        }
+        for j in 0..d {
+            result = sub(result, shift(a, j)?);
+        }
+    }
+    Some(result)
+}
+
+/// Sums a polynomial with Faulhaber's formula, `p(a) + ... + p(b) = P(b + 1) - P(a)`
+///
+/// When `a` is a number the result is a polynomial in `b`, which is factored over its rational
+/// roots so `sum(k, k, 1, n)` is `n*(n + 1)/2`.
+fn polynomial(p: &Polynomial, var: &str, a: &Node, b: &Node) -> Option<Node> {
+    if p.is_zero() {
+        return Some(number(0));
+    }
+    let antidifference = p.antidifference()?;
+    let index = Node::Variable(var.to_string());
+    let Some(start) = Coefficient::from_node(&simplify(a)) else {
+        let at = |n: Node| replace(&antidifference.to_node(var), &index, &n);
+        return Some(sub(at(add(b.clone(), number(1))), at(a.clone())));
+    };
+
+    let offset = Polynomial::constant(antidifference.evaluate(start)?);
+    let result = antidifference
+        .shifted(Coefficient::integer(1))?
+        .sub(&offset)?;
+    Some(replace(&factored(&result, var), &index, b))
+}
+
+/// The polynomial as a number times powers of `q*x - p` for its roots `p/q`, when all of its
+/// roots are rational
+fn factored(p: &Polynomial, var: &str) -> Node {
+    let factors = || {
+        if p.degree() < 2 {
+            return None;
+        }
+        let mut linear = Polynomial::constant(Coefficient::integer(1));
+        let mut scale = Coefficient::integer(1);
+        let mut node = Coefficient::integer(1).to_node();
+        for (root, multiplicity) in p.rational_roots()? {
+            let denominator = match root {
+                Coefficient::Rational(_, den) => Coefficient::integer(den),
+                Coefficient::Real(_) => return None,
+            };
+            let factor = Polynomial::linear(root).scale(denominator)?;
+            for _ in 0..multiplicity {
+                linear = linear.mul(&factor)?;
+            }
+            scale = scale
+                .mul(denominator.powi(multiplicity as i64).ok()?)
+                .ok()?;
+            node = mul(node, pow(factor.to_node(var), number(multiplicity as i64)));
+        }
+        let (lead, _) = p.div_rem(&linear)?;
+        Some(mul(lead.to_node(var), node))
+    };
+    factors().unwrap_or_else(|| p.to_node(var))
+}
+
+/// `t(a) + ... + t(b)` for a product of exponentials `c^u` and `e^u` with `u` linear in `var`,
+/// which has a constant ratio `r = t(k + 1)/t(k)` and sums to `(t(a) - t(b + 1))/(1 - r)`
+fn geometric(factors: &[(Node, Node)], var: &str, a: &Node, b: &Node) -> Option<Node> {
+    let mut ratio = number(1);
+    for (base, exponent) in factors {
+        let (base, exponent) = match base {
+            Node::Exp(u) if !depends_on(exponent, var) => {
+                (Node::EConstant, mul(*u.clone(), exponent.clone()))
+            }
+            _ if !depends_on(base, var) => (base.clone(), exponent.clone()),
+            _ => return None,
+        };
+        let slope = diff(&exponent, var);
+        if depends_on(&slope, var) {
+            return None;
+        }
+        ratio = mul(ratio, pow(base, slope));
+    }
+    let ratio = simplify(&ratio);
+    if Coefficient::from_node(&ratio).is_some_and(Coefficient::is_one) {
+        return None;
+    }
+
+    let node = factors_product(factors);
+    let index = Node::Variable(var.to_string());
+    let at = |n: Node| replace(&node, &index, &n);
+    let difference = sub(at(a.clone()), at(add(b.clone(), number(1))));
+    let denominator = simplify(&sub(number(1), ratio));
+    if Coefficient::from_node(&denominator).is_none() {
+        return Some(div(difference, denominator));
+    }
+    // A number is divided into every term, so the sum isn't left negated
+    let mut terms = vec![];
+    summands(&difference, false, &mut terms);
+    let terms = terms
+        .into_iter()
+        .map(|term| simplify(&div(term, denominator.clone())));
+    terms.reduce(add)
+}
+
+/// `g(k) - g(k + 1)` sums to `g(a) - g(b + 1)`
+fn telescoping(node: &Node, var: &str, a: &Node, b: &Node) -> Option<Node> {
+    let mut terms = vec![];
+    summands(node, false, &mut terms);
+    let [u, v] = terms.as_slice() else {
+        return None;
+    };
+    let index = Node::Variable(var.to_string());
+    let next = |n: &Node| replace(n, &index, &add(index.clone(), number(1)));
+    // u(k) + v(k) with v(k) = -u(k + 1) sums to u(a) + v(b)
+    let sums = |u: &Node, v: &Node| {
+        is_zero(&simplify(&add(next(u), v.clone())))
+            .then(|| add(replace(u, &index, a), replace(v, &index, b)))
+    };
+    sums(u, v).or_else(|| sums(v, u))
+}
+
+fn closed_product(node: &Node, var: &str, a: &Node, b: &Node) -> Option<Node> {
+    if is_fractional(a, b) {
+        return None;
+    }
+    if let Some((terms, reversed)) = expanded(node, var, a, b) {
+        let product = terms.into_iter().fold(number(1), mul);
+        return Some(if reversed {
+            div(number(1), product)
+        } else {
+            product
+        });
+    }
+    let node = simplify(node);
+    if !depends_on(&node, var) {
+        return Some(pow(node, count(a, b)));
+    }
+
+    let mut all = vec![];
+    factors(&node, false, &mut all);
+    telescoping_ratio(&all, var, a, b).or_else(|| {
+        let products = all.iter().map(|(base, exponent)| {
+            match (depends_on(base, var), depends_on(exponent, var)) {
+                (false, false) => Some(pow(pow(base.clone(), exponent.clone()), count(a, b))),
+                (false, true) => Some(pow(base.clone(), closed_sum(exponent, var, a, b)?)),
+                (true, false) => match base {
+                    Node::Exp(u) => {
+                        let exponent = mul(*u.clone(), exponent.clone());
+                        Some(Node::Exp(Box::new(closed_sum(&exponent, var, a, b)?)))
+                    }
+                    _ => Some(pow(factorials(base, var, a, b)?, exponent.clone())),
+                },
+                (true, true) => None,
+            }
+        });
+        products
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .reduce(mul)
+    })
+}
+
+/// `g(k + 1)/g(k)` multiplies to `g(b + 1)/g(a)`
+fn telescoping_ratio(factors: &[(Node, Node)], var: &str, a: &Node, b: &Node) -> Option<Node> {
+    let (denominator, numerator): (Vec<_>, Vec<_>) =
+        factors.iter().cloned().partition(|(_, exponent)| {
+            Coefficient::from_node(exponent).is_some_and(Coefficient::is_negative)
+        });
+    let numerator = factors_product(&numerator);
+    let denominator = simplify(&div(number(1), factors_product(&denominator)));
+    if !depends_on(&denominator, var) {
+        return None;
+    }
+    let index = Node::Variable(var.to_string());
+    let next = replace(&denominator, &index, &add(index.clone(), number(1)));
+    (simplify(&next) == numerator).then(|| {
+        div(
+            replace(&numerator, &index, b),
+            replace(&denominator, &index, a),
+        )
+    })
+}
+
+/// The product of a polynomial with integer roots, `k + s` multiplies to `(b + s)!/(a + s - 1)!`
+fn factorials(base: &Node, var: &str, a: &Node, b: &Node) -> Option<Node> {
+    let p = Polynomial::from_node(base, var)?;
+    let start = Coefficient::from_node(&simplify(a));
+    let mut linear = Polynomial::constant(Coefficient::integer(1));
+    let mut result = Coefficient::integer(1).to_node();
+    for (root, multiplicity) in p.rational_roots()? {
+        root.as_integer()?;
+        // A root in the range makes the product 0, which the factorials can't show
+        if start.is_some_and(|start| root.to_f64() >= start.to_f64()) {
+            return None;
+        }
+        for _ in 0..multiplicity {
+            linear = linear.mul(&Polynomial::linear(root))?;
+        }
+        let shifted = |n: &Node, offset: i64| {
+            let offset = Coefficient::integer(offset).add(root.neg()).ok()?;
+            Some(factorial(add(n.clone(), offset.to_node())))
+        };
+        let ratio = div(shifted(b, 0)?, shifted(a, -1)?);
+        result = mul(result, pow(ratio, number(multiplicity as i64)));
+    }
+    let (lead, _) = p.div_rem(&linear)?;
+    Some(mul(pow(lead.to_node(var), count(a, b)), result))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::eval::evaluate;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+    use crate::Number;
+
+    fn simplified(input: &str) -> String {
+        simplify(&parse(&tokenize(input).unwrap()).unwrap()).to_string()
+    }
+
+    /// Checks the closed form against adding up the terms for a few values of `n`
+    fn assert_closed_form(input: &str) {
+        let node = parse(&tokenize(input).unwrap()).unwrap();
+        let closed = simplify(&node);
+        assert!(
+            !matches!(closed, Node::Function(Function::Sum | Function::Product, _)),
+            "{} has no closed form",
+            input
+        );
+        for n in [3.0, 7.0, 12.0] {
+            let variables = HashMap::from([
+                ("n".to_string(), Number::real(n)),
+                ("x".to_string(), Number::real(0.7)),
+            ]);
+            let expected = evaluate(&node, &variables).unwrap().value();
+            let actual = evaluate(&closed, &variables).unwrap().value();
+            assert!(
+                (expected - actual).abs() < 1e-9 * expected.abs().max(1.0),
+                "{} = {} is {} at n = {} but should be {}",
+                input,
+                closed,
+                actual,
+                n,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_numeric_bounds() {
+        assert_eq!(simplified("sum(k^2, k, 1, 10)"), "385");
+        assert_eq!(simplified("sum(1/k, k, 1, 4)"), "25/12");
+        assert_eq!(simplified("product(k, k, 1, 6)"), "720");
+        assert_eq!(simplified("sum(x^k, k, 0, 3)"), "x^3 + x^2 + x + 1");
+        assert_eq!(simplified("sum(k, k, 1, 0)"), "0");
+        assert_eq!(simplified("sum(k, k, 1, 10^6)"), "500000500000");
+        assert_eq!(simplified("sum(k, k, 4, 1)"), "-5");
+        assert_eq!(simplified("product(k, k, 5, 2)"), "1/12");
+    }
+
+    #[test]
+    fn test_reversed_ranges() {
+        // The closed forms and the evaluator agree below the lower bound too
+        for input in ["sum(k, k, n, 1)", "sum(k^2, k, 1, n)", "sum(2^k, k, 0, n)"] {
+            let node = parse(&tokenize(input).unwrap()).unwrap();
+            let closed = simplify(&node);
+            for n in [-4.0, -1.0, 0.0, 4.0] {
+                let variables = HashMap::from([("n".to_string(), Number::real(n))]);
+                let expected = evaluate(&node, &variables).unwrap().value();
+                let actual = evaluate(&closed, &variables).unwrap().value();
+                assert!(
+                    (expected - actual).abs() < 1e-12,
+                    "{} = {} is {} at n = {} but should be {}",
+                    input,
+                    closed,
+                    actual,
+                    n,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_faulhaber() {
+        assert_eq!(simplified("sum(k, k, 1, n)"), "n*(n + 1)/2");
+        assert_eq!(simplified("sum(k^2, k, 1, n)"), "n*(2*n + 1)*(n + 1)/6");
+        assert_eq!(simplified("sum(k^3, k, 1, n)"), "n^2*(n + 1)^2/4");
+        assert_eq!(simplified("sum(c, k, 1, n)"), "c*n");
+        for input in [
+            "sum(k^4 - 3*k + 2, k, 0, n)",
+            "sum(x*k^2, k, 1, n)",
+            "sum(k, k, n, 2*n)",
+            "sum((2*k + 1)^2, k, 3, n)",
+        ] {
+            assert_closed_form(input);
+        }
+    }
+
+    #[test]
+    fn test_geometric() {
+        assert_eq!(simplified("sum(2^k, k, 0, n)"), "-1 + 2^(n + 1)");
+        for input in [
+            "sum(x^k, k, 0, n)",
+            "sum(3*(1/2)^k, k, 1, n)",
+            "sum(e^(2*k), k, 1, n)",
+            "sum(k + 2^k, k, 1, n)",
+        ] {
+            assert_closed_form(input);
+        }
+    }
+
+    #[test]
+    fn test_telescoping() {
+        assert_eq!(simplified("sum(1/(k*(k + 1)), k, 1, n)"), "-1/(n + 1) + 1");
+        for input in [
+            "sum(1/(k^2 + 3*k + 2), k, 0, n)",
+            "sum(1/(k*(k + 2)), k, 1, n)",
+            "sum(1/k - 1/(k + 1), k, 1, n)",
+            "sum(sqrt(k + 1) - sqrt(k), k, 1, n)",
+            "sum(ln(k) - ln(k + 1), k, 1, n)",
+        ] {
+            assert_closed_form(input);
+        }
+    }
+
+    #[test]
+    fn test_products() {
+        assert_eq!(simplified("product(k, k, 1, n)"), "n!");
+        assert_eq!(simplified("product((k + 1)/k, k, 1, n)"), "n + 1");
+        for input in [
+            "product(2*k, k, 1, n)",
+            "product(x^k, k, 1, n)",
+            "product(k*(k + 2), k, 1, n)",
+            "product(e^k, k, 0, n)",
+            "product(ln(k + 1)/ln(k), k, 2, n)",
+        ] {
+            assert_closed_form(input);
+        }
+    }
+
+    #[test]
+    fn test_unevaluated() {
+        assert_eq!(simplified("sum(1/k, k, 1, n)"), "sum(1/k, k, 1, n)");
+        assert_eq!(simplified("sum(1/k^2, k, 1, n)"), "sum(1/k^2, k, 1, n)");
+        assert_eq!(simplified("product(k, k, 0, n)"), "product(k, k, 0, n)");
+        assert_eq!(simplified("sum(sin(k), k, 1, n)"), "sum(sin(k), k, 1, n)");
+        assert_eq!(simplified("sum(k, k, 1, 2.5)"), "sum(k, k, 1, 2.5)");
+        assert_eq!(simplified("sum(k, k, 0.5, 3)"), "sum(k, k, 0.5, 3)");
+        assert_eq!(simplified("product(k, k, 1/2, n)"), "product(k, k, 1/2, n)");
+    }
+}
@@ -0,0 +1,401 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Number, NumberError};
+
+/// How a decimal is rounded when digits past its scale are dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Round to the nearest value, ties go to the even neighbour (banker's rounding)
+    HalfEven,
+    /// Round to the nearest value, ties go away from zero
+    HalfUp,
+    /// Drop the extra digits, rounding towards zero
+    Truncate,
+}
+
+/// The scale results are rounded to, together with the rounding mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalMode {
+    pub scale: u32,
+    pub rounding: RoundingMode,
+}
+
+impl DecimalMode {
+    pub fn new(scale: u32, rounding: RoundingMode) -> DecimalMode {
+        DecimalMode { scale, rounding }
+    }
+}
+
+/// An exact base-10 number `mantissa * 10^-scale`, so `0.1 + 0.2` is exactly `0.3`
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+fn pow10(exponent: u32) -> Result<i128, NumberError> {
+    10i128.checked_pow(exponent).ok_or(NumberError::Overflow)
+}
+
+/// `numerator / denominator` rounded to an integer with the given mode
+fn div_round(numerator: i128, denominator: i128, rounding: RoundingMode) -> i128 {
+    let quotient = numerator / denominator;
+    let remainder = (numerator % denominator).unsigned_abs();
+    let rest = denominator.unsigned_abs() - remainder;
+    let sign = if (numerator < 0) != (denominator < 0) {
+        -1
+    } else {
+        1
+    };
+
+    // The remainder is past the halfway point when it is larger than what's left to the next value
+    let round_away = match rounding {
+        RoundingMode::Truncate => false,
+        RoundingMode::HalfUp => remainder >= rest,
+        RoundingMode::HalfEven => remainder > rest || (remainder == rest && quotient % 2 != 0),
+    };
+    if remainder != 0 && round_away {
+        quotient + sign
+    } else {
+        quotient
+    }
+}
+
+impl Decimal {
+    pub fn new(mantissa: i128, scale: u32) -> Decimal {
+        Decimal { mantissa, scale }
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    /// The number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    /// The exact decimal that prints like the float, so `0.1` becomes exactly `0.1`
+    pub fn from_f64(value: f64) -> Result<Decimal, NumberError> {
+        Number::real(value).checked()?;
+        value.to_string().parse()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    pub fn to_number(&self) -> Number {
+        Number::real(self.to_f64())
+    }
+
+    /// Whether the value is a whole number, e.g. `2.00`
+    pub fn to_integer(&self) -> Option<i128> {
+        let divisor = pow10(self.scale).ok()?;
+        (self.mantissa % divisor == 0).then(|| self.mantissa / divisor)
+    }
+
+    /// Changes the scale, rounding away the extra digits when it gets smaller
+    pub fn round(&self, scale: u32, rounding: RoundingMode) -> Result<Decimal, NumberError> {
+        let mantissa = if scale >= self.scale {
+            self.mantissa
+                .checked_mul(pow10(scale - self.scale)?)
+                .ok_or(NumberError::Overflow)?
+        } else {
+            match pow10(self.scale - scale) {
+                Ok(divisor) => div_round(self.mantissa, divisor, rounding),
+                // Every digit is dropped, which leaves zero unless rounded up
+                Err(_) => 0,
+            }
+        };
+        Ok(Decimal::new(mantissa, scale))
+    }
+
+    /// Drops trailing zeros after the decimal point
+    fn normalized(mut self) -> Decimal {
+        while self.scale > 0 && self.mantissa % 10 == 0 {
+            self.mantissa /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    /// Both mantissas at the larger of the two scales
+    fn aligned(left: &Decimal, right: &Decimal) -> Result<(i128, i128, u32), NumberError> {
+        let scale = left.scale.max(right.scale);
+        let left = left.round(scale, RoundingMode::Truncate)?;
+        let right = right.round(scale, RoundingMode::Truncate)?;
+        Ok((left.mantissa, right.mantissa, scale))
+    }
+
+    pub fn add(left: &Decimal, right: &Decimal) -> Result<Decimal, NumberError> {
+        let (left, right, scale) = Decimal::aligned(left, right)?;
+        let mantissa = left.checked_add(right).ok_or(NumberError::Overflow)?;
+        Ok(Decimal::new(mantissa, scale))
+    }
+
+    pub fn sub(left: &Decimal, right: &Decimal) -> Result<Decimal, NumberError> {
+        Decimal::add(left, &Decimal::neg(right))
+    }
+
+    /// The exact product, its scale is the sum of both scales
+    pub fn mul(left: &Decimal, right: &Decimal) -> Result<Decimal, NumberError> {
+        let (left, right) = (left.normalized(), right.normalized());
+        let mantissa = left
+            .mantissa
+            .checked_mul(right.mantissa)
+            .ok_or(NumberError::Overflow)?;
+        Ok(Decimal::new(mantissa, left.scale + right.scale))
+    }
+
+    /// The quotient rounded to `mode.scale` digits
+    pub fn div(left: &Decimal, right: &Decimal, mode: DecimalMode) -> Result<Decimal, NumberError> {
+        if right.is_zero() {
+            return Err(NumberError::DivisionByZero);
+        }
+        let (left, right) = (left.normalized(), right.normalized());
+
+        // left / right = (left.mantissa * 10^(scale + right.scale)) / (right.mantissa * 10^left.scale)
+        let numerator = left
+            .mantissa
+            .checked_mul(pow10(mode.scale + right.scale)?)
+            .ok_or(NumberError::Overflow)?;
+        let denominator = right
+            .mantissa
+            .checked_mul(pow10(left.scale)?)
+            .ok_or(NumberError::Overflow)?;
+        let mantissa = div_round(numerator, denominator, mode.rounding);
+        Ok(Decimal::new(mantissa, mode.scale))
+    }
+
+    /// The product rounded to `mode.scale` digits.
+    ///
+    /// If the exact product doesn't fit, digits are dropped from the operands until it does,
+    /// which only happens for values with more than about 19 significant digits.
+    pub fn mul_rounded(
+        left: &Decimal,
+        right: &Decimal,
+        mode: DecimalMode,
+    ) -> Result<Decimal, NumberError> {
+        let (mut left, mut right) = (left.normalized(), right.normalized());
+        loop {
+            match Decimal::mul(&left, &right) {
+                Ok(product) => return product.trimmed(mode),
+                Err(_) if left.scale == 0 && right.scale == 0 => return Err(NumberError::Overflow),
+                Err(_) if left.scale >= right.scale => {
+                    left = left.round(left.scale - 1, mode.rounding)?
+                }
+                Err(_) => right = right.round(right.scale - 1, mode.rounding)?,
+            }
+        }
+    }
+
+    /// Raises to an integer power, every multiplication is rounded to `mode.scale`
+    pub fn powi(base: &Decimal, exponent: i64, mode: DecimalMode) -> Result<Decimal, NumberError> {
+        if exponent < 0 {
+            if base.is_zero() {
+                return Err(NumberError::Domain("zero to a negative power"));
+            }
+            let power = Decimal::powi(base, -exponent, mode)?;
+            return Decimal::div(&Decimal::new(1, 0), &power, mode);
+        }
+
+        let mut base = *base;
+        let mut result = Decimal::new(1, 0);
+        let mut exponent = exponent;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = Decimal::mul_rounded(&result, &base, mode)?;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = Decimal::mul_rounded(&base, &base, mode)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Rounds to the scale of the mode, but only if the value has more digits than that
+    pub fn trimmed(&self, mode: DecimalMode) -> Result<Decimal, NumberError> {
+        if self.scale > mode.scale {
+            self.round(mode.scale, mode.rounding)
+        } else {
+            Ok(*self)
+        }
+    }
+
+    /// The largest integer that is not larger than the value
+    pub fn floor(&self) -> Result<Decimal, NumberError> {
+        let divisor = pow10(self.scale)?;
+        Ok(Decimal::new(self.mantissa.div_euclid(divisor), 0))
+    }
+
+    /// The smallest integer that is not smaller than the value
+    pub fn ceil(&self) -> Result<Decimal, NumberError> {
+        Ok(Decimal::neg(&Decimal::neg(self).floor()?))
+    }
+
+    pub fn neg(number: &Decimal) -> Decimal {
+        Decimal::new(-number.mantissa, number.scale)
+    }
+}
+
+impl PartialEq for Decimal {
+    /// Compares values, so `0.30` equals `0.3`
+    fn eq(&self, other: &Decimal) -> bool {
+        let (left, right) = (self.normalized(), other.normalized());
+        left.mantissa == right.mantissa && left.scale == right.scale
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = NumberError;
+
+    /// Reads a plain decimal like `-12.50`
+    fn from_str(input: &str) -> Result<Decimal, NumberError> {
+        let (negative, digits) = match input.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(NumberError::ParsingError);
+        }
+
+        let mut mantissa: i128 = 0;
+        for digit in integer.chars().chain(fraction.chars()) {
+            mantissa = mantissa
+                .checked_mul(10)
+                .and_then(|m| m.checked_add(digit.to_digit(10).unwrap_or(0) as i128))
+                .ok_or(NumberError::Overflow)?;
+        }
+        let mantissa = if negative { -mantissa } else { mantissa };
+        Ok(Decimal::new(mantissa, fraction.len() as u32).normalized())
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+
+        if self.mantissa < 0 {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", integer)?;
+        if scale > 0 {
+            write!(f, ".{}", fraction)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(input: &str) -> Decimal {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn test_exact_arithmetic() {
+        let sum = Decimal::add(&decimal("0.1"), &decimal("0.2")).unwrap();
+        assert_eq!(sum, decimal("0.3"));
+        assert_eq!(sum.to_string(), "0.3");
+
+        let product = Decimal::mul(&decimal("19.99"), &decimal("3")).unwrap();
+        assert_eq!(product.to_string(), "59.97");
+        assert_eq!(
+            Decimal::sub(&decimal("1"), &decimal("1.05"))
+                .unwrap()
+                .to_string(),
+            "-0.05"
+        );
+        assert_eq!(Decimal::from_f64(0.1).unwrap(), decimal("0.1"));
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let round = |input: &str, rounding| decimal(input).round(2, rounding).unwrap().to_string();
+
+        assert_eq!(round("2.345", RoundingMode::HalfEven), "2.34");
+        assert_eq!(round("2.355", RoundingMode::HalfEven), "2.36");
+        assert_eq!(round("2.345", RoundingMode::HalfUp), "2.35");
+        assert_eq!(round("-2.345", RoundingMode::HalfUp), "-2.35");
+        assert_eq!(round("2.349", RoundingMode::Truncate), "2.34");
+        assert_eq!(round("-2.349", RoundingMode::Truncate), "-2.34");
+        assert_eq!(round("7", RoundingMode::Truncate), "7.00");
+    }
+
+    #[test]
+    fn test_division() {
+        let mode = DecimalMode::new(2, RoundingMode::HalfUp);
+
+        let third = Decimal::div(&decimal("100"), &decimal("3"), mode).unwrap();
+        assert_eq!(third.to_string(), "33.33");
+        let two_thirds = Decimal::div(&decimal("2"), &decimal("0.03"), mode).unwrap();
+        assert_eq!(two_thirds.to_string(), "66.67");
+        assert_eq!(
+            Decimal::div(&decimal("1"), &decimal("0"), mode),
+            Err(NumberError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn test_rounded_multiplication() {
+        let mode = DecimalMode::new(4, RoundingMode::HalfEven);
+        let third = Decimal::div(&decimal("1"), &decimal("3"), mode).unwrap();
+
+        assert_eq!(
+            Decimal::mul_rounded(&third, &third, mode)
+                .unwrap()
+                .to_string(),
+            "0.1111"
+        );
+        assert_eq!(
+            Decimal::powi(&decimal("1.1"), 10, DecimalMode::new(10, mode.rounding))
+                .unwrap()
+                .to_string(),
+            "2.5937424601"
+        );
+        assert_eq!(
+            Decimal::powi(&decimal("2"), -2, mode).unwrap(),
+            decimal("0.25")
+        );
+
+        let big = Decimal::div(
+            &decimal("1"),
+            &decimal("7"),
+            DecimalMode::new(30, mode.rounding),
+        );
+        let product = Decimal::mul_rounded(&big.unwrap(), &decimal("7"), mode).unwrap();
+        assert_eq!(product, decimal("1"));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let big = Decimal::new(i128::MAX, 0);
+        assert_eq!(Decimal::add(&big, &big), Err(NumberError::Overflow));
+        assert_eq!(
+            decimal("1").round(40, RoundingMode::HalfEven),
+            Err(NumberError::Overflow)
+        );
+        assert_eq!(
+            decimal("12345").round(50, RoundingMode::HalfEven),
+            Err(NumberError::Overflow)
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use super::EvalError;
+use crate::integer;
+use crate::parser::{Function, Node};
+use crate::{Decimal, DecimalMode, Number, NumberError};
+
+/// Digits kept after the decimal point in intermediate results, unless the requested scale is larger
+const WORKING_SCALE: u32 = 18;
+
+/// Evaluates an expression with exact base-10 decimals and rounds the result to `mode.scale` digits
+///
+/// Sums, differences, products and integer powers are exact, quotients are rounded to 18 digits
+/// after the point before the final rounding, like a calculator would. Functions, pi, e and
+/// fractional powers have no exact decimal value and go through `f64`.
+pub fn evaluate(
+    node: &Node,
+    variables: &HashMap<String, Decimal>,
+    mode: DecimalMode,
+) -> Result<Decimal, EvalError> {
+    let working = DecimalMode::new(mode.scale.max(WORKING_SCALE), mode.rounding);
+    Ok(eval(node, variables, working)?.round(mode.scale, mode.rounding)?)
+}
+
+fn eval(
+    node: &Node,
+    variables: &HashMap<String, Decimal>,
+    mode: DecimalMode,
+) -> Result<Decimal, EvalError> {
+    let eval = |node: &Node| eval(node, variables, mode);
+    let float = |n: &Node, f: fn(&Number) -> Result<Number, NumberError>| {
+        from_number(f(&eval(n)?.to_number())?, mode)
+    };
+
+    Ok(match node {
+        Node::Number(number) => from_number(number.clone(), mode)?,
+        Node::Variable(name) => *variables
+            .get(name)
+            .ok_or_else(|| EvalError::UnboundVariable(name.clone()))?,
+        Node::PiConstant => from_number(Number::real(std::f64::consts::PI), mode)?,
+        Node::EConstant => from_number(Number::real(std::f64::consts::E), mode)?,
+        Node::IConstant => return Err(EvalError::DomainError("complex number")),
+        Node::Add(l, r) => Decimal::add(&eval(l)?, &eval(r)?)?,
+        Node::Sub(l, r) => Decimal::sub(&eval(l)?, &eval(r)?)?,
+        Node::Mul(l, r) => Decimal::mul_rounded(&eval(l)?, &eval(r)?, mode)?,
+        Node::Div(l, r) => Decimal::div(&eval(l)?, &eval(r)?, mode)?,
+        Node::Pow(l, r) => {
+            let (base, exponent) = (eval(l)?, eval(r)?);
+            match exponent.to_integer().and_then(|n| i64::try_from(n).ok()) {
+                Some(n) => Decimal::powi(&base, n, mode)?,
+                None => {
+                    let power = Number::checked_pow(&base.to_number(), &exponent.to_number())?;
+                    from_number(power, mode)?
+                }
+            }
+        }
+        Node::Exp(n) => float(n, |x| Number::exp(x).checked())?,
+        Node::Log(n) => float(n, |x| {
+            if x.re() < 0.0 {
+                return Err(NumberError::Domain("ln of a negative number"));
+            }
+            Number::checked_ln(x)
+        })?,
+        Node::Sin(n) => float(n, |x| Number::sin(x).checked())?,
+        Node::Cos(n) => float(n, |x| Number::cos(x).checked())?,
+        Node::Tan(n) => float(n, |x| Number::tan(x).checked())?,
+        Node::Sqrt(n) => float(n, |x| {
+            if x.re() < 0.0 {
+                return Err(NumberError::Domain("sqrt of a negative number"));
+            }
+            Ok(Number::sqrt(x))
+        })?,
+        Node::Neg(n) => Decimal::neg(&eval(n)?),
+        Node::Function(Function::Floor, arguments) => eval(&arguments[0])?.floor()?,
+        Node::Function(Function::Ceil, arguments) => eval(&arguments[0])?.ceil()?,
+        Node::Function(function, arguments) if function.binds_index() => {
+            let (a, b) = (eval(&arguments[2])?.to_f64(), eval(&arguments[3])?.to_f64());
+            let identity = Decimal::new(i128::from(*function == Function::Product), 0);
+            let (mut terms, reversed) = integer::terms(arguments, a, b)?;
+            let result = terms.try_fold(identity, |result, term| {
+                let term = eval(&term)?;
+                Ok::<_, EvalError>(match function {
+                    Function::Sum => Decimal::add(&result, &term)?,
+                    _ => Decimal::mul_rounded(&result, &term, mode)?,
+                })
+            })?;
+            match function {
+                _ if !reversed => result,
+                Function::Sum => Decimal::neg(&result),
+                _ => Decimal::div(&identity, &result, mode)?,
+            }
+        }
+        Node::Function(function, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|n| Ok(eval(n)?.to_number()))
+                .collect::<Result<Vec<_>, EvalError>>()?;
+            from_number(integer::evaluate(*function, &arguments)?, mode)?
+        }
+    })
+}
+
+/// Numbers keep the digits they were written with, so `0.123456789012345678` isn't rounded to an
+/// f64 first. Literals like `1e3` that aren't plain decimals go through the float.
+fn from_number(number: Number, mode: DecimalMode) -> Result<Decimal, EvalError> {
+    if !number.is_real() {
+        return Err(EvalError::DomainError("complex number"));
+    }
+    let decimal = match number.literal().and_then(|literal| literal.parse().ok()) {
+        Some(decimal) => decimal,
+        None => Decimal::from_f64(number.value())?,
+    };
+    Ok(decimal.trimmed(mode)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+    use crate::RoundingMode;
+
+    fn eval(input: &str, scale: u32, rounding: RoundingMode) -> Result<Decimal, EvalError> {
+        let node = parse(&tokenize(input).unwrap()).unwrap();
+        evaluate(&node, &HashMap::new(), DecimalMode::new(scale, rounding))
+    }
+
+    fn evaluated(input: &str, scale: u32) -> String {
+        eval(input, scale, RoundingMode::HalfEven)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_exact_decimals() {
+        assert_eq!(evaluated("0.1 + 0.2", 2), "0.30");
+        assert_eq!(
+            eval("0.1 + 0.2", 1, RoundingMode::Truncate).unwrap(),
+            "0.3".parse().unwrap()
+        );
+        assert_eq!(evaluated("19.99 * 3 - 0.97", 2), "59.00");
+        assert_eq!(evaluated("100 / 3", 4), "33.3333");
+    }
+
+    #[test]
+    fn test_literals_keep_their_digits() {
+        assert_eq!(
+            evaluated("0.123456789012345678", 18),
+            "0.123456789012345678"
+        );
+        assert_eq!(evaluated("12345678901234567.89", 2), "12345678901234567.89");
+        assert_eq!(evaluated("2^64 + 1", 0), "18446744073709551617");
+        assert_eq!(evaluated("1.5e3", 0), "1500");
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        assert_eq!(evaluated("2.675 * 1", 2), "2.68");
+        assert_eq!(evaluated("0.125 + 0", 2), "0.12");
+        assert_eq!(
+            eval("0.125", 2, RoundingMode::HalfUp).unwrap().to_string(),
+            "0.13"
+        );
+        assert_eq!(
+            eval("2 / 3", 2, RoundingMode::Truncate)
+                .unwrap()
+                .to_string(),
+            "0.66"
+        );
+    }
+
+    #[test]
+    fn test_compound_interest() {
+        // 1000 at 5% a year, compounded monthly for 10 years
+        assert_eq!(evaluated("1000 * (1 + 0.05/12)^120", 2), "1647.01");
+        assert_eq!(evaluated("sqrt(2)", 4), "1.4142");
+    }
+
+    #[test]
+    fn test_integer_functions() {
+        assert_eq!(evaluated("floor(2.999) + floor(-0.001)", 0), "1");
+        assert_eq!(evaluated("ceil(-2.5) + mod(7.5, 2)", 1), "-0.5");
+        assert_eq!(evaluated("10! / 2", 0), "1814400");
+        assert_eq!(evaluated("sum(k^2, k, 1, 3)", 2), "14.00");
+        assert_eq!(
+            evaluated("sum(0.1, k, 1, 10)", 20),
+            "1.00000000000000000000"
+        );
+        assert_eq!(evaluated("product(1.1, k, 1, 2)", 2), "1.21");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            eval("1/0", 2, RoundingMode::HalfEven),
+            Err(EvalError::DivisionByZero)
+        );
+        assert_eq!(
+            eval("i", 2, RoundingMode::HalfEven),
+            Err(EvalError::DomainError("complex number"))
+        );
+        assert!(matches!(
+            eval("sum(k^2, k, 1, 2.5)", 2, RoundingMode::HalfEven),
+            Err(EvalError::DomainError(_))
+        ));
+        let node = parse(&tokenize("x * 2").unwrap()).unwrap();
+        let variables = HashMap::from([("x".to_string(), "1.25".parse().unwrap())]);
+        let mode = DecimalMode::new(2, RoundingMode::HalfEven);
+        assert_eq!(
+            evaluate(&node, &variables, mode).unwrap().to_string(),
+            "2.50"
+        );
+    }
+}
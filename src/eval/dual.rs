@@ -0,0 +1,690 @@
+use std::collections::HashMap;
+use std::f64::consts::{E, PI};
+
+use super::EvalError;
+use crate::integer;
+use crate::parser::{Function, Node};
+use crate::Number;
+
+/// A value together with its partial derivatives with respect to a set of chosen variables
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dual {
+    value: f64,
+    derivatives: Vec<f64>,
+}
+
+impl Dual {
+    fn constant(value: f64, count: usize) -> Dual {
+        Dual {
+            value,
+            derivatives: vec![0.0; count],
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Partial derivatives, in the same order as the variables they were taken with respect to
+    pub fn derivatives(&self) -> &[f64] {
+        &self.derivatives
+    }
+
+    /// Applies a function with the given value and derivative at self.value, using the chain rule
+    ///
+    /// A zero partial stays zero even where the derivative is infinite, since the input doesn't
+    /// change in that direction.
+    fn chain(&self, value: f64, derivative: f64) -> Dual {
+        let scale = |d: &f64| if *d == 0.0 { 0.0 } else { d * derivative };
+        Dual {
+            value,
+            derivatives: self.derivatives.iter().map(scale).collect(),
+        }
+    }
+
+    /// Turns infinite values or partials into [`EvalError::Overflow`] and NaNs into
+    /// [`EvalError::NaN`], the same way [`Number::checked`] does
+    fn checked(self) -> Result<Dual, EvalError> {
+        let parts = || std::iter::once(&self.value).chain(&self.derivatives);
+        // Overflowed terms turn into NaN once multiplied by zero, so they're reported first
+        if parts().any(|x| x.is_infinite()) {
+            return Err(EvalError::Overflow);
+        }
+        if parts().any(|x| x.is_nan()) {
+            return Err(EvalError::NaN);
+        }
+        Ok(self)
+    }
+
+    fn zip(&self, other: &Dual, value: f64, f: impl Fn(f64, f64) -> f64) -> Dual {
+        Dual {
+            value,
+            derivatives: self
+                .derivatives
+                .iter()
+                .zip(&other.derivatives)
+                .map(|(a, b)| f(*a, *b))
+                .collect(),
+        }
+    }
+
+    fn add(&self, other: &Dual) -> Dual {
+        self.zip(other, self.value + other.value, |a, b| a + b)
+    }
+
+    fn sub(&self, other: &Dual) -> Dual {
+        self.zip(other, self.value - other.value, |a, b| a - b)
+    }
+
+    fn mul(&self, other: &Dual) -> Dual {
+        self.zip(other, self.value * other.value, |a, b| {
+            a * other.value + self.value * b
+        })
+    }
+
+    fn div(&self, other: &Dual) -> Result<Dual, EvalError> {
+        if other.value == 0.0 {
+            return Err(EvalError::DivisionByZero);
+        }
+        let value = self.value / other.value;
+        Ok(self.zip(other, value, |a, b| (a - value * b) / other.value))
+    }
+
+    fn pow(&self, other: &Dual) -> Result<Dual, EvalError> {
+        // A constant exponent also works for negative bases
+        if other.derivatives.iter().all(|d| *d == 0.0) {
+            let exponent = other.value;
+            let value =
+                Number::checked_pow(&Number::real(self.value), &Number::real(exponent))?.value();
+            let derivative = if exponent == 0.0 {
+                0.0
+            } else {
+                exponent * self.value.powf(exponent - 1.0)
+            };
+            if !derivative.is_finite() && self.derivatives.iter().any(|d| *d != 0.0) {
+                return Err(EvalError::DomainError("power is not differentiable at 0"));
+            }
+            return Ok(self.chain(value, derivative));
+        }
+
+        if self.value <= 0.0 {
+            return Err(EvalError::DomainError(
+                "non-positive base with a variable exponent",
+            ));
+        }
+        Ok(other.mul(&self.ln()?).exp())
+    }
+
+    fn exp(&self) -> Dual {
+        let value = self.value.exp();
+        self.chain(value, value)
+    }
+
+    fn ln(&self) -> Result<Dual, EvalError> {
+        if self.value <= 0.0 {
+            return Err(EvalError::DomainError("ln of a non-positive number"));
+        }
+        Ok(self.chain(self.value.ln(), 1.0 / self.value))
+    }
+
+    fn sqrt(&self) -> Result<Dual, EvalError> {
+        if self.value < 0.0 {
+            return Err(EvalError::DomainError("sqrt of a negative number"));
+        }
+        if self.value == 0.0 && self.derivatives.iter().any(|d| *d != 0.0) {
+            return Err(EvalError::DomainError("sqrt is not differentiable at 0"));
+        }
+        let value = self.value.sqrt();
+        Ok(self.chain(value, 0.5 / value))
+    }
+}
+
+/// A truncated Taylor series `c_0 + c_1 t + ... + c_n t^n` of an expression around a point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Taylor {
+    coefficients: Vec<f64>,
+}
+
+impl Taylor {
+    fn constant(value: f64, order: usize) -> Taylor {
+        let mut coefficients = vec![0.0; order + 1];
+        coefficients[0] = value;
+        Taylor { coefficients }
+    }
+
+    fn variable(value: f64, order: usize) -> Taylor {
+        let mut taylor = Taylor::constant(value, order);
+        if order > 0 {
+            taylor.coefficients[1] = 1.0;
+        }
+        taylor
+    }
+
+    /// The Taylor coefficients, the k-th coefficient is the k-th derivative divided by k!
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    pub fn value(&self) -> f64 {
+        self.coefficients[0]
+    }
+
+    /// The k-th derivative, or None if it's above the order the series was computed to
+    pub fn derivative(&self, k: usize) -> Option<f64> {
+        let factorial: f64 = (1..=k).map(|i| i as f64).product();
+        self.coefficients.get(k).map(|c| c * factorial)
+    }
+
+    /// Turns infinite coefficients into [`EvalError::Overflow`] and NaNs into [`EvalError::NaN`]
+    fn checked(self) -> Result<Taylor, EvalError> {
+        if self.coefficients.iter().any(|c| c.is_infinite()) {
+            return Err(EvalError::Overflow);
+        }
+        if self.coefficients.iter().any(|c| c.is_nan()) {
+            return Err(EvalError::NaN);
+        }
+        Ok(self)
+    }
+
+    fn order(&self) -> usize {
+        self.coefficients.len() - 1
+    }
+
+    fn is_constant(&self) -> bool {
+        self.coefficients[1..].iter().all(|c| *c == 0.0)
+    }
+
+    fn map(&self, f: impl Fn(f64) -> f64) -> Taylor {
+        Taylor {
+            coefficients: self.coefficients.iter().map(|c| f(*c)).collect(),
+        }
+    }
+
+    fn add(&self, other: &Taylor) -> Taylor {
+        Taylor {
+            coefficients: (0..=self.order())
+                .map(|k| self.coefficients[k] + other.coefficients[k])
+                .collect(),
+        }
+    }
+
+    fn sub(&self, other: &Taylor) -> Taylor {
+        self.add(&other.map(|c| -c))
+    }
+
+    fn mul(&self, other: &Taylor) -> Taylor {
+        Taylor {
+            coefficients: (0..=self.order())
+                .map(|k| {
+                    (0..=k)
+                        .map(|j| self.coefficients[j] * other.coefficients[k - j])
+                        .sum()
+                })
+                .collect(),
+        }
+    }
+
+    fn div(&self, other: &Taylor) -> Result<Taylor, EvalError> {
+        let b = &other.coefficients;
+        if b[0] == 0.0 {
+            return Err(EvalError::DivisionByZero);
+        }
+        let mut c = vec![0.0; self.order() + 1];
+        for k in 0..=self.order() {
+            let sum: f64 = (1..=k).map(|j| b[j] * c[k - j]).sum();
+            c[k] = (self.coefficients[k] - sum) / b[0];
+        }
+        Ok(Taylor { coefficients: c })
+    }
+
+    fn exp(&self) -> Taylor {
+        let a = &self.coefficients;
+        let mut e = vec![a[0].exp(); self.order() + 1];
+        for k in 1..=self.order() {
+            let sum: f64 = (1..=k).map(|j| j as f64 * a[j] * e[k - j]).sum();
+            e[k] = sum / k as f64;
+        }
+        Taylor { coefficients: e }
+    }
+
+    fn ln(&self) -> Result<Taylor, EvalError> {
+        let a = &self.coefficients;
+        if a[0] <= 0.0 {
+            return Err(EvalError::DomainError("ln of a non-positive number"));
+        }
+        let mut l = vec![a[0].ln(); self.order() + 1];
+        for k in 1..=self.order() {
+            let sum: f64 = (1..k).map(|j| j as f64 * l[j] * a[k - j]).sum();
+            l[k] = (a[k] - sum / k as f64) / a[0];
+        }
+        Ok(Taylor { coefficients: l })
+    }
+
+    fn sin_cos(&self) -> (Taylor, Taylor) {
+        let a = &self.coefficients;
+        let mut s = vec![a[0].sin(); self.order() + 1];
+        let mut c = vec![a[0].cos(); self.order() + 1];
+        for k in 1..=self.order() {
+            let sum_s: f64 = (1..=k).map(|j| j as f64 * a[j] * c[k - j]).sum();
+            let sum_c: f64 = (1..=k).map(|j| j as f64 * a[j] * s[k - j]).sum();
+            s[k] = sum_s / k as f64;
+            c[k] = -sum_c / k as f64;
+        }
+        (Taylor { coefficients: s }, Taylor { coefficients: c })
+    }
+
+    fn sqrt(&self) -> Result<Taylor, EvalError> {
+        let a = &self.coefficients;
+        if a[0] < 0.0 {
+            return Err(EvalError::DomainError("sqrt of a negative number"));
+        }
+        if a[0] == 0.0 && !self.is_constant() {
+            return Err(EvalError::DomainError("sqrt is not differentiable at 0"));
+        }
+        let mut r = vec![a[0].sqrt(); self.order() + 1];
+        for k in 1..=self.order() {
+            let sum: f64 = (1..k).map(|j| r[j] * r[k - j]).sum();
+            r[k] = (a[k] - sum) / (2.0 * r[0]);
+        }
+        Ok(Taylor { coefficients: r })
+    }
+
+    fn powi(&self, n: u32) -> Taylor {
+        let mut base = self.clone();
+        let mut result = Taylor::constant(1.0, self.order());
+        let mut n = n;
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.mul(&base);
+            }
+            n >>= 1;
+            if n > 0 {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+
+    fn pow(&self, other: &Taylor) -> Result<Taylor, EvalError> {
+        if !other.is_constant() {
+            if self.value() <= 0.0 {
+                return Err(EvalError::DomainError(
+                    "non-positive base with a variable exponent",
+                ));
+            }
+            return Ok(other.mul(&self.ln()?).exp());
+        }
+
+        let p = other.value();
+        if p.fract() == 0.0 && p >= 0.0 && p <= u32::MAX as f64 {
+            return Ok(self.powi(p as u32));
+        }
+        let a = &self.coefficients;
+        if a[0] == 0.0 {
+            return Err(EvalError::DomainError("power is not differentiable at 0"));
+        }
+
+        // Recurrence for y = a^p, from a * y' = p * a' * y
+        let mut y = vec![a[0].powf(p); self.order() + 1];
+        for k in 1..=self.order() {
+            let sum: f64 = (1..=k)
+                .map(|j| (p * j as f64 - (k - j) as f64) * a[j] * y[k - j])
+                .sum();
+            y[k] = sum / (k as f64 * a[0]);
+        }
+        Ok(Taylor { coefficients: y })
+    }
+}
+
+/// floor and ceil have a zero derivative everywhere except at their jumps
+fn is_piecewise_constant(function: Function) -> bool {
+    matches!(function, Function::Floor | Function::Ceil)
+}
+
+fn real_constant(node: &Node) -> Result<Option<f64>, EvalError> {
+    Ok(match node {
+        Node::Number(number) => {
+            if !number.is_real() {
+                return Err(EvalError::DomainError("complex number"));
+            }
+            Some(number.value())
+        }
+        Node::PiConstant => Some(PI),
+        Node::EConstant => Some(E),
+        Node::IConstant => return Err(EvalError::DomainError("complex number")),
+        _ => None,
+    })
+}
+
+/// Evaluates an expression on dual numbers, giving its value and the exact partial derivatives with
+/// respect to each variable in `wrt` in a single pass
+pub fn evaluate(
+    node: &Node,
+    variables: &HashMap<String, f64>,
+    wrt: &[&str],
+) -> Result<Dual, EvalError> {
+    let eval = |node: &Node| evaluate(node, variables, wrt);
+
+    if let Some(value) = real_constant(node)? {
+        return Ok(Dual::constant(value, wrt.len()));
+    }
+
+    let dual = match node {
+        Node::Variable(name) => {
+            let value = *variables
+                .get(name)
+                .ok_or_else(|| EvalError::UnboundVariable(name.clone()))?;
+            Dual {
+                value,
+                derivatives: wrt
+                    .iter()
+                    .map(|v| if v == name { 1.0 } else { 0.0 })
+                    .collect(),
+            }
+        }
+        Node::Add(l, r) => eval(l)?.add(&eval(r)?),
+        Node::Sub(l, r) => eval(l)?.sub(&eval(r)?),
+        Node::Mul(l, r) => eval(l)?.mul(&eval(r)?),
+        Node::Div(l, r) => eval(l)?.div(&eval(r)?)?,
+        Node::Pow(l, r) => eval(l)?.pow(&eval(r)?)?,
+        Node::Exp(n) => eval(n)?.exp(),
+        Node::Log(n) => eval(n)?.ln()?,
+        Node::Sin(n) => {
+            let n = eval(n)?;
+            n.chain(n.value.sin(), n.value.cos())
+        }
+        Node::Cos(n) => {
+            let n = eval(n)?;
+            n.chain(n.value.cos(), -n.value.sin())
+        }
+        Node::Tan(n) => {
+            let n = eval(n)?;
+            let cos = n.value.cos();
+            n.chain(n.value.tan(), 1.0 / (cos * cos))
+        }
+        Node::Sqrt(n) => eval(n)?.sqrt()?,
+        Node::Neg(n) => {
+            let n = eval(n)?;
+            n.chain(-n.value, -1.0)
+        }
+        // The bounds are integers, so they can't depend on the variables the derivatives are
+        // taken for
+        Node::Function(function, arguments) if function.binds_index() => {
+            let bound = |n: &Node| match eval(n)? {
+                bound if bound.derivatives.iter().all(|d| *d == 0.0) => Ok(bound.value),
+                _ => Err(EvalError::DomainError(
+                    "integer function isn't differentiable",
+                )),
+            };
+            let (a, b) = (bound(&arguments[2])?, bound(&arguments[3])?);
+            let identity = Dual::constant(f64::from(*function == Function::Product), wrt.len());
+            let (mut terms, reversed) = integer::terms(arguments, a, b)?;
+            let result = terms.try_fold(identity.clone(), |result, term| {
+                let term = eval(&term)?;
+                Ok::<_, EvalError>(match function {
+                    Function::Sum => result.add(&term),
+                    _ => result.mul(&term),
+                })
+            })?;
+            match function {
+                _ if !reversed => result,
+                Function::Sum => identity.sub(&result),
+                _ => identity.div(&result)?,
+            }
+        }
+        Node::Function(function, arguments) => {
+            let arguments = arguments.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            let constant = arguments
+                .iter()
+                .all(|a| a.derivatives.iter().all(|d| *d == 0.0));
+            if !constant && !is_piecewise_constant(*function) {
+                return Err(EvalError::DomainError(
+                    "integer function isn't differentiable",
+                ));
+            }
+            let values: Vec<f64> = arguments.iter().map(|a| a.value).collect();
+            Dual::constant(integer::evaluate_real(*function, &values)?, wrt.len())
+        }
+        Node::Number(_) | Node::PiConstant | Node::EConstant | Node::IConstant => unreachable!(),
+    };
+    dual.checked()
+}
+
+/// Computes the Taylor series of an expression in `var` up to `order` using truncated Taylor
+/// arithmetic, which gives every derivative up to that order in one pass
+pub fn evaluate_taylor(
+    node: &Node,
+    variables: &HashMap<String, f64>,
+    var: &str,
+    order: usize,
+) -> Result<Taylor, EvalError> {
+    let eval = |node: &Node| evaluate_taylor(node, variables, var, order);
+
+    if let Some(value) = real_constant(node)? {
+        return Ok(Taylor::constant(value, order));
+    }
+
+    let taylor = match node {
+        Node::Variable(name) => {
+            let value = *variables
+                .get(name)
+                .ok_or_else(|| EvalError::UnboundVariable(name.clone()))?;
+            if name == var {
+                Taylor::variable(value, order)
+            } else {
+                Taylor::constant(value, order)
+            }
+        }
+        Node::Add(l, r) => eval(l)?.add(&eval(r)?),
+        Node::Sub(l, r) => eval(l)?.sub(&eval(r)?),
+        Node::Mul(l, r) => eval(l)?.mul(&eval(r)?),
+        Node::Div(l, r) => eval(l)?.div(&eval(r)?)?,
+        Node::Pow(l, r) => eval(l)?.pow(&eval(r)?)?,
+        Node::Exp(n) => eval(n)?.exp(),
+        Node::Log(n) => eval(n)?.ln()?,
+        Node::Sin(n) => eval(n)?.sin_cos().0,
+        Node::Cos(n) => eval(n)?.sin_cos().1,
+        Node::Tan(n) => {
+            let (sin, cos) = eval(n)?.sin_cos();
+            sin.div(&cos)?
+        }
+        Node::Sqrt(n) => eval(n)?.sqrt()?,
+        Node::Neg(n) => eval(n)?.map(|c| -c),
+        Node::Function(function, arguments) if function.binds_index() => {
+            let bound = |n: &Node| match eval(n)? {
+                bound if bound.is_constant() => Ok(bound.value()),
+                _ => Err(EvalError::DomainError(
+                    "integer function isn't differentiable",
+                )),
+            };
+            let (a, b) = (bound(&arguments[2])?, bound(&arguments[3])?);
+            let identity = Taylor::constant(f64::from(*function == Function::Product), order);
+            let (mut terms, reversed) = integer::terms(arguments, a, b)?;
+            let result = terms.try_fold(identity.clone(), |result, term| {
+                let term = eval(&term)?;
+                Ok::<_, EvalError>(match function {
+                    Function::Sum => result.add(&term),
+                    _ => result.mul(&term),
+                })
+            })?;
+            match function {
+                _ if !reversed => result,
+                Function::Sum => identity.sub(&result),
+                _ => identity.div(&result)?,
+            }
+        }
+        Node::Function(function, arguments) => {
+            let arguments = arguments.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            if !arguments.iter().all(Taylor::is_constant) && !is_piecewise_constant(*function) {
+                return Err(EvalError::DomainError(
+                    "integer function isn't differentiable",
+                ));
+            }
+            let values: Vec<f64> = arguments.iter().map(Taylor::value).collect();
+            Taylor::constant(integer::evaluate_real(*function, &values)?, order)
+        }
+        Node::Number(_) | Node::PiConstant | Node::EConstant | Node::IConstant => unreachable!(),
+    };
+    taylor.checked()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9 * expected.abs().max(1.0),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_first_order_partials() {
+        let node = parsed("x^2 * y + sin(x * y) / y");
+        let variables = HashMap::from([("x".to_string(), 1.5), ("y".to_string(), 2.0)]);
+        let dual = evaluate(&node, &variables, &["x", "y"]).unwrap();
+
+        let (x, y) = (1.5f64, 2.0f64);
+        assert_close(dual.value(), x * x * y + (x * y).sin() / y);
+        assert_close(dual.derivatives()[0], 2.0 * x * y + (x * y).cos());
+        assert_close(
+            dual.derivatives()[1],
+            x * x + (x * (x * y).cos() * y - (x * y).sin()) / (y * y),
+        );
+    }
+
+    #[test]
+    fn test_variable_exponent() {
+        let node = parsed("x^x");
+        let variables = HashMap::from([("x".to_string(), 2.0)]);
+        let dual = evaluate(&node, &variables, &["x"]).unwrap();
+
+        assert_close(dual.value(), 4.0);
+        assert_close(dual.derivatives()[0], 4.0 * (2f64.ln() + 1.0));
+    }
+
+    #[test]
+    fn test_higher_order_derivatives() {
+        let variables = HashMap::from([("x".to_string(), 0.5)]);
+
+        let taylor = evaluate_taylor(&parsed("e^(2*x)"), &variables, "x", 5).unwrap();
+        for k in 0..=5 {
+            assert_close(
+                taylor.derivative(k).unwrap(),
+                2f64.powi(k as i32) * 1f64.exp(),
+            );
+        }
+
+        let taylor = evaluate_taylor(&parsed("sin(x)"), &variables, "x", 4).unwrap();
+        assert_close(taylor.derivative(3).unwrap(), -(0.5f64.cos()));
+        assert_close(taylor.derivative(4).unwrap(), 0.5f64.sin());
+
+        let taylor = evaluate_taylor(&parsed("ln(x) + sqrt(x) - 1/x"), &variables, "x", 2).unwrap();
+        assert_close(
+            taylor.derivative(1).unwrap(),
+            2.0 + 0.5 / 0.5f64.sqrt() + 4.0,
+        );
+        assert_close(
+            taylor.derivative(2).unwrap(),
+            -4.0 - 0.25 * 0.5f64.powf(-1.5) - 16.0,
+        );
+        assert_eq!(taylor.derivative(3), None);
+    }
+
+    #[test]
+    fn test_taylor_of_tan_and_powers() {
+        let variables = HashMap::from([("x".to_string(), 0.0)]);
+        let taylor = evaluate_taylor(&parsed("tan(x)"), &variables, "x", 5).unwrap();
+        let expected = [0.0, 1.0, 0.0, 1.0 / 3.0, 0.0, 2.0 / 15.0];
+        for (actual, expected) in taylor.coefficients().iter().zip(expected) {
+            assert_close(*actual, expected);
+        }
+
+        let variables = HashMap::from([("x".to_string(), 4.0)]);
+        let taylor = evaluate_taylor(&parsed("x^1.5"), &variables, "x", 2).unwrap();
+        assert_close(taylor.derivative(1).unwrap(), 1.5 * 2.0);
+        assert_close(taylor.derivative(2).unwrap(), 0.75 / 2.0);
+    }
+
+    #[test]
+    fn test_sqrt_at_zero() {
+        let variables = HashMap::from([("x".to_string(), 0.0), ("y".to_string(), 2.0)]);
+
+        // Only the partial for y is asked for, and y doesn't go through the sqrt
+        let dual = evaluate(&parsed("sqrt(x) + y"), &variables, &["y"]).unwrap();
+        assert_eq!(dual.value(), 2.0);
+        assert_eq!(dual.derivatives(), &[1.0]);
+
+        assert_eq!(
+            evaluate(&parsed("sqrt(x) + y"), &variables, &["x", "y"]),
+            Err(EvalError::DomainError("sqrt is not differentiable at 0"))
+        );
+    }
+
+    #[test]
+    fn test_non_finite_powers() {
+        let at = |x: f64| HashMap::from([("x".to_string(), x)]);
+        assert_eq!(
+            evaluate(&parsed("x^0.5"), &at(0.0), &["x"]),
+            Err(EvalError::DomainError("power is not differentiable at 0"))
+        );
+        assert!(matches!(
+            evaluate(&parsed("x^0.5"), &at(-1.0), &["x"]),
+            Err(EvalError::DomainError(_))
+        ));
+        assert_eq!(
+            evaluate(&parsed("e^(1000*x)"), &at(1.0), &["x"]),
+            Err(EvalError::Overflow)
+        );
+        assert_eq!(
+            evaluate_taylor(&parsed("e^(1000*x)"), &at(1.0), "x", 2),
+            Err(EvalError::Overflow)
+        );
+        let dual = evaluate(&parsed("x^2"), &at(0.0), &["x"]).unwrap();
+        assert_eq!(dual.derivatives(), &[0.0]);
+    }
+
+    #[test]
+    fn test_domain_errors() {
+        let variables = HashMap::from([("x".to_string(), 0.0)]);
+
+        assert_eq!(
+            evaluate(&parsed("1 / x"), &variables, &["x"]),
+            Err(EvalError::DivisionByZero)
+        );
+        assert!(evaluate_taylor(&parsed("ln(x)"), &variables, "x", 2).is_err());
+        assert!(evaluate(&parsed("i * x"), &variables, &["x"]).is_err());
+        assert!(matches!(
+            evaluate(&parsed("sum(k, k, 1, x)"), &variables, &["x"]),
+            Err(EvalError::DomainError(_))
+        ));
+        assert!(matches!(
+            evaluate_taylor(&parsed("product(k, k, x, 3)"), &variables, "x", 2),
+            Err(EvalError::DomainError(_))
+        ));
+    }
+
+    #[test]
+    fn test_sums_and_products() {
+        let variables = HashMap::from([("x".to_string(), 2.0), ("n".to_string(), 3.0)]);
+
+        let dual = evaluate(&parsed("sum(k*x^k, k, 1, n)"), &variables, &["x"]).unwrap();
+        assert_eq!(dual.value(), 2.0 + 8.0 + 24.0);
+        assert_eq!(dual.derivatives(), &[1.0 + 8.0 + 36.0]);
+        let dual = evaluate(&parsed("sum(x, k, 1, 0)"), &variables, &["x"]).unwrap();
+        assert_eq!((dual.value(), dual.derivatives()), (0.0, &[0.0][..]));
+
+        // x(x + 1)(x + 2) = x^3 + 3x^2 + 2x
+        let taylor = evaluate_taylor(&parsed("product(x + k, k, 0, 2)"), &variables, "x", 3);
+        assert_eq!(taylor.unwrap().coefficients(), &[24.0, 26.0, 9.0, 1.0]);
+    }
+}
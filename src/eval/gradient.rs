@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::f64::consts::{E, PI};
+
+use super::EvalError;
+use crate::integer;
+use crate::parser::{Function, Node};
+use crate::Number;
+
+/// The value of an expression together with its partial derivative for every variable in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    value: f64,
+    partials: HashMap<String, f64>,
+}
+
+impl Gradient {
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn partials(&self) -> &HashMap<String, f64> {
+        &self.partials
+    }
+
+    /// The partial derivative with respect to `name`, which is zero for variables the expression
+    /// doesn't depend on
+    pub fn partial(&self, name: &str) -> f64 {
+        self.partials.get(name).copied().unwrap_or(0.0)
+    }
+}
+
+/// One evaluated node, the edges point to the entries of its children together with the partial
+/// derivative of this node with respect to that child
+struct Entry {
+    value: f64,
+    variable: Option<String>,
+    depends_on_variables: bool,
+    edges: Vec<(usize, f64)>,
+}
+
+struct Tape {
+    entries: Vec<Entry>,
+}
+
+impl Tape {
+    /// Records an entry, the value must be finite and so must the local derivative with respect
+    /// to every child that depends on a variable. Edges to constants carry no adjoint, so theirs
+    /// are dropped.
+    fn push(&mut self, value: f64, edges: Vec<(usize, f64)>) -> Result<usize, EvalError> {
+        if value.is_infinite() {
+            return Err(EvalError::Overflow);
+        }
+        if value.is_nan() {
+            return Err(EvalError::NaN);
+        }
+        let edges: Vec<(usize, f64)> = edges
+            .into_iter()
+            .filter(|(child, _)| self.entries[*child].depends_on_variables)
+            .collect();
+        if edges.iter().any(|(_, derivative)| !derivative.is_finite()) {
+            return Err(EvalError::DomainError("not differentiable at this point"));
+        }
+        self.entries.push(Entry {
+            value,
+            variable: None,
+            depends_on_variables: !edges.is_empty(),
+            edges,
+        });
+        Ok(self.entries.len() - 1)
+    }
+
+    fn value(&self, index: usize) -> f64 {
+        self.entries[index].value
+    }
+
+    /// The forward sweep, evaluates the tree and records the local derivatives
+    fn record(
+        &mut self,
+        node: &Node,
+        variables: &HashMap<String, f64>,
+    ) -> Result<usize, EvalError> {
+        let unary = |tape: &mut Tape, n: &Node, f: fn(f64) -> Result<(f64, f64), EvalError>| {
+            let child = tape.record(n, variables)?;
+            let (value, derivative) = f(tape.value(child))?;
+            tape.push(value, vec![(child, derivative)])
+        };
+
+        match node {
+            Node::Number(number) => {
+                if !number.is_real() {
+                    return Err(EvalError::DomainError("complex number"));
+                }
+                self.push(number.value(), vec![])
+            }
+            Node::PiConstant => self.push(PI, vec![]),
+            Node::EConstant => self.push(E, vec![]),
+            Node::IConstant => Err(EvalError::DomainError("complex number")),
+            Node::Variable(name) => {
+                let value = *variables
+                    .get(name)
+                    .ok_or_else(|| EvalError::UnboundVariable(name.clone()))?;
+                self.entries.push(Entry {
+                    value,
+                    variable: Some(name.clone()),
+                    depends_on_variables: true,
+                    edges: vec![],
+                });
+                Ok(self.entries.len() - 1)
+            }
+            Node::Add(l, r) => {
+                let (l, r) = (self.record(l, variables)?, self.record(r, variables)?);
+                self.push(self.value(l) + self.value(r), vec![(l, 1.0), (r, 1.0)])
+            }
+            Node::Sub(l, r) => {
+                let (l, r) = (self.record(l, variables)?, self.record(r, variables)?);
+                self.push(self.value(l) - self.value(r), vec![(l, 1.0), (r, -1.0)])
+            }
+            Node::Mul(l, r) => {
+                let (l, r) = (self.record(l, variables)?, self.record(r, variables)?);
+                let (a, b) = (self.value(l), self.value(r));
+                self.push(a * b, vec![(l, b), (r, a)])
+            }
+            Node::Div(l, r) => {
+                let (l, r) = (self.record(l, variables)?, self.record(r, variables)?);
+                let (a, b) = (self.value(l), self.value(r));
+                if b == 0.0 {
+                    return Err(EvalError::DivisionByZero);
+                }
+                self.push(a / b, vec![(l, 1.0 / b), (r, -a / (b * b))])
+            }
+            Node::Pow(l, r) => {
+                let (l, r) = (self.record(l, variables)?, self.record(r, variables)?);
+                let (a, b) = (self.value(l), self.value(r));
+                let value = Number::checked_pow(&Number::real(a), &Number::real(b))?.value();
+                let d_base = if b == 0.0 { 0.0 } else { b * a.powf(b - 1.0) };
+                let d_exponent = if a > 0.0 {
+                    value * a.ln()
+                } else if self.entries[r].depends_on_variables {
+                    return Err(EvalError::DomainError(
+                        "non-positive base with a variable exponent",
+                    ));
+                } else {
+                    0.0
+                };
+                self.push(value, vec![(l, d_base), (r, d_exponent)])
+            }
+            Node::Exp(n) => unary(self, n, |x| Ok((x.exp(), x.exp()))),
+            Node::Log(n) => unary(self, n, |x| {
+                if x <= 0.0 {
+                    return Err(EvalError::DomainError("ln of a non-positive number"));
+                }
+                Ok((x.ln(), 1.0 / x))
+            }),
+            Node::Sin(n) => unary(self, n, |x| Ok((x.sin(), x.cos()))),
+            Node::Cos(n) => unary(self, n, |x| Ok((x.cos(), -x.sin()))),
+            Node::Tan(n) => unary(self, n, |x| Ok((x.tan(), 1.0 / (x.cos() * x.cos())))),
+            Node::Sqrt(n) => unary(self, n, |x| {
+                if x < 0.0 {
+                    return Err(EvalError::DomainError("sqrt of a negative number"));
+                }
+                Ok((x.sqrt(), 0.5 / x.sqrt()))
+            }),
+            Node::Neg(n) => unary(self, n, |x| Ok((-x, -1.0))),
+            Node::Function(function, arguments) if function.binds_index() => {
+                let mut bound = |n: &Node| {
+                    let bound = self.record(n, variables)?;
+                    if self.entries[bound].depends_on_variables {
+                        return Err(EvalError::DomainError(
+                            "integer function isn't differentiable",
+                        ));
+                    }
+                    Ok(self.value(bound))
+                };
+                let (a, b) = (bound(&arguments[2])?, bound(&arguments[3])?);
+                let identity = f64::from(*function == Function::Product);
+                let mut result = self.push(identity, vec![])?;
+                let (terms, reversed) = integer::terms(arguments, a, b)?;
+                for term in terms {
+                    let term = self.record(&term, variables)?;
+                    let (x, y) = (self.value(result), self.value(term));
+                    result = match function {
+                        Function::Sum => self.push(x + y, vec![(result, 1.0), (term, 1.0)])?,
+                        _ => self.push(x * y, vec![(result, y), (term, x)])?,
+                    };
+                }
+                let x = self.value(result);
+                match function {
+                    _ if !reversed => Ok(result),
+                    Function::Sum => self.push(-x, vec![(result, -1.0)]),
+                    _ if x == 0.0 => Err(EvalError::DivisionByZero),
+                    _ => self.push(1.0 / x, vec![(result, -1.0 / (x * x))]),
+                }
+            }
+            Node::Function(function, arguments) => {
+                let children = arguments
+                    .iter()
+                    .map(|n| self.record(n, variables))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let piecewise_constant = matches!(function, Function::Floor | Function::Ceil);
+                if !piecewise_constant
+                    && children
+                        .iter()
+                        .any(|c| self.entries[*c].depends_on_variables)
+                {
+                    return Err(EvalError::DomainError(
+                        "integer function isn't differentiable",
+                    ));
+                }
+                let values: Vec<f64> = children.iter().map(|c| self.value(*c)).collect();
+                let value = integer::evaluate_real(*function, &values)?;
+                self.push(value, vec![])
+            }
+        }
+    }
+}
+
+/// Computes the value and the full gradient of an expression in reverse mode.
+///
+/// The tree is evaluated once while the local derivatives are recorded on a tape, and a single
+/// backward sweep over the tape then gives the partial derivative for every variable, so the cost
+/// doesn't grow with the number of variables.
+pub fn gradient(node: &Node, variables: &HashMap<String, f64>) -> Result<Gradient, EvalError> {
+    let mut tape = Tape { entries: vec![] };
+    let root = tape.record(node, variables)?;
+
+    // Children are always recorded before their parents, so walking the tape backwards visits
+    // every entry after all of its parents
+    let mut adjoints = vec![0.0; tape.entries.len()];
+    adjoints[root] = 1.0;
+    let mut partials = HashMap::new();
+    for (index, entry) in tape.entries.iter().enumerate().rev() {
+        let adjoint = adjoints[index];
+        if let Some(name) = &entry.variable {
+            *partials.entry(name.clone()).or_insert(0.0) += adjoint;
+        }
+        for (child, derivative) in &entry.edges {
+            adjoints[*child] += adjoint * derivative;
+        }
+    }
+
+    Ok(Gradient {
+        value: tape.value(root),
+        partials,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9 * expected.abs().max(1.0),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_gradient() {
+        let node = parsed("x * y + sin(x) * z^2 - y / z");
+        let variables = HashMap::from([
+            ("x".to_string(), 0.5),
+            ("y".to_string(), 2.0),
+            ("z".to_string(), 3.0),
+        ]);
+        let result = gradient(&node, &variables).unwrap();
+
+        let (x, y, z) = (0.5f64, 2.0, 3.0);
+        assert_close(result.value(), x * y + x.sin() * z * z - y / z);
+        assert_close(result.partial("x"), y + x.cos() * z * z);
+        assert_close(result.partial("y"), x - 1.0 / z);
+        assert_close(result.partial("z"), 2.0 * x.sin() * z + y / (z * z));
+        assert_eq!(result.partials().len(), 3);
+    }
+
+    #[test]
+    fn test_many_variables() {
+        // sum of (x_k - k)^2 has the partials 2 * (x_k - k)
+        let names: Vec<String> = (0..40u8)
+            .map(|k| format!("x{}{}", (b'a' + k / 26) as char, (b'a' + k % 26) as char))
+            .collect();
+        let node = names
+            .iter()
+            .enumerate()
+            .map(|(k, name)| format!("({} - {})^2", name, k))
+            .collect::<Vec<_>>()
+            .join(" + ");
+        let variables = names.iter().map(|name| (name.clone(), 1.0)).collect();
+        let result = gradient(&parsed(&node), &variables).unwrap();
+
+        for (k, name) in names.iter().enumerate() {
+            assert_close(result.partial(name), 2.0 * (1.0 - k as f64));
+        }
+    }
+
+    #[test]
+    fn test_repeated_variables_and_powers() {
+        let variables = HashMap::from([("x".to_string(), 2.0), ("y".to_string(), -1.0)]);
+
+        let result = gradient(&parsed("x^x + e^(x*x)"), &variables).unwrap();
+        assert_close(
+            result.partial("x"),
+            4.0 * (2f64.ln() + 1.0) + 4.0 * 4f64.exp(),
+        );
+
+        let result = gradient(&parsed("y^3"), &variables).unwrap();
+        assert_close(result.partial("y"), 3.0);
+
+        assert!(gradient(&parsed("y^x"), &variables).is_err());
+    }
+
+    #[test]
+    fn test_sums_and_products() {
+        let variables = HashMap::from([("x".to_string(), 2.0), ("y".to_string(), 3.0)]);
+
+        let result = gradient(&parsed("sum(k*x^k, k, 1, 3)"), &variables).unwrap();
+        assert_close(result.value(), 2.0 + 8.0 + 24.0);
+        assert_close(result.partial("x"), 1.0 + 8.0 + 36.0);
+        assert_eq!(result.partials().len(), 1);
+
+        let result = gradient(&parsed("product(x + k*y, k, 0, 1)"), &variables).unwrap();
+        assert_close(result.partial("x"), 2.0 * 2.0 + 3.0);
+        assert_close(result.partial("y"), 2.0);
+
+        assert!(matches!(
+            gradient(&parsed("sum(k*x, k, 1, x)"), &variables),
+            Err(EvalError::DomainError(_))
+        ));
+    }
+
+    #[test]
+    fn test_non_finite_derivatives() {
+        let at = |x: f64| HashMap::from([("x".to_string(), x)]);
+        for input in ["sqrt(x)", "0*sqrt(x)", "x^0.5"] {
+            assert_eq!(
+                gradient(&parsed(input), &at(0.0)),
+                Err(EvalError::DomainError("not differentiable at this point")),
+                "{}",
+                input
+            );
+        }
+        assert!(matches!(
+            gradient(&parsed("x^0.5"), &at(-1.0)),
+            Err(EvalError::DomainError(_))
+        ));
+        assert_eq!(
+            gradient(&parsed("e^(1000*x)"), &at(1.0)),
+            Err(EvalError::Overflow)
+        );
+        // Constants don't need a derivative
+        let result = gradient(&parsed("x + sqrt(0)"), &at(0.0)).unwrap();
+        assert_eq!(result.partial("x"), 1.0);
+    }
+}
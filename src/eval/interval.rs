@@ -0,0 +1,575 @@
+use std::collections::HashMap;
+use std::f64::consts::{E, FRAC_PI_2, PI};
+use std::fmt;
+
+use super::EvalError;
+use crate::integer;
+use crate::parser::{Function, Node};
+
+/// A closed interval `[lo, hi]` of real numbers, the bounds may be infinite
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    lo: f64,
+    hi: f64,
+}
+
+/// Widens the bounds by one ulp in each direction.
+///
+/// Rust can't change the rounding mode of the FPU, so rounding outwards after every operation is
+/// what keeps the result a guaranteed enclosure.
+fn outward(lo: f64, hi: f64) -> Interval {
+    Interval {
+        lo: lo.next_down(),
+        hi: hi.next_up(),
+    }
+}
+
+/// Multiplication where 0 * inf is 0, as needed for bounds of intervals
+fn mul_bound(a: f64, b: f64) -> f64 {
+    if a == 0.0 || b == 0.0 {
+        0.0
+    } else {
+        a * b
+    }
+}
+
+/// Encloses `x^n` by squaring and multiplying with outward rounding, `f64::powi` accumulates
+/// rounding errors over its multiplications that one ulp doesn't cover
+fn powi_bounds(x: f64, mut n: u32) -> Interval {
+    let mut base = Interval::point(x);
+    let mut result = Interval::point(1.0);
+    while n > 0 {
+        if n & 1 == 1 {
+            result = result.mul(&base);
+        }
+        n >>= 1;
+        if n > 0 {
+            base = base.mul(&base);
+        }
+    }
+    result
+}
+
+fn min(values: [f64; 4]) -> f64 {
+    values.into_iter().fold(f64::INFINITY, f64::min)
+}
+
+fn max(values: [f64; 4]) -> f64 {
+    values.into_iter().fold(f64::NEG_INFINITY, f64::max)
+}
+
+impl Interval {
+    /// Creates the interval `[lo, hi]`, which fails if `lo > hi` or a bound is NaN
+    pub fn new(lo: f64, hi: f64) -> Result<Interval, EvalError> {
+        if lo.is_nan() || hi.is_nan() {
+            return Err(EvalError::NaN);
+        }
+        if lo > hi {
+            return Err(EvalError::DomainError("lower bound above the upper bound"));
+        }
+        Ok(Interval { lo, hi })
+    }
+
+    /// The interval that only contains `value`
+    ///
+    /// # Panics
+    ///
+    /// If `value` is NaN, use [`Interval::new`] for values that may be NaN.
+    pub fn point(value: f64) -> Interval {
+        assert!(!value.is_nan(), "interval of NaN");
+        Interval {
+            lo: value,
+            hi: value,
+        }
+    }
+
+    pub fn entire() -> Interval {
+        Interval {
+            lo: f64::NEG_INFINITY,
+            hi: f64::INFINITY,
+        }
+    }
+
+    pub fn lo(&self) -> f64 {
+        self.lo
+    }
+
+    pub fn hi(&self) -> f64 {
+        self.hi
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn midpoint(&self) -> f64 {
+        self.lo + (self.hi - self.lo) / 2.0
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+
+    pub fn add(&self, other: &Interval) -> Interval {
+        outward(self.lo + other.lo, self.hi + other.hi)
+    }
+
+    pub fn sub(&self, other: &Interval) -> Interval {
+        outward(self.lo - other.hi, self.hi - other.lo)
+    }
+
+    pub fn mul(&self, other: &Interval) -> Interval {
+        let products = [
+            mul_bound(self.lo, other.lo),
+            mul_bound(self.lo, other.hi),
+            mul_bound(self.hi, other.lo),
+            mul_bound(self.hi, other.hi),
+        ];
+        outward(min(products), max(products))
+    }
+
+    /// Divides by an interval, if the divisor contains zero the result is the hull of every
+    /// possible quotient which is usually unbounded
+    pub fn div(&self, other: &Interval) -> Result<Interval, EvalError> {
+        if !other.contains(0.0) {
+            let quotients = [
+                self.lo / other.lo,
+                self.lo / other.hi,
+                self.hi / other.lo,
+                self.hi / other.hi,
+            ];
+            return Ok(outward(min(quotients), max(quotients)));
+        }
+
+        if other.lo == 0.0 && other.hi == 0.0 {
+            return Err(EvalError::DivisionByZero);
+        }
+        if self.contains(0.0) || (other.lo < 0.0 && other.hi > 0.0) {
+            return Ok(Interval::entire());
+        }
+
+        // The divisor touches zero at one end, so the quotient is unbounded in one direction only
+        let positive = self.lo > 0.0;
+        Ok(match (other.lo == 0.0, positive) {
+            (true, true) => outward(self.lo / other.hi, f64::INFINITY),
+            (true, false) => outward(f64::NEG_INFINITY, self.hi / other.hi),
+            (false, true) => outward(f64::NEG_INFINITY, self.lo / other.lo),
+            (false, false) => outward(self.hi / other.lo, f64::INFINITY),
+        })
+    }
+
+    pub fn neg(&self) -> Interval {
+        Interval {
+            lo: -self.hi,
+            hi: -self.lo,
+        }
+    }
+
+    pub fn pow(&self, exponent: &Interval) -> Result<Interval, EvalError> {
+        if exponent.lo == exponent.hi
+            && exponent.lo.fract() == 0.0
+            && exponent.lo.abs() <= i32::MAX as f64
+        {
+            return self.powi(exponent.lo as i32);
+        }
+        if self.hi < 0.0 {
+            return Err(EvalError::DomainError(
+                "negative base with a non-integer exponent",
+            ));
+        }
+        if self.hi == 0.0 {
+            if exponent.lo > 0.0 {
+                return Ok(Interval::point(0.0));
+            }
+            return Err(EvalError::DomainError("zero to a non-positive power"));
+        }
+
+        // Only the non-negative part of the base is in the domain
+        let base = Interval {
+            lo: self.lo.max(0.0),
+            hi: self.hi,
+        };
+        let power = exponent.mul(&base.ln()?).exp();
+        Ok(Interval {
+            lo: power.lo.max(0.0),
+            hi: power.hi,
+        })
+    }
+
+    fn powi(&self, n: i32) -> Result<Interval, EvalError> {
+        if n < 0 {
+            return Interval::point(1.0).div(&self.powi(-n)?);
+        }
+        if n == 0 {
+            return Ok(Interval::point(1.0));
+        }
+
+        let n = n as u32;
+        if n % 2 == 1 {
+            // Odd powers are monotone, so the bounds map to the bounds
+            return Ok(Interval {
+                lo: powi_bounds(self.lo, n).lo,
+                hi: powi_bounds(self.hi, n).hi,
+            });
+        }
+        let (small, large) = if self.contains(0.0) {
+            (0.0, self.lo.abs().max(self.hi.abs()))
+        } else {
+            (
+                self.lo.abs().min(self.hi.abs()),
+                self.lo.abs().max(self.hi.abs()),
+            )
+        };
+        Ok(Interval {
+            lo: powi_bounds(small, n).lo.max(0.0),
+            hi: powi_bounds(large, n).hi,
+        })
+    }
+
+    pub fn exp(&self) -> Interval {
+        let bounds = outward(self.lo.exp(), self.hi.exp());
+        Interval {
+            lo: bounds.lo.max(0.0),
+            hi: bounds.hi,
+        }
+    }
+
+    /// Natural logarithm of the positive part of the interval
+    pub fn ln(&self) -> Result<Interval, EvalError> {
+        if self.hi <= 0.0 {
+            return Err(EvalError::DomainError("ln of a non-positive number"));
+        }
+        Ok(outward(self.lo.max(0.0).ln(), self.hi.ln()))
+    }
+
+    /// Square root of the non-negative part of the interval
+    pub fn sqrt(&self) -> Result<Interval, EvalError> {
+        if self.hi < 0.0 {
+            return Err(EvalError::DomainError("sqrt of a negative number"));
+        }
+        let bounds = outward(self.lo.max(0.0).sqrt(), self.hi.sqrt());
+        Ok(Interval {
+            lo: bounds.lo.max(0.0),
+            hi: bounds.hi,
+        })
+    }
+
+    pub fn cos(&self) -> Interval {
+        if !self.lo.is_finite() || !self.hi.is_finite() || self.width() >= 2.0 * PI {
+            return Interval { lo: -1.0, hi: 1.0 };
+        }
+
+        // cos has its maxima at even multiples of pi and its minima at odd ones. The multiples are
+        // searched for with a little slack since pi itself isn't exact.
+        let first = (self.lo / PI - 1e-9).ceil() as i64;
+        let last = (self.hi / PI + 1e-9).floor() as i64;
+        let contains_max = (first..=last).any(|k| k % 2 == 0);
+        let contains_min = (first..=last).any(|k| k % 2 != 0);
+
+        let a = self.lo.cos();
+        let b = self.hi.cos();
+        let bounds = outward(a.min(b), a.max(b));
+        Interval {
+            lo: if contains_min {
+                -1.0
+            } else {
+                bounds.lo.max(-1.0)
+            },
+            hi: if contains_max {
+                1.0
+            } else {
+                bounds.hi.min(1.0)
+            },
+        }
+    }
+
+    pub fn sin(&self) -> Interval {
+        self.sub(&Interval::point(FRAC_PI_2)).cos()
+    }
+
+    pub fn tan(&self) -> Interval {
+        if !self.lo.is_finite() || !self.hi.is_finite() || self.width() >= PI {
+            return Interval::entire();
+        }
+
+        // tan is increasing between its poles at odd multiples of pi/2
+        let first = (self.lo / FRAC_PI_2 - 1e-9).ceil() as i64;
+        let last = (self.hi / FRAC_PI_2 + 1e-9).floor() as i64;
+        if (first..=last).any(|k| k % 2 != 0) {
+            return Interval::entire();
+        }
+        outward(self.lo.tan(), self.hi.tan())
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+/// Evaluates an expression with every variable bound to an interval.
+///
+/// The result is guaranteed to contain the value of the expression for every choice of the
+/// variables within their intervals, though it can be wider than the true range.
+pub fn evaluate(node: &Node, variables: &HashMap<String, Interval>) -> Result<Interval, EvalError> {
+    let eval = |node: &Node| evaluate(node, variables);
+
+    Ok(match node {
+        Node::Number(number) => {
+            if !number.is_real() {
+                return Err(EvalError::DomainError("complex number"));
+            }
+            // Literals like 0.1 or 1e23 aren't exactly representable, so they're widened unless
+            // the digits are an integer that the f64 holds exactly
+            let value = number.value();
+            let exact = match number.literal() {
+                Some(literal) => literal
+                    .parse::<i128>()
+                    .is_ok_and(|n| n as f64 == value && value as i128 == n),
+                None => value.fract() == 0.0,
+            };
+            if exact {
+                Interval::point(value)
+            } else {
+                outward(value, value)
+            }
+        }
+        Node::Variable(name) => *variables
+            .get(name)
+            .ok_or_else(|| EvalError::UnboundVariable(name.clone()))?,
+        Node::PiConstant => outward(PI, PI),
+        Node::EConstant => outward(E, E),
+        Node::IConstant => return Err(EvalError::DomainError("complex number")),
+        Node::Add(l, r) => eval(l)?.add(&eval(r)?),
+        Node::Sub(l, r) => eval(l)?.sub(&eval(r)?),
+        Node::Mul(l, r) => {
+            // x*x is a square and can't be negative, which the plain product doesn't know
+            if l == r {
+                eval(l)?.powi(2)?
+            } else {
+                eval(l)?.mul(&eval(r)?)
+            }
+        }
+        Node::Div(l, r) => eval(l)?.div(&eval(r)?)?,
+        Node::Pow(l, r) => eval(l)?.pow(&eval(r)?)?,
+        Node::Exp(n) => eval(n)?.exp(),
+        Node::Log(n) => eval(n)?.ln()?,
+        Node::Sin(n) => eval(n)?.sin(),
+        Node::Cos(n) => eval(n)?.cos(),
+        Node::Tan(n) => eval(n)?.tan(),
+        Node::Sqrt(n) => eval(n)?.sqrt()?,
+        Node::Neg(n) => eval(n)?.neg(),
+        Node::Function(function, arguments) if function.binds_index() => {
+            let bound = |n: &Node| match eval(n)? {
+                bound if bound.lo == bound.hi => Ok(bound.lo),
+                _ => Err(EvalError::DomainError(
+                    "integer function of a non-degenerate interval",
+                )),
+            };
+            let (a, b) = (bound(&arguments[2])?, bound(&arguments[3])?);
+            let identity = Interval::point(f64::from(*function == Function::Product));
+            let (mut terms, reversed) = integer::terms(arguments, a, b)?;
+            let result = terms.try_fold(identity, |result, term| {
+                let term = eval(&term)?;
+                Ok::<_, EvalError>(match function {
+                    Function::Sum => result.add(&term),
+                    _ => result.mul(&term),
+                })
+            })?;
+            match function {
+                _ if !reversed => result,
+                Function::Sum => result.neg(),
+                _ => identity.div(&result)?,
+            }
+        }
+        Node::Function(function, arguments) => {
+            let arguments = arguments.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            match function {
+                // Both are monotone, so the bounds map to the bounds
+                Function::Floor => Interval {
+                    lo: arguments[0].lo.floor(),
+                    hi: arguments[0].hi.floor(),
+                },
+                Function::Ceil => Interval {
+                    lo: arguments[0].lo.ceil(),
+                    hi: arguments[0].hi.ceil(),
+                },
+                _ if arguments.iter().all(|a| a.lo == a.hi) => {
+                    let values: Vec<f64> = arguments.iter().map(|a| a.lo).collect();
+                    Interval::point(integer::evaluate_real(*function, &values)?)
+                }
+                _ => {
+                    return Err(EvalError::DomainError(
+                        "integer function of a non-degenerate interval",
+                    ))
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn eval(input: &str, x: Interval) -> Result<Interval, EvalError> {
+        let node = parse(&tokenize(input).unwrap()).unwrap();
+        evaluate(&node, &HashMap::from([("x".to_string(), x)]))
+    }
+
+    fn assert_encloses(interval: Interval, lo: f64, hi: f64) {
+        assert!(
+            interval.lo() <= lo && interval.hi() >= hi,
+            "{} doesn't enclose [{}, {}]",
+            interval,
+            lo,
+            hi
+        );
+        assert!(
+            interval.lo() >= lo - 1e-9 && interval.hi() <= hi + 1e-9,
+            "{} is wider than [{}, {}]",
+            interval,
+            lo,
+            hi
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_is_rounded_outwards() {
+        let result = eval("x + 0.2", Interval::point(0.1)).unwrap();
+
+        assert!(result.contains(0.1 + 0.2));
+        assert!(result.lo() < 0.30000000000000004 && result.hi() > 0.3);
+        assert_encloses(
+            eval("x * x - x", Interval::new(-1.0, 2.0).unwrap()).unwrap(),
+            -2.0,
+            5.0,
+        );
+        assert_encloses(
+            eval("x^2", Interval::new(-1.0, 2.0).unwrap()).unwrap(),
+            0.0,
+            4.0,
+        );
+
+        // powi would lose more than an ulp over the repeated multiplications
+        let result = eval("x^1000", Interval::point(1.0 + 2f64.powi(-20))).unwrap();
+        assert!(result.contains(1.000954128753171));
+        assert_encloses(
+            eval("x^3", Interval::new(-2.0, 1.0).unwrap()).unwrap(),
+            -8.0,
+            1.0,
+        );
+    }
+
+    #[test]
+    fn test_literals_that_are_not_exact() {
+        assert_eq!(eval("3", Interval::point(0.0)), Ok(Interval::point(3.0)));
+        // 10^23 lies between two f64s, so the literal can't be a point
+        let result = eval("1e23", Interval::point(0.0)).unwrap();
+        assert!(result.lo() < 1e23 && result.hi() > 1e23);
+        let result = eval("100000000000000000000001", Interval::point(0.0)).unwrap();
+        assert!(result.width() > 0.0);
+    }
+
+    #[test]
+    fn test_division_by_intervals_containing_zero() {
+        let result = eval("1 / x", Interval::new(0.0, 2.0).unwrap()).unwrap();
+        assert_encloses(result, 0.5, f64::INFINITY);
+
+        let result = eval("1 / x", Interval::new(-2.0, 0.0).unwrap()).unwrap();
+        assert_encloses(result, f64::NEG_INFINITY, -0.5);
+
+        assert_eq!(
+            eval("1 / x", Interval::new(-1.0, 1.0).unwrap()).unwrap(),
+            Interval::entire()
+        );
+        assert_eq!(
+            eval("1 / x", Interval::point(0.0)),
+            Err(EvalError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn test_invalid_intervals() {
+        assert_eq!(Interval::new(f64::NAN, 1.0), Err(EvalError::NaN));
+        assert_eq!(Interval::new(0.0, f64::NAN), Err(EvalError::NaN));
+        assert!(matches!(
+            Interval::new(2.0, 1.0),
+            Err(EvalError::DomainError(_))
+        ));
+        assert_eq!(Interval::new(1.0, 1.0), Ok(Interval::point(1.0)));
+    }
+
+    #[test]
+    fn test_domain_edges() {
+        assert_encloses(
+            eval("sqrt(x)", Interval::new(-1.0, 4.0).unwrap()).unwrap(),
+            0.0,
+            2.0,
+        );
+        assert_eq!(
+            eval("sqrt(x)", Interval::new(-1.0, 4.0).unwrap())
+                .unwrap()
+                .lo(),
+            0.0
+        );
+        assert!(eval("sqrt(x)", Interval::new(-2.0, -1.0).unwrap()).is_err());
+
+        let result = eval("ln(x)", Interval::new(0.0, E).unwrap()).unwrap();
+        assert_encloses(result, f64::NEG_INFINITY, 1.0);
+        assert!(eval("ln(x)", Interval::new(-1.0, 0.0).unwrap()).is_err());
+        assert!(eval("0^x", Interval::new(-1.0, 0.0).unwrap()).is_err());
+        assert!(eval("0^x", Interval::new(-1.0, 1.0).unwrap()).is_err());
+        assert_eq!(
+            eval("0^x", Interval::new(0.5, 2.0).unwrap()),
+            Ok(Interval::point(0.0))
+        );
+    }
+
+    #[test]
+    fn test_sums_and_products() {
+        assert_encloses(
+            eval("sum(k^2, k, 1, 3)", Interval::point(0.0)).unwrap(),
+            14.0,
+            14.0,
+        );
+        let x = Interval::new(1.0, 2.0).unwrap();
+        assert_encloses(eval("sum(x^k, k, 1, 2)", x).unwrap(), 2.0, 6.0);
+        assert_encloses(eval("product(x + k, k, 0, 1)", x).unwrap(), 2.0, 6.0);
+        assert!(matches!(
+            eval("sum(k, k, 1, x)", x),
+            Err(EvalError::DomainError(_))
+        ));
+    }
+
+    #[test]
+    fn test_trigonometry() {
+        assert_encloses(
+            eval("sin(x)", Interval::new(0.0, PI).unwrap()).unwrap(),
+            0.0,
+            1.0,
+        );
+        assert_encloses(
+            eval("cos(x)", Interval::new(0.0, 1.0).unwrap()).unwrap(),
+            1f64.cos(),
+            1.0,
+        );
+        assert_encloses(
+            eval("cos(x)", Interval::new(3.0, 3.5).unwrap()).unwrap(),
+            -1.0,
+            3.5f64.cos(),
+        );
+        assert_eq!(
+            eval("tan(x)", Interval::new(1.0, 2.0).unwrap()).unwrap(),
+            Interval::entire()
+        );
+        assert_encloses(
+            eval("tan(x)", Interval::new(-1.0, 1.0).unwrap()).unwrap(),
+            -(1f64.tan()),
+            1f64.tan(),
+        );
+    }
+}
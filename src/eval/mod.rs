@@ -0,0 +1,42 @@
+use thiserror::Error;
+
+use crate::NumberError;
+
+pub mod decimal;
+pub mod dual;
+pub mod gradient;
+pub mod interval;
+pub mod modular;
+mod number;
+pub mod precise;
+pub mod uncertain;
+
+pub use number::evaluate;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EvalError {
+    #[error("Unbound variable: {0}")]
+    UnboundVariable(String),
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Domain error: {0}")]
+    DomainError(&'static str),
+    #[error("Overflow")]
+    Overflow,
+    #[error("Result is not a number")]
+    NaN,
+    #[error("{0} has no inverse modulo {1}")]
+    NotInvertible(u64, u64),
+}
+
+impl From<NumberError> for EvalError {
+    fn from(error: NumberError) -> EvalError {
+        match error {
+            NumberError::ParsingError => EvalError::DomainError("invalid number"),
+            NumberError::DivisionByZero => EvalError::DivisionByZero,
+            NumberError::Overflow => EvalError::Overflow,
+            NumberError::Domain(message) => EvalError::DomainError(message),
+            NumberError::NaN => EvalError::NaN,
+        }
+    }
+}
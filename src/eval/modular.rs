@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+
+use super::EvalError;
+use crate::integer;
+use crate::parser::{Function, Node};
+use crate::simplify::simplify;
+use crate::Number;
+
+/// Evaluates an integer expression modulo `modulus`, the result is in `0..modulus`
+///
+/// Division multiplies with the modular inverse, which fails with [`EvalError::NotInvertible`]
+/// when the divisor shares a factor with the modulus. Exponents are evaluated as plain integers,
+/// so `3^100 / 7` modulo 13 never computes `3^100`.
+pub fn evaluate(
+    node: &Node,
+    variables: &HashMap<String, i64>,
+    modulus: u64,
+) -> Result<u64, EvalError> {
+    if modulus == 0 {
+        return Err(EvalError::DomainError("modulus must be positive"));
+    }
+    Modular { variables, modulus }.eval(node)
+}
+
+/// Simplifies an expression and reduces every part without variables modulo `modulus`, e.g.
+/// `x * 15 + 3^100` modulo 13 becomes `2 * x + 3`
+///
+/// The parts without variables fail the same way as in [`evaluate`], so `x + 1/4` modulo 12 is
+/// [`EvalError::NotInvertible`].
+pub fn reduce(node: &Node, modulus: u64) -> Result<Node, EvalError> {
+    if modulus == 0 {
+        return Err(EvalError::DomainError("modulus must be positive"));
+    }
+    let variables = HashMap::new();
+    let modular = Modular {
+        variables: &variables,
+        modulus,
+    };
+    // Constants are reduced before simplifying so huge powers never turn into floats, and again
+    // afterwards for the coefficients that collecting like terms creates
+    modular.reduce(&simplify(&modular.reduce(node)?))
+}
+
+struct Modular<'a> {
+    variables: &'a HashMap<String, i64>,
+    modulus: u64,
+}
+
+/// Integers up to this size are exact in an f64
+const MAX_EXACT: f64 = 9007199254740992.0;
+
+/// Factorials below the modulus are multiplied out term by term, up to this many terms unless
+/// the product reaches 0 first
+const MAX_TERMS: i128 = 10_000_000;
+
+/// Reads an integer, literals from their digits so they aren't rounded to an f64 first
+fn integer(number: &Number) -> Result<i128, EvalError> {
+    if let Some(value) = number.literal().and_then(|literal| literal.parse().ok()) {
+        return Ok(value);
+    }
+    let value = number.value();
+    if !number.is_real() || value.fract() != 0.0 {
+        return Err(EvalError::DomainError("modular arithmetic needs integers"));
+    }
+    if value.abs() > MAX_EXACT {
+        return Err(EvalError::Overflow);
+    }
+    Ok(value as i128)
+}
+
+fn has_variables(node: &Node) -> bool {
+    match node {
+        Node::Variable(_) => true,
+        Node::Number(_) | Node::PiConstant | Node::EConstant | Node::IConstant => false,
+        Node::Add(l, r) | Node::Sub(l, r) | Node::Mul(l, r) | Node::Div(l, r) | Node::Pow(l, r) => {
+            has_variables(l) || has_variables(r)
+        }
+        Node::Exp(n)
+        | Node::Log(n)
+        | Node::Sin(n)
+        | Node::Cos(n)
+        | Node::Tan(n)
+        | Node::Sqrt(n)
+        | Node::Neg(n) => has_variables(n),
+        Node::Function(_, arguments) => arguments.iter().any(has_variables),
+    }
+}
+
+impl Modular<'_> {
+    fn residue(&self, value: i128) -> u64 {
+        value.rem_euclid(self.modulus as i128) as u64
+    }
+
+    fn mul(&self, a: u64, b: u64) -> u64 {
+        (a as u128 * b as u128 % self.modulus as u128) as u64
+    }
+
+    /// Square and multiply
+    fn pow(&self, mut base: u64, mut exponent: u128) -> u64 {
+        let mut result = 1 % self.modulus;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = self.mul(result, base);
+            }
+            base = self.mul(base, base);
+            exponent >>= 1;
+        }
+        result
+    }
+
+    /// The inverse from the extended Euclidean algorithm, if `gcd(value, modulus) == 1`
+    fn inverse(&self, value: u64) -> Result<u64, EvalError> {
+        let (mut r, mut next_r) = (self.modulus as i128, value as i128);
+        let (mut t, mut next_t) = (0i128, 1i128);
+        while next_r != 0 {
+            let quotient = r / next_r;
+            (r, next_r) = (next_r, r - quotient * next_r);
+            (t, next_t) = (next_t, t - quotient * next_t);
+        }
+        if r != 1 {
+            return Err(EvalError::NotInvertible(value, self.modulus));
+        }
+        Ok(self.residue(t))
+    }
+
+    fn eval(&self, node: &Node) -> Result<u64, EvalError> {
+        Ok(match node {
+            Node::Number(number) => self.residue(integer(number)?),
+            Node::Variable(name) => self.residue(
+                *self
+                    .variables
+                    .get(name)
+                    .ok_or_else(|| EvalError::UnboundVariable(name.clone()))?
+                    as i128,
+            ),
+            Node::Add(l, r) => self.residue(self.eval(l)? as i128 + self.eval(r)? as i128),
+            Node::Sub(l, r) => self.residue(self.eval(l)? as i128 - self.eval(r)? as i128),
+            Node::Mul(l, r) => self.mul(self.eval(l)?, self.eval(r)?),
+            Node::Div(l, r) => {
+                let (l, r) = (self.eval(l)?, self.eval(r)?);
+                self.mul(l, self.inverse(r)?)
+            }
+            Node::Pow(l, r) => {
+                let base = self.eval(l)?;
+                let exponent = self.exponent(r)?;
+                let power = self.pow(base, exponent.unsigned_abs());
+                if exponent < 0 {
+                    self.inverse(power)?
+                } else {
+                    power
+                }
+            }
+            Node::Neg(n) => self.residue(-(self.eval(n)? as i128)),
+            // n! contains the factor n once n reaches the modulus
+            Node::Function(Function::Factorial, arguments) => {
+                let n = self.exponent(&arguments[0])?;
+                if n < 0 {
+                    return Err(EvalError::DomainError("factorial of a negative number"));
+                }
+                if n >= self.modulus as i128 {
+                    return Ok(0);
+                }
+                // The product stays 0 once it has every prime factor of the modulus
+                let mut factorial = 1 % self.modulus;
+                for k in 1..=n {
+                    if factorial == 0 {
+                        break;
+                    }
+                    if k > MAX_TERMS {
+                        return Err(EvalError::DomainError("too many terms"));
+                    }
+                    factorial = self.mul(factorial, k as u64);
+                }
+                factorial
+            }
+            Node::Function(function, arguments) if function.binds_index() => {
+                let identity = u64::from(*function == Function::Product) % self.modulus;
+                let (mut terms, reversed) = self.terms(arguments)?;
+                let result = terms.try_fold(identity, |result, term| {
+                    let term = self.eval(&term)?;
+                    Ok::<_, EvalError>(match function {
+                        Function::Sum => self.residue(result as i128 + term as i128),
+                        _ => self.mul(result, term),
+                    })
+                })?;
+                match function {
+                    _ if !reversed => result,
+                    Function::Sum => self.residue(-(result as i128)),
+                    _ => self.inverse(result)?,
+                }
+            }
+            Node::Function(function, arguments) => {
+                self.residue(self.function(*function, arguments)?)
+            }
+            Node::PiConstant | Node::EConstant | Node::IConstant => {
+                return Err(EvalError::DomainError("modular arithmetic needs integers"))
+            }
+            Node::Exp(_)
+            | Node::Log(_)
+            | Node::Sin(_)
+            | Node::Cos(_)
+            | Node::Tan(_)
+            | Node::Sqrt(_) => {
+                return Err(EvalError::DomainError("function in modular arithmetic"))
+            }
+        })
+    }
+
+    /// Exponents aren't reduced modulo n, so they are evaluated as exact integers
+    fn exponent(&self, node: &Node) -> Result<i128, EvalError> {
+        let overflow = |value: Option<i128>| value.ok_or(EvalError::Overflow);
+        match node {
+            Node::Number(number) => integer(number),
+            Node::Variable(name) => self
+                .variables
+                .get(name)
+                .map(|value| *value as i128)
+                .ok_or_else(|| EvalError::UnboundVariable(name.clone())),
+            Node::Add(l, r) => overflow(self.exponent(l)?.checked_add(self.exponent(r)?)),
+            Node::Sub(l, r) => overflow(self.exponent(l)?.checked_sub(self.exponent(r)?)),
+            Node::Mul(l, r) => overflow(self.exponent(l)?.checked_mul(self.exponent(r)?)),
+            Node::Pow(l, r) => {
+                let exponent = u32::try_from(self.exponent(r)?)
+                    .map_err(|_| EvalError::DomainError("exponent must be a natural number"))?;
+                overflow(self.exponent(l)?.checked_pow(exponent))
+            }
+            Node::Neg(n) => overflow(self.exponent(n)?.checked_neg()),
+            Node::Function(Function::Factorial, arguments) => {
+                let n = self.exponent(&arguments[0])?;
+                if n < 0 {
+                    return Err(EvalError::DomainError("factorial of a negative number"));
+                }
+                (1..=n).try_fold(1i128, |acc, k| overflow(acc.checked_mul(k)))
+            }
+            Node::Function(function, arguments) if function.binds_index() => {
+                let identity = i128::from(*function == Function::Product);
+                let (mut terms, reversed) = self.terms(arguments)?;
+                let result = terms.try_fold(identity, |result, term| {
+                    let term = self.exponent(&term)?;
+                    overflow(match function {
+                        Function::Sum => result.checked_add(term),
+                        _ => result.checked_mul(term),
+                    })
+                })?;
+                match function {
+                    _ if !reversed => Ok(result),
+                    Function::Sum => overflow(result.checked_neg()),
+                    // Only 1 and -1 have an integer inverse
+                    _ if result.abs() == 1 => Ok(result),
+                    _ => Err(EvalError::DomainError("exponent must be an integer")),
+                }
+            }
+            Node::Function(function, arguments) => self.function(*function, arguments),
+            _ => Err(EvalError::DomainError("exponent must be an integer")),
+        }
+    }
+
+    /// The terms of a sum or product, the bounds are exact integers like the exponents
+    fn terms<'n>(
+        &self,
+        arguments: &'n [Node],
+    ) -> Result<(impl Iterator<Item = Node> + 'n, bool), EvalError> {
+        let (a, b) = (self.exponent(&arguments[2])?, self.exponent(&arguments[3])?);
+        Ok(integer::terms(arguments, a as f64, b as f64)?)
+    }
+
+    /// The other integer functions aren't compatible with residues, `gcd(15, 5)` isn't `gcd(2, 5)`
+    /// modulo 13, so they are evaluated on the exact arguments
+    fn function(&self, function: Function, arguments: &[Node]) -> Result<i128, EvalError> {
+        let arguments = arguments
+            .iter()
+            .map(|n| Ok(Number::real(self.exponent(n)? as f64)))
+            .collect::<Result<Vec<_>, EvalError>>()?;
+        let value = integer::evaluate(function, &arguments)?.value();
+        // Larger results are rounded
+        if value.abs() > 9007199254740992.0 {
+            return Err(EvalError::Overflow);
+        }
+        Ok(value as i128)
+    }
+
+    fn reduce(&self, node: &Node) -> Result<Node, EvalError> {
+        if !has_variables(node) {
+            return Ok(Node::Number(Number::real(self.eval(node)? as f64)));
+        }
+
+        let reduce = |n: &Node| self.reduce(n).map(Box::new);
+        Ok(match node {
+            Node::Add(l, r) => Node::Add(reduce(l)?, reduce(r)?),
+            // Subtracting a constant is adding its residue, `x - 1` is `x + 12` modulo 13
+            Node::Sub(l, r) if !has_variables(r) => match self.eval(&Node::Neg(r.clone()))? {
+                0 => self.reduce(l)?,
+                value => Node::Add(
+                    reduce(l)?,
+                    Box::new(Node::Number(Number::real(value as f64))),
+                ),
+            },
+            Node::Sub(l, r) => Node::Sub(reduce(l)?, reduce(r)?),
+            Node::Mul(l, r) => Node::Mul(reduce(l)?, reduce(r)?),
+            Node::Div(l, r) => Node::Div(reduce(l)?, reduce(r)?),
+            // The exponent is an integer, not a residue
+            Node::Pow(l, r) => Node::Pow(reduce(l)?, r.clone()),
+            Node::Neg(n) => Node::Neg(reduce(n)?),
+            _ => node.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    fn eval(input: &str, modulus: u64) -> Result<u64, EvalError> {
+        evaluate(&parsed(input), &HashMap::new(), modulus)
+    }
+
+    #[test]
+    fn test_modular_evaluation() {
+        // 3^100 = 3^(3*33 + 1) = 3 (mod 13) and 7^-1 = 2 (mod 13)
+        assert_eq!(eval("3^100 / 7", 13), Ok(6));
+        assert_eq!(eval("2^(10^18)", 1_000_000_007), Ok(719_476_260));
+        assert_eq!(eval("-5 - 3", 7), Ok(6));
+        assert_eq!(eval("3^-1", 7), Ok(5));
+        assert_eq!(
+            eval("1234567891011 * 1213141516171", 1_000_000_007),
+            Ok((1234567891011u128 * 1213141516171 % 1_000_000_007) as u64)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(eval("1 / 4", 12), Err(EvalError::NotInvertible(4, 12)));
+        assert_eq!(eval("1 / 13", 13), Err(EvalError::NotInvertible(0, 13)));
+        assert!(matches!(eval("1.5 * 2", 7), Err(EvalError::DomainError(_))));
+        assert!(matches!(eval("sin(1)", 7), Err(EvalError::DomainError(_))));
+        assert!(matches!(eval("1", 0), Err(EvalError::DomainError(_))));
+        assert!(matches!(
+            eval("sum(k, k, 1, 10^7)", 7),
+            Err(EvalError::DomainError(_))
+        ));
+    }
+
+    #[test]
+    fn test_sums_and_products() {
+        assert_eq!(eval("sum(k^2, k, 1, 3)", 7), Ok(0));
+        assert_eq!(
+            eval("product(k, k, 1, 20)", 1_000_000_007),
+            eval("20!", 1_000_000_007)
+        );
+        // The exponent is summed exactly, 2^(1 + 2 + ... + 100) = 2^5050
+        assert_eq!(eval("2^sum(k, k, 1, 100)", 1000), eval("2^5050", 1000));
+        assert_eq!(eval("sum(1/k, k, 1, 2)", 7), Ok(5));
+        // -(2 + 3) and 1/(3*4)
+        assert_eq!(eval("sum(k, k, 4, 1)", 7), Ok(2));
+        assert_eq!(eval("product(k, k, 5, 2)", 13), Ok(12));
+    }
+
+    #[test]
+    fn test_integer_functions() {
+        // Wilson's theorem, (p - 1)! = -1 modulo a prime p
+        assert_eq!(eval("1000002!", 1_000_003), Ok(1_000_002));
+        assert_eq!(eval("gcd(15, 5) + binomial(10, 3)", 13), Ok(8));
+        assert_eq!(eval("2^(3!)", 100), Ok(64));
+        // 66! already has 2^63 as a factor, and 10^18 is past the modulus
+        assert_eq!(eval("(10^12)!", 1 << 63), Ok(0));
+        assert_eq!(eval("(10^18)!", 1_000_000_007), Ok(0));
+        assert!(matches!(
+            eval("(10^12)!", 1_000_000_000_039),
+            Err(EvalError::DomainError(_))
+        ));
+    }
+
+    #[test]
+    fn test_large_literals() {
+        assert_eq!(eval("18446744073709551617", 10), Ok(7));
+        assert_eq!(eval("123456789012345678901", 1000), Ok(901));
+    }
+
+    #[test]
+    fn test_variables() {
+        let variables = HashMap::from([("x".to_string(), 10)]);
+        assert_eq!(evaluate(&parsed("x^x + x"), &variables, 7), Ok(0));
+        assert_eq!(
+            reduce(&parsed("x * 15 + 3^100"), 13),
+            Ok(parsed("2 * x + 3"))
+        );
+        assert_eq!(
+            reduce(&parsed("7*y + 8*y - 2^-1"), 13),
+            Ok(parsed("2 * y + 6"))
+        );
+        assert!(matches!(
+            reduce(&parsed("x + 3"), 0),
+            Err(EvalError::DomainError(_))
+        ));
+        assert_eq!(
+            reduce(&parsed("x + 1/4"), 12),
+            Err(EvalError::NotInvertible(4, 12))
+        );
+        assert_eq!(
+            reduce(&parsed("x - 1/4"), 12),
+            Err(EvalError::NotInvertible(4, 12))
+        );
+        assert!(matches!(
+            reduce(&parsed("x * sin(1)"), 7),
+            Err(EvalError::DomainError(_))
+        ));
+    }
+}
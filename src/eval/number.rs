@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use super::EvalError;
+use crate::integer;
+use crate::parser::{Function, Node};
+use crate::Number;
+
+/// Evaluates an expression over the complex numbers, using the principal branch of every function
+///
+/// See [`Number`] for the branches, `sqrt(-1)` evaluates to `i` and `ln(-2)` to `ln(2) + pi*i`.
+pub fn evaluate(node: &Node, variables: &HashMap<String, Number>) -> Result<Number, EvalError> {
+    let eval = |node: &Node| evaluate(node, variables);
+
+    Ok(match node {
+        Node::Number(number) => number.clone(),
+        Node::Variable(name) => variables
+            .get(name)
+            .cloned()
+            .ok_or_else(|| EvalError::UnboundVariable(name.clone()))?,
+        Node::PiConstant => Number::real(std::f64::consts::PI),
+        Node::EConstant => Number::real(std::f64::consts::E),
+        Node::IConstant => Number::imaginary_unit(),
+        Node::Add(l, r) => Number::checked_add(&eval(l)?, &eval(r)?)?,
+        Node::Sub(l, r) => Number::checked_sub(&eval(l)?, &eval(r)?)?,
+        Node::Mul(l, r) => Number::checked_mul(&eval(l)?, &eval(r)?)?,
+        Node::Div(l, r) => Number::checked_div(&eval(l)?, &eval(r)?)?,
+        Node::Pow(l, r) => {
+            let (base, exponent) = (eval(l)?, eval(r)?);
+            // Negative bases have a principal complex value here instead of a domain error
+            if base.is_real() && base.re() < 0.0 {
+                Number::pow(&base, &exponent).checked()?
+            } else {
+                Number::checked_pow(&base, &exponent)?
+            }
+        }
+        Node::Exp(n) => Number::exp(&eval(n)?).checked()?,
+        Node::Log(n) => Number::checked_ln(&eval(n)?)?,
+        Node::Sin(n) => Number::sin(&eval(n)?).checked()?,
+        Node::Cos(n) => Number::cos(&eval(n)?).checked()?,
+        Node::Tan(n) => Number::tan(&eval(n)?).checked()?,
+        Node::Sqrt(n) => Number::sqrt(&eval(n)?),
+        Node::Neg(n) => Number::neg(&eval(n)?),
+        Node::Function(function, arguments) if function.binds_index() => {
+            repeated(*function, arguments, variables)?
+        }
+        Node::Function(function, arguments) => {
+            let arguments = arguments.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            integer::evaluate(*function, &arguments)?
+        }
+    })
+}
+
+/// Evaluates `sum(f, k, a, b)` or `product(f, k, a, b)` by binding `k` to every integer from `a`
+/// to `b`, an empty range gives 0 or 1 and a reversed one is negated or inverted, see
+/// [`integer::range`]
+fn repeated(
+    function: Function,
+    arguments: &[Node],
+    variables: &HashMap<String, Number>,
+) -> Result<Number, EvalError> {
+    let [summand, Node::Variable(index), a, b] = arguments else {
+        return Err(EvalError::DomainError("the index must be a variable"));
+    };
+    let a = integer::integer(&evaluate(a, variables)?)?;
+    let b = integer::integer(&evaluate(b, variables)?)?;
+    let (range, reversed) = integer::range(a, b)?;
+
+    let mut variables = variables.clone();
+    let identity = Number::real(if function == Function::Sum { 0.0 } else { 1.0 });
+    let mut result = identity.clone();
+    for k in range {
+        variables.insert(index.clone(), Number::real(k as f64));
+        let term = evaluate(summand, &variables)?;
+        result = match function {
+            Function::Sum => Number::checked_add(&result, &term)?,
+            _ => Number::checked_mul(&result, &term)?,
+        };
+    }
+    Ok(match function {
+        _ if !reversed => result,
+        Function::Sum => Number::neg(&result),
+        _ => Number::checked_div(&identity, &result)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn eval(input: &str) -> Number {
+        let node = parse(&tokenize(input).unwrap()).unwrap();
+        evaluate(&node, &HashMap::new()).unwrap()
+    }
+
+    fn assert_close(actual: Number, re: f64, im: f64) {
+        assert!(
+            (actual.re() - re).abs() < 1e-12 && (actual.im() - im).abs() < 1e-12,
+            "{:?} != {} + {}i",
+            actual,
+            re,
+            im
+        );
+    }
+
+    #[test]
+    fn test_evaluates_complex_functions() {
+        assert_close(eval("sqrt(-1)"), 0.0, 1.0);
+        assert_close(eval("ln(-2)"), 2f64.ln(), std::f64::consts::PI);
+        assert_close(eval("e^(i*pi) + 1"), 0.0, 0.0);
+        assert_close(eval("(1 + 2*i) * (3 - i)"), 5.0, 5.0);
+        assert_close(eval("i^2"), -1.0, 0.0);
+    }
+
+    #[test]
+    fn test_reports_numeric_errors() {
+        let evaluated = |input: &str| {
+            let node = parse(&tokenize(input).unwrap()).unwrap();
+            evaluate(&node, &HashMap::new())
+        };
+
+        assert_eq!(evaluated("1/0"), Err(EvalError::DivisionByZero));
+        assert_eq!(evaluated("10^400"), Err(EvalError::Overflow));
+        assert!(matches!(evaluated("0^-1"), Err(EvalError::DomainError(_))));
+        assert!(matches!(evaluated("ln(0)"), Err(EvalError::DomainError(_))));
+        assert_close(evaluated("(-8)^(1/3)").unwrap(), 1.0, 3f64.sqrt());
+    }
+
+    #[test]
+    fn test_integer_functions() {
+        assert_close(eval("5! / 3!"), 20.0, 0.0);
+        assert_close(eval("binomial(10, 3) + gcd(12, 18)"), 126.0, 0.0);
+        assert_close(eval("nextprime(floor(pi * 10))"), 37.0, 0.0);
+        assert_close(eval("isprime(2^31 - 1)"), 1.0, 0.0);
+    }
+
+    #[test]
+    fn test_sums_and_products() {
+        assert_close(eval("sum(k^2, k, 1, 10)"), 385.0, 0.0);
+        assert_close(eval("product(k, k, 1, 5)"), 120.0, 0.0);
+        assert_close(eval("sum(i^k, k, 0, 3)"), 0.0, 0.0);
+        assert_close(eval("sum(sum(j*k, j, 1, k), k, 1, 3)"), 25.0, 0.0);
+        assert_close(eval("sum(k, k, 5, 4) + product(k, k, 5, 4)"), 1.0, 0.0);
+        assert_close(eval("sum(k, k, 4, 1)"), -5.0, 0.0);
+        assert_close(eval("product(k, k, 5, 2)"), 1.0 / 12.0, 0.0);
+
+        let node = parse(&tokenize("sum(x^k/k!, k, 0, n)").unwrap()).unwrap();
+        let variables = HashMap::from([
+            ("x".to_string(), Number::real(1.0)),
+            ("n".to_string(), Number::real(20.0)),
+        ]);
+        assert_close(
+            evaluate(&node, &variables).unwrap(),
+            std::f64::consts::E,
+            0.0,
+        );
+        assert!(matches!(
+            evaluate(
+                &node,
+                &HashMap::from([("n".to_string(), Number::real(1.5))])
+            ),
+            Err(EvalError::DomainError(_))
+        ));
+    }
+
+    #[test]
+    fn test_variables() {
+        let node = parse(&tokenize("x * i").unwrap()).unwrap();
+        let variables = HashMap::from([("x".to_string(), Number::real(2.0))]);
+
+        assert_close(evaluate(&node, &variables).unwrap(), 0.0, 2.0);
+        assert_eq!(
+            evaluate(&node, &HashMap::new()),
+            Err(EvalError::UnboundVariable("x".to_string()))
+        );
+    }
+}
@@ -0,0 +1,547 @@
+use std::cmp::Ordering;
+
+use super::int::Int;
+use super::PrecisionError;
+
+/// Binary floating point number with an arbitrary sized mantissa, the value is `mant * 2^exp`.
+///
+/// Every operation takes the precision in bits that the result is truncated to.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Float {
+    mant: Int,
+    exp: i64,
+}
+
+/// Extra bits used internally by the elementary functions
+const GUARD_BITS: u64 = 32;
+
+fn int_to_i64(int: &Int) -> Option<i64> {
+    let magnitude = i64::try_from(int.abs().to_u64()?).ok()?;
+    Some(if int.is_negative() {
+        -magnitude
+    } else {
+        magnitude
+    })
+}
+
+/// Computes `sum (1 / ((2k + 1) * n^(2k + 1)))` scaled by 2^wp, with alternating signs if `alternate`
+fn arctan_series(n: u32, wp: u64, alternate: bool) -> Int {
+    let mut term = (Int::from_u64(1) << wp).div_small(n);
+    let mut sum = Int::zero();
+    let n2 = n * n;
+    let mut k = 0u32;
+    while !term.is_zero() {
+        let part = term.div_small(2 * k + 1);
+        if alternate && k % 2 == 1 {
+            sum = sum - part;
+        } else {
+            sum = sum + part;
+        }
+        term = term.div_small(n2);
+        k += 1;
+    }
+    sum
+}
+
+/// Pi scaled by 2^wp, using Machin's formula
+fn pi_fixed(wp: u64) -> Int {
+    let w = wp + 16;
+    let sum = Int::from_u64(16) * arctan_series(5, w, true)
+        - Int::from_u64(4) * arctan_series(239, w, true);
+    sum >> 16
+}
+
+/// ln(2) scaled by 2^wp, using ln(2) = 2 * atanh(1/3)
+fn ln2_fixed(wp: u64) -> Int {
+    let w = wp + 16;
+    (arctan_series(3, w, false) << 1) >> 16
+}
+
+impl Float {
+    pub fn zero() -> Float {
+        Float {
+            mant: Int::zero(),
+            exp: 0,
+        }
+    }
+
+    pub fn from_int(mant: Int) -> Float {
+        Float { mant, exp: 0 }
+    }
+
+    pub fn pi(prec: u64) -> Float {
+        Float::from_fixed(pi_fixed(prec + GUARD_BITS), prec + GUARD_BITS).round(prec)
+    }
+
+    /// Parses an unsigned decimal such as `12.5` or `1.5e-7`
+    pub fn from_decimal(input: &str, prec: u64) -> Option<Float> {
+        let (mantissa, exponent) = match input.find(['e', 'E']) {
+            Some(i) => (&input[..i], input[i + 1..].parse::<i64>().ok()?),
+            None => (input, 0),
+        };
+        let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = Int::from_digits(&format!("{}{}", int_part, frac_part))?;
+        let scale = exponent - frac_part.len() as i64;
+
+        if scale >= 0 {
+            let scale = u32::try_from(scale).ok()?;
+            return Some(Float::from_int(digits * Int::pow10(scale)).round(prec));
+        }
+
+        let denominator = Int::pow10(u32::try_from(-scale).ok()?);
+        let shift = (prec + denominator.bit_len() + 2).saturating_sub(digits.bit_len());
+        let (quotient, _) = (digits << shift).div_rem(&denominator);
+        Some(
+            Float {
+                mant: quotient,
+                exp: -(shift as i64),
+            }
+            .round(prec),
+        )
+    }
+
+    fn from_fixed(value: Int, wp: u64) -> Float {
+        Float {
+            mant: value,
+            exp: -(wp as i64),
+        }
+    }
+
+    /// The value truncated to a fixed point integer scaled by 2^wp
+    fn to_fixed(&self, wp: u64) -> Int {
+        let shift = self.exp + wp as i64;
+        if shift >= 0 {
+            self.mant.clone() << shift as u64
+        } else {
+            self.mant.clone() >> shift.unsigned_abs()
+        }
+    }
+
+    /// Truncates the mantissa to at most `prec` bits
+    pub fn round(self, prec: u64) -> Float {
+        let len = self.mant.bit_len();
+        if len == 0 {
+            return Float::zero();
+        }
+        if len <= prec {
+            return self;
+        }
+        let shift = len - prec;
+        Float {
+            mant: self.mant >> shift,
+            exp: self.exp + shift as i64,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mant.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.mant.is_negative()
+    }
+
+    /// Returns m such that 2^(m - 1) <= |self| < 2^m, zero gives i64::MIN
+    pub fn magnitude(&self) -> i64 {
+        if self.is_zero() {
+            return i64::MIN;
+        }
+        self.mant.bit_len() as i64 + self.exp
+    }
+
+    pub fn neg(self) -> Float {
+        Float {
+            mant: -self.mant,
+            exp: self.exp,
+        }
+    }
+
+    pub fn add(&self, other: &Float, prec: u64) -> Float {
+        if self.is_zero() {
+            return other.clone().round(prec);
+        }
+        if other.is_zero() {
+            return self.clone().round(prec);
+        }
+
+        // If one of the terms is too small to affect the result there is no need to align them
+        let gap = prec as i64 + 2;
+        if self.magnitude() - other.magnitude() > gap {
+            return self.clone().round(prec);
+        }
+        if other.magnitude() - self.magnitude() > gap {
+            return other.clone().round(prec);
+        }
+
+        let exp = self.exp.min(other.exp);
+        let left = self.mant.clone() << (self.exp - exp) as u64;
+        let right = other.mant.clone() << (other.exp - exp) as u64;
+        Float {
+            mant: left + right,
+            exp,
+        }
+        .round(prec)
+    }
+
+    pub fn sub(&self, other: &Float, prec: u64) -> Float {
+        self.add(&other.clone().neg(), prec)
+    }
+
+    pub fn mul(&self, other: &Float, prec: u64) -> Float {
+        Float {
+            mant: &self.mant * &other.mant,
+            exp: self.exp + other.exp,
+        }
+        .round(prec)
+    }
+
+    pub fn div(&self, other: &Float, prec: u64) -> Result<Float, PrecisionError> {
+        if other.is_zero() {
+            return Err(PrecisionError::DivisionByZero);
+        }
+        let shift = (prec + other.mant.bit_len() + 2).saturating_sub(self.mant.bit_len());
+        let (quotient, _) = (self.mant.clone() << shift).div_rem(&other.mant);
+        Ok(Float {
+            mant: quotient,
+            exp: self.exp - shift as i64 - other.exp,
+        }
+        .round(prec))
+    }
+
+    pub fn sqrt(&self, prec: u64) -> Result<Float, PrecisionError> {
+        if self.is_negative() {
+            return Err(PrecisionError::DomainError("sqrt of a negative number"));
+        }
+        if self.is_zero() {
+            return Ok(Float::zero());
+        }
+
+        let mut shift = (2 * prec + 2).saturating_sub(self.mant.bit_len());
+        if (self.exp - shift as i64) % 2 != 0 {
+            shift += 1;
+        }
+        Ok(Float {
+            mant: (self.mant.clone() << shift).isqrt(),
+            exp: (self.exp - shift as i64) / 2,
+        }
+        .round(prec))
+    }
+
+    /// Returns the value as an integer if it has no fractional part
+    pub fn to_int(&self) -> Option<Int> {
+        if self.exp >= 0 {
+            return Some(self.mant.clone() << self.exp as u64);
+        }
+        let shift = self.exp.unsigned_abs();
+        if self.mant.trailing_bits_zero(shift) {
+            Some(self.mant.clone() >> shift)
+        } else {
+            None
+        }
+    }
+
+    /// Rounds towards negative infinity
+    pub fn floor(&self) -> Float {
+        if self.to_int().is_some() {
+            return self.clone();
+        }
+        let truncated = self.to_fixed(0);
+        if self.is_negative() {
+            Float::from_int(truncated - Int::from_u64(1))
+        } else {
+            Float::from_int(truncated)
+        }
+    }
+
+    /// Rounds towards positive infinity
+    pub fn ceil(&self) -> Float {
+        self.clone().neg().floor().neg()
+    }
+
+    pub fn exp(&self, prec: u64) -> Result<Float, PrecisionError> {
+        if self.is_zero() {
+            return Ok(Float::from_int(Int::from_u64(1)));
+        }
+        if self.magnitude() > 48 {
+            return Err(PrecisionError::Overflow);
+        }
+
+        // Reduce to exp(x) = 2^k * exp(r) with |r| <= ln(2) / 2, then halve r a few more times
+        // and square the result back up
+        let halvings = 16;
+        let w = prec + GUARD_BITS + self.magnitude().max(0) as u64 + halvings;
+        let x = self.to_fixed(w);
+        let ln2 = ln2_fixed(w);
+        let k = x.div_round(&ln2);
+        let r = (x - &k * &ln2) >> halvings;
+
+        let one = Int::from_u64(1) << w;
+        let mut sum = one.clone();
+        let mut term = one;
+        let mut i = 1;
+        loop {
+            term = ((&term * &r) >> w).div_small(i);
+            if term.is_zero() {
+                break;
+            }
+            sum = sum + term.clone();
+            i += 1;
+        }
+        for _ in 0..halvings {
+            sum = (&sum * &sum) >> w;
+        }
+
+        let k = int_to_i64(&k).ok_or(PrecisionError::Overflow)?;
+        Ok(Float {
+            mant: sum,
+            exp: k - w as i64,
+        }
+        .round(prec))
+    }
+
+    pub fn ln(&self, prec: u64) -> Result<Float, PrecisionError> {
+        if self.is_zero() || self.is_negative() {
+            return Err(PrecisionError::DomainError("ln of a non-positive number"));
+        }
+
+        // Write x = m * 2^e with m in [0.75, 1.5), then ln(m) = 2 * atanh((m - 1) / (m + 1))
+        let len = self.mant.bit_len();
+        let mut e = len as i64 - 1 + self.exp;
+        let w = prec + GUARD_BITS + 64;
+        let mut m = if len - 1 <= w {
+            self.mant.clone() << (w - (len - 1))
+        } else {
+            self.mant.clone() >> (len - 1 - w)
+        };
+        let one = Int::from_u64(1) << w;
+        if m.cmp(&(Int::from_u64(3) << (w - 1))) != Ordering::Less {
+            m = m >> 1;
+            e += 1;
+        }
+
+        let (z, _) = ((&m - &one) << w).div_rem(&(&m + &one));
+        let z2 = (&z * &z) >> w;
+        let mut sum = Int::zero();
+        let mut term = z;
+        let mut k = 0;
+        while !term.is_zero() {
+            sum = sum + term.div_small(2 * k + 1);
+            term = (&term * &z2) >> w;
+            k += 1;
+        }
+
+        let result = (sum << 1) + Int::from_i64(e) * ln2_fixed(w);
+        Ok(Float::from_fixed(result, w).round(prec))
+    }
+
+    /// Computes sine and cosine together since they share the argument reduction
+    pub fn sin_cos(&self, prec: u64) -> Result<(Float, Float), PrecisionError> {
+        if self.magnitude() > 4096 {
+            return Err(PrecisionError::Overflow);
+        }
+
+        // Reduce to x = n * pi / 2 + r with |r| <= pi / 4
+        let w = prec + GUARD_BITS + self.magnitude().max(0) as u64;
+        let x = self.to_fixed(w);
+        let half_pi = pi_fixed(w) >> 1;
+        let n = x.div_round(&half_pi);
+        let r = x - &n * &half_pi;
+        let r2 = (&r * &r) >> w;
+
+        let mut sin = Int::zero();
+        let mut term = r;
+        let mut i = 1;
+        while !term.is_zero() {
+            sin = sin + term.clone();
+            term = -((&term * &r2) >> w).div_small(((i + 1) * (i + 2)) as u32);
+            i += 2;
+        }
+
+        let mut cos = Int::zero();
+        let mut term = Int::from_u64(1) << w;
+        let mut i = 0;
+        while !term.is_zero() {
+            cos = cos + term.clone();
+            term = -((&term * &r2) >> w).div_small(((i + 1) * (i + 2)) as u32);
+            i += 2;
+        }
+
+        let quadrant = (&n - &(Int::from_u64(4) * n_div_4(&n)))
+            .to_u64()
+            .unwrap_or(0);
+        let (sin, cos) = match quadrant {
+            0 => (sin, cos),
+            1 => (cos, -sin),
+            2 => (-sin, -cos),
+            _ => (-cos, sin),
+        };
+        Ok((
+            Float::from_fixed(sin, w).round(prec),
+            Float::from_fixed(cos, w).round(prec),
+        ))
+    }
+
+    pub fn pow(&self, exponent: &Float, prec: u64) -> Result<Float, PrecisionError> {
+        if let Some(n) = exponent.to_int() {
+            if n.bit_len() <= 32 {
+                return self.powi(&n, prec);
+            }
+        }
+
+        if self.is_zero() {
+            return if exponent.is_negative() {
+                Err(PrecisionError::DivisionByZero)
+            } else {
+                Ok(Float::zero())
+            };
+        }
+        if self.is_negative() {
+            return Err(PrecisionError::DomainError(
+                "negative base with a non-integer exponent",
+            ));
+        }
+
+        // The absolute error of b * ln(a) becomes the relative error of the result
+        let ln_magnitude = (self.magnitude().unsigned_abs() + 1).ilog2() as i64 + 1;
+        let extra = (exponent.magnitude() + ln_magnitude).max(0) as u64;
+        let wp = prec + GUARD_BITS + extra;
+        exponent.mul(&self.ln(wp)?, wp).exp(prec)
+    }
+
+    /// Raises to an integer power with binary exponentiation
+    fn powi(&self, n: &Int, prec: u64) -> Result<Float, PrecisionError> {
+        let negative = n.is_negative();
+        let mut n = n.abs().to_u64().ok_or(PrecisionError::Overflow)?;
+        let wp = prec + GUARD_BITS + 64;
+
+        let mut base = self.clone();
+        let mut result = Float::from_int(Int::from_u64(1));
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.mul(&base, wp);
+            }
+            n >>= 1;
+            if n > 0 {
+                base = base.mul(&base, wp);
+            }
+        }
+
+        if negative {
+            Float::from_int(Int::from_u64(1)).div(&result, prec)
+        } else {
+            Ok(result.round(prec))
+        }
+    }
+
+    /// Rounds the value to `digits` significant decimal digits.
+    ///
+    /// Returns the digits together with the decimal exponent of the first digit.
+    pub fn to_decimal(&self, digits: usize) -> (String, i64) {
+        if self.is_zero() {
+            return ("0".to_string(), 0);
+        }
+
+        let mut e10 = ((self.magnitude() - 1) as f64 * std::f64::consts::LOG10_2).floor() as i64;
+        loop {
+            let n = self
+                .scaled_by_pow10(digits as i64 - 1 - e10)
+                .abs()
+                .to_digits();
+            match n.len().cmp(&digits) {
+                Ordering::Greater => e10 += 1,
+                Ordering::Less => e10 -= 1,
+                Ordering::Equal => return (n, e10),
+            }
+        }
+    }
+
+    /// Computes round(self * 10^scale) exactly
+    fn scaled_by_pow10(&self, scale: i64) -> Int {
+        let mut numerator = self.mant.clone();
+        let mut denominator = Int::from_u64(1);
+        if scale >= 0 {
+            numerator = numerator * Int::pow10(scale as u32);
+        } else {
+            denominator = Int::pow10(scale.unsigned_abs() as u32);
+        }
+        if self.exp >= 0 {
+            numerator = numerator << self.exp as u64;
+        } else {
+            denominator = denominator << self.exp.unsigned_abs();
+        }
+        numerator.div_round(&denominator)
+    }
+}
+
+/// Floor division of n by 4
+fn n_div_4(n: &Int) -> Int {
+    let (q, r) = n.div_rem(&Int::from_u64(4));
+    if r.is_negative() {
+        q - Int::from_u64(1)
+    } else {
+        q
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREC: u64 = 200;
+
+    fn decimal(input: &str) -> Float {
+        Float::from_decimal(input, PREC).unwrap()
+    }
+
+    fn digits(float: &Float, count: usize) -> String {
+        float.to_decimal(count).0
+    }
+
+    #[test]
+    fn test_constants() {
+        assert_eq!(
+            digits(&Float::pi(PREC), 40),
+            "3141592653589793238462643383279502884197"
+        );
+        assert_eq!(
+            digits(&Float::from_int(Int::from_u64(1)).exp(PREC).unwrap(), 40),
+            "2718281828459045235360287471352662497757"
+        );
+    }
+
+    #[test]
+    fn test_from_decimal() {
+        let float = decimal("0.1");
+        assert_eq!(
+            float.to_decimal(30),
+            ("1".to_string() + &"0".repeat(29), -1)
+        );
+        assert_eq!(decimal("1.5e3").to_int(), Some(Int::from_u64(1500)));
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(
+            digits(&decimal("2").sqrt(PREC).unwrap(), 30),
+            "141421356237309504880168872421"
+        );
+        assert_eq!(
+            digits(&decimal("10").ln(PREC).unwrap(), 30),
+            "230258509299404568401799145468"
+        );
+        let (sin, cos) = decimal("1").sin_cos(PREC).unwrap();
+        assert_eq!(digits(&sin, 30), "841470984807896506652502321630");
+        assert_eq!(digits(&cos, 30), "540302305868139717400936607443");
+        assert_eq!(
+            digits(&decimal("2").pow(&decimal("0.5"), PREC).unwrap(), 30),
+            "141421356237309504880168872421"
+        );
+    }
+
+    #[test]
+    fn test_domain_errors() {
+        assert!(decimal("0").ln(PREC).is_err());
+        assert!(decimal("1").neg().sqrt(PREC).is_err());
+        assert!(decimal("1").div(&Float::zero(), PREC).is_err());
+    }
+}
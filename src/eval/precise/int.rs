@@ -0,0 +1,492 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Neg, Shl, Shr, Sub};
+
+/// Signed arbitrary precision integer.
+///
+/// The magnitude is stored as little endian base 2^32 limbs without trailing zero limbs, so zero is
+/// an empty vector and is never negative.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Int {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+    for i in (0..a.len()).rev() {
+        if a[i] != b[i] {
+            return a[i].cmp(&b[i]);
+        }
+    }
+    Ordering::Equal
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &x) in long.iter().enumerate() {
+        let sum = x as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        out.push(carry as u32);
+    }
+    out
+}
+
+/// Computes a - b, expects |a| >= |b|
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let mut diff = x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        } else {
+            borrow = 0;
+        }
+        out.push(diff as u32);
+    }
+    trim(&mut out);
+    out
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let cur = out[i + j] as u64 + x as u64 * y as u64 + carry;
+            out[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    trim(&mut out);
+    out
+}
+
+/// Divides the magnitude by a single limb, returning quotient and remainder
+fn div_small_mag(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut out = vec![0u32; a.len()];
+    let mut rem = 0u64;
+    for i in (0..a.len()).rev() {
+        let cur = (rem << 32) | a[i] as u64;
+        out[i] = (cur / d as u64) as u32;
+        rem = cur % d as u64;
+    }
+    trim(&mut out);
+    (out, rem as u32)
+}
+
+fn shl_mag(a: &[u32], bits: u64) -> Vec<u32> {
+    if a.is_empty() {
+        return vec![];
+    }
+    let limbs = (bits / 32) as usize;
+    let bits = (bits % 32) as u32;
+    let mut out = vec![0u32; limbs];
+    if bits == 0 {
+        out.extend_from_slice(a);
+    } else {
+        let mut carry = 0u32;
+        for &x in a {
+            out.push((x << bits) | carry);
+            carry = x >> (32 - bits);
+        }
+        if carry > 0 {
+            out.push(carry);
+        }
+    }
+    out
+}
+
+fn shr_mag(a: &[u32], bits: u64) -> Vec<u32> {
+    let limbs = (bits / 32) as usize;
+    if limbs >= a.len() {
+        return vec![];
+    }
+    let bits = (bits % 32) as u32;
+    let mut out: Vec<u32> = a[limbs..].to_vec();
+    if bits > 0 {
+        for i in 0..out.len() {
+            let high = out.get(i + 1).map(|x| x << (32 - bits)).unwrap_or(0);
+            out[i] = (out[i] >> bits) | high;
+        }
+    }
+    trim(&mut out);
+    out
+}
+
+fn bit_len_mag(a: &[u32]) -> u64 {
+    match a.last() {
+        Some(&top) => (a.len() as u64 - 1) * 32 + (32 - top.leading_zeros()) as u64,
+        None => 0,
+    }
+}
+
+/// Long division on magnitudes, shift and subtract one bit at a time
+fn div_rem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if b.len() == 1 {
+        let (q, r) = div_small_mag(a, b[0]);
+        let mut r = vec![r];
+        trim(&mut r);
+        return (q, r);
+    }
+    if cmp_mag(a, b) == Ordering::Less {
+        return (vec![], a.to_vec());
+    }
+
+    let shift = bit_len_mag(a) - bit_len_mag(b);
+    let mut divisor = shl_mag(b, shift);
+    let mut rem = a.to_vec();
+    let mut quotient = vec![0u32; (shift / 32 + 1) as usize];
+    for bit in (0..=shift).rev() {
+        if cmp_mag(&rem, &divisor) != Ordering::Less {
+            rem = sub_mag(&rem, &divisor);
+            quotient[(bit / 32) as usize] |= 1 << (bit % 32);
+        }
+        divisor = shr_mag(&divisor, 1);
+    }
+    trim(&mut quotient);
+    (quotient, rem)
+}
+
+impl Int {
+    pub fn zero() -> Int {
+        Int {
+            negative: false,
+            limbs: vec![],
+        }
+    }
+
+    fn from_parts(negative: bool, mut limbs: Vec<u32>) -> Int {
+        trim(&mut limbs);
+        Int {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    pub fn from_u64(value: u64) -> Int {
+        Int::from_parts(false, vec![value as u32, (value >> 32) as u32])
+    }
+
+    pub fn from_i64(value: i64) -> Int {
+        let int = Int::from_u64(value.unsigned_abs());
+        if value < 0 {
+            -int
+        } else {
+            int
+        }
+    }
+
+    /// Parses a string of ascii digits, returns None on anything else
+    pub fn from_digits(digits: &str) -> Option<Int> {
+        if digits.is_empty() {
+            return None;
+        }
+        let mut limbs: Vec<u32> = vec![];
+        for c in digits.chars() {
+            let digit = c.to_digit(10)?;
+            let mut carry = digit as u64;
+            for limb in limbs.iter_mut() {
+                let cur = *limb as u64 * 10 + carry;
+                *limb = cur as u32;
+                carry = cur >> 32;
+            }
+            if carry > 0 {
+                limbs.push(carry as u32);
+            }
+        }
+        Some(Int::from_parts(false, limbs))
+    }
+
+    pub fn pow10(exponent: u32) -> Int {
+        Int::from_u64(10).pow(exponent)
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> Int {
+        let mut base = self.clone();
+        let mut result = Int::from_u64(1);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> Int {
+        Int::from_parts(false, self.limbs.clone())
+    }
+
+    /// Number of bits in the magnitude, zero has a bit length of 0
+    pub fn bit_len(&self) -> u64 {
+        bit_len_mag(&self.limbs)
+    }
+
+    /// Returns true if the lowest `bits` bits of the magnitude are all zero
+    pub fn trailing_bits_zero(&self, bits: u64) -> bool {
+        let limbs = (bits / 32) as usize;
+        if self.limbs.iter().take(limbs).any(|&x| x != 0) {
+            return false;
+        }
+        let rest = bits % 32;
+        rest == 0
+            || self
+                .limbs
+                .get(limbs)
+                .is_none_or(|&x| x & ((1 << rest) - 1) == 0)
+    }
+
+    /// Truncating division, the remainder has the same sign as self
+    pub fn div_rem(&self, other: &Int) -> (Int, Int) {
+        assert!(!other.is_zero(), "division by zero");
+        let (q, r) = div_rem_mag(&self.limbs, &other.limbs);
+        (
+            Int::from_parts(self.negative != other.negative, q),
+            Int::from_parts(self.negative, r),
+        )
+    }
+
+    /// Division rounded to the nearest integer, ties away from zero
+    pub fn div_round(&self, other: &Int) -> Int {
+        let (q, r) = self.div_rem(other);
+        let twice = r.abs() << 1;
+        if cmp_mag(&twice.limbs, &other.limbs) == Ordering::Less {
+            return q;
+        }
+        if self.negative != other.negative {
+            q - Int::from_u64(1)
+        } else {
+            q + Int::from_u64(1)
+        }
+    }
+
+    pub fn div_small(&self, divisor: u32) -> Int {
+        Int::from_parts(self.negative, div_small_mag(&self.limbs, divisor).0)
+    }
+
+    /// Floor of the square root of the magnitude
+    pub fn isqrt(&self) -> Int {
+        if self.is_zero() {
+            return Int::zero();
+        }
+        let mut x = Int::from_u64(1) << self.bit_len().div_ceil(2);
+        loop {
+            let (q, _) = self.abs().div_rem(&x);
+            let y = (x.clone() + q) >> 1;
+            if y.cmp(&x) != Ordering::Less {
+                return x;
+            }
+            x = y;
+        }
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.negative || self.limbs.len() > 2 {
+            return None;
+        }
+        let low = *self.limbs.first().unwrap_or(&0) as u64;
+        let high = *self.limbs.get(1).unwrap_or(&0) as u64;
+        Some(low | (high << 32))
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        let magnitude = i64::try_from(self.abs().to_u64()?).ok()?;
+        Some(if self.negative { -magnitude } else { magnitude })
+    }
+
+    /// Decimal digits of the magnitude
+    pub fn to_digits(&self) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+        let mut chunks = vec![];
+        let mut rest = self.limbs.clone();
+        while !rest.is_empty() {
+            let (q, r) = div_small_mag(&rest, 1_000_000_000);
+            chunks.push(r);
+            rest = q;
+        }
+        let mut out = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            out.push_str(&format!("{:09}", chunk));
+        }
+        out
+    }
+}
+
+impl PartialOrd for Int {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Int {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.limbs, &other.limbs),
+            (true, true) => cmp_mag(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl Neg for Int {
+    type Output = Int;
+
+    fn neg(self) -> Int {
+        let negative = !self.negative;
+        Int::from_parts(negative, self.limbs)
+    }
+}
+
+impl Add for &Int {
+    type Output = Int;
+
+    fn add(self, other: &Int) -> Int {
+        if self.negative == other.negative {
+            return Int::from_parts(self.negative, add_mag(&self.limbs, &other.limbs));
+        }
+        match cmp_mag(&self.limbs, &other.limbs) {
+            Ordering::Less => Int::from_parts(other.negative, sub_mag(&other.limbs, &self.limbs)),
+            _ => Int::from_parts(self.negative, sub_mag(&self.limbs, &other.limbs)),
+        }
+    }
+}
+
+impl Add for Int {
+    type Output = Int;
+
+    fn add(self, other: Int) -> Int {
+        &self + &other
+    }
+}
+
+impl Sub for &Int {
+    type Output = Int;
+
+    fn sub(self, other: &Int) -> Int {
+        self + &-other.clone()
+    }
+}
+
+impl Sub for Int {
+    type Output = Int;
+
+    fn sub(self, other: Int) -> Int {
+        &self - &other
+    }
+}
+
+impl Mul for &Int {
+    type Output = Int;
+
+    fn mul(self, other: &Int) -> Int {
+        Int::from_parts(
+            self.negative != other.negative,
+            mul_mag(&self.limbs, &other.limbs),
+        )
+    }
+}
+
+impl Mul for Int {
+    type Output = Int;
+
+    fn mul(self, other: Int) -> Int {
+        &self * &other
+    }
+}
+
+impl Shl<u64> for Int {
+    type Output = Int;
+
+    fn shl(self, bits: u64) -> Int {
+        Int::from_parts(self.negative, shl_mag(&self.limbs, bits))
+    }
+}
+
+/// Shifts the magnitude, which truncates towards zero
+impl Shr<u64> for Int {
+    type Output = Int;
+
+    fn shr(self, bits: u64) -> Int {
+        Int::from_parts(self.negative, shr_mag(&self.limbs, bits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(s: &str) -> Int {
+        match s.strip_prefix('-') {
+            Some(digits) => -Int::from_digits(digits).unwrap(),
+            None => Int::from_digits(s).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = int("123456789012345678901234567890");
+        let b = int("-987654321098765432109876543210");
+
+        assert_eq!((&a + &b), int("-864197532086419753208641975320"));
+        assert_eq!((&a - &b), int("1111111110111111111011111111100"));
+        assert_eq!(
+            (&a * &b),
+            int("-121932631137021795226185032733622923332237463801111263526900")
+        );
+    }
+
+    #[test]
+    fn test_div_rem() {
+        let a = int("121932631137021795226185032733622923332237463801111263526901");
+        let b = int("987654321098765432109876543210");
+        let (q, r) = a.div_rem(&b);
+
+        assert_eq!(q, int("123456789012345678901234567890"));
+        assert_eq!(r, int("1"));
+        assert_eq!(int("-7").div_round(&int("2")), int("-4"));
+        assert_eq!(int("7").div_round(&int("3")), int("2"));
+    }
+
+    #[test]
+    fn test_shifts_and_sqrt() {
+        let a = int("1") << 100;
+
+        assert_eq!(a.to_digits(), "1267650600228229401496703205376");
+        assert_eq!((a.clone() >> 98), int("4"));
+        assert_eq!(a.isqrt(), int("1") << 50);
+        assert_eq!(int("99").isqrt(), int("9"));
+    }
+}
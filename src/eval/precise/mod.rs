@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::integer;
+use crate::parser::{Function, Node};
+use crate::{Number, NumberError};
+
+mod float;
+mod int;
+
+use float::Float;
+use int::Int;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PrecisionError {
+    #[error("Requested precision must be at least one digit")]
+    InvalidDigits,
+    #[error("Unbound variable: {0}")]
+    UnboundVariable(String),
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Domain error: {0}")]
+    DomainError(&'static str),
+    #[error("Result is too large")]
+    Overflow,
+    #[error("Result did not converge to the requested precision")]
+    NotConverged,
+}
+
+/// How many times the working precision is doubled before giving up
+const MAX_DOUBLINGS: u32 = 4;
+
+/// The most terms a sum or product may have, fewer than for f64s since every term is evaluated
+/// again at every precision
+const MAX_TERMS: i64 = 100_000;
+
+/// A real number rounded to a fixed number of significant decimal digits
+#[derive(Debug, Clone, PartialEq)]
+pub struct Approximation {
+    negative: bool,
+    digits: String,
+    exponent: i64,
+}
+
+impl Approximation {
+    fn from_float(float: &Float, digits: usize) -> Approximation {
+        let (digits, exponent) = float.to_decimal(digits);
+        Approximation {
+            negative: float.is_negative(),
+            digits,
+            exponent,
+        }
+    }
+
+    fn zero() -> Approximation {
+        Approximation {
+            negative: false,
+            digits: "0".to_string(),
+            exponent: 0,
+        }
+    }
+
+    /// The significant digits, without sign or decimal point
+    pub fn digits(&self) -> &str {
+        &self.digits
+    }
+
+    /// The power of ten of the first significant digit
+    pub fn exponent(&self) -> i64 {
+        self.exponent
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+}
+
+impl fmt::Display for Approximation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+
+        let digits = self.digits.len() as i64;
+        if self.exponent >= digits || self.exponent < -5 {
+            let (first, rest) = self.digits.split_at(1);
+            write!(f, "{}", first)?;
+            if !rest.is_empty() {
+                write!(f, ".{}", rest)?;
+            }
+            return write!(f, "e{}", self.exponent);
+        }
+
+        if self.exponent < 0 {
+            let zeros = "0".repeat((-self.exponent - 1) as usize);
+            return write!(f, "0.{}{}", zeros, self.digits);
+        }
+
+        let (int_part, frac_part) = self.digits.split_at(self.exponent as usize + 1);
+        write!(f, "{}", int_part)?;
+        if !frac_part.is_empty() {
+            write!(f, ".{}", frac_part)?;
+        }
+        Ok(())
+    }
+}
+
+/// Numerically evaluates an expression to the given number of significant digits, like `N[expr, digits]`
+///
+/// The expression is evaluated at increasing working precision until two consecutive results agree
+/// on every requested digit, so cancellation such as `(1 + 10^-30) - 1` is absorbed automatically.
+/// A result that keeps shrinking as the precision grows, like `sin(pi)`, is reported as zero.
+/// Number literals are taken to be the exact decimal they were written as.
+pub fn evaluate(node: &Node, digits: usize) -> Result<Approximation, PrecisionError> {
+    if digits == 0 {
+        return Err(PrecisionError::InvalidDigits);
+    }
+
+    let base = (digits as f64 * std::f64::consts::LOG2_10).ceil() as u64 + 16;
+    let mut prec = base;
+    let mut previous = None;
+
+    for _ in 0..=MAX_DOUBLINGS {
+        let value = eval(node, prec, &HashMap::new())?;
+        let current = Approximation::from_float(&value, digits);
+
+        // Exact zeros agree at every precision even when they come from cancellation, so they are
+        // only trusted once the precision has been raised as far as it goes
+        if !value.is_zero() && previous.as_ref() == Some(&current) {
+            return Ok(current);
+        }
+
+        previous = Some(current);
+        prec *= 2;
+    }
+
+    // A result that is still on the order of the rounding error is zero
+    let value = eval(node, prec, &HashMap::new())?;
+    if value.is_zero() || value.magnitude() < 32 - prec as i64 {
+        return Ok(Approximation::zero());
+    }
+    let current = Approximation::from_float(&value, digits);
+    if previous == Some(current.clone()) {
+        Ok(current)
+    } else {
+        Err(PrecisionError::NotConverged)
+    }
+}
+
+/// Evaluates the node with every intermediate result truncated to `prec` bits, `bound` holds the
+/// values of the indices of the sums and products around it
+fn eval(node: &Node, prec: u64, bound: &HashMap<String, Float>) -> Result<Float, PrecisionError> {
+    match node {
+        Node::Number(number) => {
+            if !number.is_real() {
+                return Err(PrecisionError::DomainError("complex number"));
+            }
+            let value = number.value();
+            if !value.is_finite() {
+                return Err(PrecisionError::Overflow);
+            }
+            let literal = match number.literal() {
+                Some(literal) => literal.to_string(),
+                None => value.to_string(),
+            };
+            let digits = literal.trim_start_matches('-');
+            let float = Float::from_decimal(digits, prec).ok_or(PrecisionError::Overflow)?;
+            Ok(if value < 0.0 { float.neg() } else { float })
+        }
+        Node::Variable(name) => bound
+            .get(name)
+            .cloned()
+            .ok_or_else(|| PrecisionError::UnboundVariable(name.clone())),
+        Node::PiConstant => Ok(Float::pi(prec)),
+        Node::EConstant => Float::from_decimal("1", prec).unwrap().exp(prec),
+        Node::IConstant => Err(PrecisionError::DomainError("complex number")),
+        Node::Add(l, r) => Ok(eval(l, prec, bound)?.add(&eval(r, prec, bound)?, prec)),
+        Node::Sub(l, r) => Ok(eval(l, prec, bound)?.sub(&eval(r, prec, bound)?, prec)),
+        Node::Mul(l, r) => Ok(eval(l, prec, bound)?.mul(&eval(r, prec, bound)?, prec)),
+        Node::Div(l, r) => eval(l, prec, bound)?.div(&eval(r, prec, bound)?, prec),
+        Node::Pow(l, r) => eval(l, prec, bound)?.pow(&eval(r, prec, bound)?, prec),
+        Node::Exp(n) => eval(n, prec, bound)?.exp(prec),
+        Node::Log(n) => eval(n, prec, bound)?.ln(prec),
+        Node::Sin(n) => Ok(eval(n, prec, bound)?.sin_cos(prec)?.0),
+        Node::Cos(n) => Ok(eval(n, prec, bound)?.sin_cos(prec)?.1),
+        Node::Tan(n) => {
+            let (sin, cos) = eval(n, prec, bound)?.sin_cos(prec)?;
+            sin.div(&cos, prec)
+        }
+        Node::Sqrt(n) => eval(n, prec, bound)?.sqrt(prec),
+        Node::Neg(n) => Ok(eval(n, prec, bound)?.neg()),
+        Node::Function(Function::Floor, arguments) => Ok(eval(&arguments[0], prec, bound)?.floor()),
+        Node::Function(Function::Ceil, arguments) => Ok(eval(&arguments[0], prec, bound)?.ceil()),
+        Node::Function(Function::Factorial, arguments) => {
+            factorial(integer(&eval(&arguments[0], prec, bound)?)?)
+        }
+        Node::Function(function, arguments) if function.binds_index() => {
+            repeated(*function, arguments, prec, bound)
+        }
+        Node::Function(function, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|n| Ok(Number::real(integer(&eval(n, prec, bound)?)? as f64)))
+                .collect::<Result<Vec<_>, PrecisionError>>()?;
+            let value = integer::evaluate(*function, &arguments)
+                .map_err(|error| match error {
+                    NumberError::DivisionByZero => PrecisionError::DivisionByZero,
+                    NumberError::Domain(message) => PrecisionError::DomainError(message),
+                    _ => PrecisionError::Overflow,
+                })?
+                .value();
+            // Larger results have already been rounded to an f64
+            if value.abs() > 9007199254740992.0 {
+                return Err(PrecisionError::Overflow);
+            }
+            Ok(Float::from_int(Int::from_i64(value as i64)))
+        }
+    }
+}
+
+/// A sum or product, with the index bound to every integer between the bounds in turn, and the
+/// result of a reversed range negated or inverted, see [`integer::range`]
+fn repeated(
+    function: Function,
+    arguments: &[Node],
+    prec: u64,
+    bound: &HashMap<String, Float>,
+) -> Result<Float, PrecisionError> {
+    let [summand, Node::Variable(index), a, b] = arguments else {
+        return Err(PrecisionError::DomainError("the index must be a variable"));
+    };
+    let a = integer(&eval(a, prec, bound)?)?;
+    let b = integer(&eval(b, prec, bound)?)?;
+    let too_many = || PrecisionError::DomainError("too many terms");
+    let (range, reversed) = integer::range(a, b).map_err(|_| too_many())?;
+    if range.end().saturating_sub(*range.start()) >= MAX_TERMS {
+        return Err(too_many());
+    }
+
+    let mut bound = bound.clone();
+    let identity = Float::from_int(Int::from_i64(i64::from(function == Function::Product)));
+    let mut result = identity.clone();
+    for k in range {
+        bound.insert(index.clone(), Float::from_int(Int::from_i64(k)));
+        let term = eval(summand, prec, &bound)?;
+        result = match function {
+            Function::Sum => result.add(&term, prec),
+            _ => result.mul(&term, prec),
+        };
+    }
+    match function {
+        _ if !reversed => Ok(result),
+        Function::Sum => Ok(result.neg()),
+        _ => identity.div(&result, prec),
+    }
+}
+
+/// The arguments of the integer functions are exact, so they have to be whole numbers at any
+/// precision
+fn integer(value: &Float) -> Result<i64, PrecisionError> {
+    value
+        .to_int()
+        .ok_or(PrecisionError::DomainError("argument must be an integer"))?
+        .to_i64()
+        .ok_or(PrecisionError::Overflow)
+}
+
+/// Factorials are exact integers of any size, unlike the f64 ones
+fn factorial(n: i64) -> Result<Float, PrecisionError> {
+    if n < 0 {
+        return Err(PrecisionError::DomainError(
+            "factorial of a negative number",
+        ));
+    }
+    if n > 100_000 {
+        return Err(PrecisionError::Overflow);
+    }
+    let value = (1..=n as u64).fold(Int::from_u64(1), |acc, k| acc * Int::from_u64(k));
+    Ok(Float::from_int(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn n(input: &str, digits: usize) -> Result<String, PrecisionError> {
+        let node = parse(&tokenize(input).unwrap()).unwrap();
+        evaluate(&node, digits).map(|approximation| approximation.to_string())
+    }
+
+    #[test]
+    fn test_constants_to_many_digits() {
+        assert_eq!(
+            n("pi", 50).unwrap(),
+            "3.1415926535897932384626433832795028841971693993751"
+        );
+        assert_eq!(
+            n("e", 50).unwrap(),
+            "2.7182818284590452353602874713526624977572470937000"
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(n("sqrt(2)", 20).unwrap(), "1.4142135623730950488");
+        assert_eq!(n("ln(2)", 20).unwrap(), "0.69314718055994530942");
+        assert_eq!(n("sin(1) + cos(1)", 20).unwrap(), "1.3817732906760362241");
+        assert_eq!(n("tan(1)", 20).unwrap(), "1.5574077246549022305");
+        assert_eq!(n("e^(-2)", 10).unwrap(), "0.1353352832");
+        assert_eq!(n("2^100", 5).unwrap(), "1.2677e30");
+        assert_eq!(n("sin(10^20)", 20).unwrap(), "-0.64525128526578084421");
+    }
+
+    #[test]
+    fn test_integer_functions() {
+        assert_eq!(n("25!", 26).unwrap(), "15511210043330985984000000");
+        assert_eq!(n("floor(-pi) + ceil(e)", 1).unwrap(), "-1");
+        assert_eq!(n("binomial(10, 3) / gcd(12, 18)", 2).unwrap(), "20");
+        assert!(matches!(
+            n("gcd(1.5, 3)", 5),
+            Err(PrecisionError::DomainError(_))
+        ));
+    }
+
+    #[test]
+    fn test_sums_and_products() {
+        assert_eq!(
+            n("sum(1/k^2, k, 1, 10)", 30).unwrap(),
+            "1.54976773116654069035021415974"
+        );
+        assert_eq!(
+            n("product(k, k, 1, 30)", 33).unwrap(),
+            "265252859812191058636308480000000"
+        );
+        assert_eq!(n("sum(sum(j, j, 1, k), k, 1, 4)", 2).unwrap(), "20");
+        assert!(matches!(
+            n("sum(k, k, 1, 2.5)", 5),
+            Err(PrecisionError::DomainError(_))
+        ));
+        assert_eq!(
+            n("sum(k, k, 1, n)", 5),
+            Err(PrecisionError::UnboundVariable("n".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_literals_are_exact_decimals() {
+        assert_eq!(
+            n("0.1 + 0.2", 30).unwrap(),
+            "0.300000000000000000000000000000"
+        );
+        assert_eq!(
+            n("12345678901234567890", 20).unwrap(),
+            "12345678901234567890"
+        );
+        assert_eq!(
+            n("1.000000000000000000001 - 1", 25).unwrap(),
+            "1.000000000000000000000000e-21"
+        );
+        assert_eq!(
+            n("0x1_0000_0000_0000_0001", 20).unwrap(),
+            "18446744073709551617"
+        );
+    }
+
+    #[test]
+    fn test_precision_is_raised_for_cancellation() {
+        assert_eq!(n("(1 + 10^-30) - 1", 10).unwrap(), "1.000000000e-30");
+        assert_eq!(n("sin(pi)", 20).unwrap(), "0");
+        assert_eq!(n("ln(e^3)", 15).unwrap(), "3.00000000000000");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            n("x + 1", 10),
+            Err(PrecisionError::UnboundVariable("x".to_string()))
+        );
+        assert_eq!(n("1 / 0", 10), Err(PrecisionError::DivisionByZero));
+        assert!(matches!(
+            n("sqrt(0 - 1)", 10),
+            Err(PrecisionError::DomainError(_))
+        ));
+        assert_eq!(n("pi", 0), Err(PrecisionError::InvalidDigits));
+    }
+}
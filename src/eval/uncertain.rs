@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::f64::consts::{E, PI};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use thiserror::Error;
+
+use super::EvalError;
+use crate::integer;
+use crate::lexer::{tokenize, LexerError, LexerToken};
+use crate::parser::{parse, Function, Node, ParserError};
+
+/// Every measurement gets its own id, so its errors are correlated with itself and nothing else
+static NEXT_SOURCE: AtomicUsize = AtomicUsize::new(0);
+
+/// A value with a standard uncertainty, `9.81 ± 0.02`
+///
+/// Errors are propagated linearly: the result remembers its derivative with respect to every
+/// measurement it was computed from, scaled by that measurement's uncertainty. Using a measurement
+/// twice is therefore correlated, `x - x` is exactly zero, while two measurements created
+/// separately are independent and their contributions add in quadrature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Uncertain {
+    value: f64,
+    /// `(measurement, derivative * uncertainty of the measurement)`, sorted by measurement
+    contributions: Vec<(usize, f64)>,
+}
+
+impl Uncertain {
+    /// A new measurement, independent of every other one
+    pub fn new(value: f64, uncertainty: f64) -> Uncertain {
+        if uncertainty == 0.0 {
+            return Uncertain::exact(value);
+        }
+        let source = NEXT_SOURCE.fetch_add(1, Ordering::Relaxed);
+        Uncertain {
+            value,
+            contributions: vec![(source, uncertainty.abs())],
+        }
+    }
+
+    pub fn exact(value: f64) -> Uncertain {
+        Uncertain {
+            value,
+            contributions: vec![],
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The standard uncertainty, the contributions of the measurements added in quadrature
+    pub fn uncertainty(&self) -> f64 {
+        self.contributions
+            .iter()
+            .map(|(_, c)| c * c)
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    fn is_exact(&self) -> bool {
+        self.contributions.is_empty()
+    }
+
+    /// Applies a function with the given value and derivative
+    fn chain(&self, value: f64, derivative: f64) -> Result<Uncertain, EvalError> {
+        Uncertain::exact(value).combine(self, derivative, &Uncertain::exact(0.0), 0.0)
+    }
+
+    /// Adds `da * a + db * b` to the contributions, checking the value
+    fn combine(
+        mut self,
+        a: &Uncertain,
+        da: f64,
+        b: &Uncertain,
+        db: f64,
+    ) -> Result<Uncertain, EvalError> {
+        if self.value.is_nan() {
+            return Err(EvalError::NaN);
+        }
+        if self.value.is_infinite() {
+            return Err(EvalError::Overflow);
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < a.contributions.len() || j < b.contributions.len() {
+            let left = a.contributions.get(i);
+            let right = b.contributions.get(j);
+            let (source, contribution) = match (left, right) {
+                (Some(l), Some(r)) if l.0 == r.0 => {
+                    (i, j) = (i + 1, j + 1);
+                    (l.0, da * l.1 + db * r.1)
+                }
+                (Some(l), Some(r)) if l.0 < r.0 => {
+                    i += 1;
+                    (l.0, da * l.1)
+                }
+                (Some(l), None) => {
+                    i += 1;
+                    (l.0, da * l.1)
+                }
+                (_, Some(r)) => {
+                    j += 1;
+                    (r.0, db * r.1)
+                }
+                (None, None) => unreachable!(),
+            };
+            if contribution != 0.0 {
+                self.contributions.push((source, contribution));
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn add(&self, other: &Uncertain) -> Result<Uncertain, EvalError> {
+        Uncertain::exact(self.value + other.value).combine(self, 1.0, other, 1.0)
+    }
+
+    pub fn sub(&self, other: &Uncertain) -> Result<Uncertain, EvalError> {
+        Uncertain::exact(self.value - other.value).combine(self, 1.0, other, -1.0)
+    }
+
+    pub fn mul(&self, other: &Uncertain) -> Result<Uncertain, EvalError> {
+        Uncertain::exact(self.value * other.value).combine(self, other.value, other, self.value)
+    }
+
+    pub fn div(&self, other: &Uncertain) -> Result<Uncertain, EvalError> {
+        if other.value == 0.0 {
+            return Err(EvalError::DivisionByZero);
+        }
+        let value = self.value / other.value;
+        Uncertain::exact(value).combine(self, 1.0 / other.value, other, -value / other.value)
+    }
+
+    pub fn neg(&self) -> Uncertain {
+        Uncertain {
+            value: -self.value,
+            contributions: self.contributions.iter().map(|(s, c)| (*s, -c)).collect(),
+        }
+    }
+
+    pub fn pow(&self, exponent: &Uncertain) -> Result<Uncertain, EvalError> {
+        let (a, b) = (self.value, exponent.value);
+        if a == 0.0 && b < 0.0 {
+            return Err(EvalError::DivisionByZero);
+        }
+        if a < 0.0 && (b.fract() != 0.0 || !exponent.is_exact()) {
+            return Err(EvalError::DomainError(
+                "negative base with a fractional or uncertain exponent",
+            ));
+        }
+        let value = a.powf(b);
+        let da = if b == 0.0 { 0.0 } else { b * a.powf(b - 1.0) };
+        let db = if exponent.is_exact() {
+            0.0
+        } else {
+            value * a.ln()
+        };
+        Uncertain::exact(value).combine(self, da, exponent, db)
+    }
+
+    pub fn exp(&self) -> Result<Uncertain, EvalError> {
+        let value = self.value.exp();
+        self.chain(value, value)
+    }
+
+    pub fn ln(&self) -> Result<Uncertain, EvalError> {
+        if self.value <= 0.0 {
+            return Err(EvalError::DomainError("ln of a non-positive number"));
+        }
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    pub fn sqrt(&self) -> Result<Uncertain, EvalError> {
+        if self.value < 0.0 {
+            return Err(EvalError::DomainError("sqrt of a negative number"));
+        }
+        if self.value == 0.0 && !self.is_exact() {
+            return Err(EvalError::DomainError("uncertain sqrt at zero"));
+        }
+        let value = self.value.sqrt();
+        self.chain(value, if self.is_exact() { 0.0 } else { 0.5 / value })
+    }
+
+    pub fn sin(&self) -> Result<Uncertain, EvalError> {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+
+    pub fn cos(&self) -> Result<Uncertain, EvalError> {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+
+    pub fn tan(&self) -> Result<Uncertain, EvalError> {
+        let cos = self.value.cos();
+        self.chain(self.value.tan(), 1.0 / (cos * cos))
+    }
+}
+
+/// Writes the uncertainty with two significant digits and the value to the same decimal place,
+/// `19.620 ± 0.040`
+impl fmt::Display for Uncertain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let uncertainty = self.uncertainty();
+        if uncertainty == 0.0 || !uncertainty.is_finite() {
+            return write!(f, "{} ± {}", self.value, uncertainty);
+        }
+
+        let decimals = 1 - uncertainty.log10().floor() as i32;
+        if decimals >= 0 {
+            let decimals = decimals as usize;
+            write!(
+                f,
+                "{:.*} ± {:.*}",
+                decimals, self.value, decimals, uncertainty
+            )
+        } else {
+            let scale = 10f64.powi(-decimals);
+            let round = |x: f64| (x / scale).round() * scale;
+            write!(f, "{} ± {}", round(self.value), round(uncertainty))
+        }
+    }
+}
+
+/// Evaluates an expression with every variable bound to a value with an uncertainty
+pub fn evaluate(
+    node: &Node,
+    variables: &HashMap<String, Uncertain>,
+) -> Result<Uncertain, EvalError> {
+    let eval = |node: &Node| evaluate(node, variables);
+
+    match node {
+        Node::Number(number) => {
+            if !number.is_real() {
+                return Err(EvalError::DomainError("complex number"));
+            }
+            Ok(Uncertain::exact(number.value()))
+        }
+        Node::Variable(name) => variables
+            .get(name)
+            .cloned()
+            .ok_or_else(|| EvalError::UnboundVariable(name.clone())),
+        Node::PiConstant => Ok(Uncertain::exact(PI)),
+        Node::EConstant => Ok(Uncertain::exact(E)),
+        Node::IConstant => Err(EvalError::DomainError("complex number")),
+        Node::Add(l, r) => eval(l)?.add(&eval(r)?),
+        Node::Sub(l, r) => eval(l)?.sub(&eval(r)?),
+        Node::Mul(l, r) => eval(l)?.mul(&eval(r)?),
+        Node::Div(l, r) => eval(l)?.div(&eval(r)?),
+        Node::Pow(l, r) => eval(l)?.pow(&eval(r)?),
+        Node::Exp(n) => eval(n)?.exp(),
+        Node::Log(n) => eval(n)?.ln(),
+        Node::Sin(n) => eval(n)?.sin(),
+        Node::Cos(n) => eval(n)?.cos(),
+        Node::Tan(n) => eval(n)?.tan(),
+        Node::Sqrt(n) => eval(n)?.sqrt(),
+        Node::Neg(n) => Ok(eval(n)?.neg()),
+        Node::Function(function, arguments) if function.binds_index() => {
+            let bound = |n: &Node| match eval(n)? {
+                bound if bound.is_exact() => Ok(bound.value),
+                _ => Err(EvalError::DomainError(
+                    "integer function of an uncertain value",
+                )),
+            };
+            let (a, b) = (bound(&arguments[2])?, bound(&arguments[3])?);
+            let identity = Uncertain::exact(f64::from(*function == Function::Product));
+            let (mut terms, reversed) = integer::terms(arguments, a, b)?;
+            let result = terms.try_fold(identity.clone(), |result, term| match function {
+                Function::Sum => result.add(&eval(&term)?),
+                _ => result.mul(&eval(&term)?),
+            })?;
+            match function {
+                _ if !reversed => Ok(result),
+                Function::Sum => Ok(result.neg()),
+                _ => identity.div(&result),
+            }
+        }
+        Node::Function(function, arguments) => {
+            let arguments = arguments.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            let is_piecewise_constant = matches!(function, Function::Floor | Function::Ceil);
+            if !is_piecewise_constant && !arguments.iter().all(Uncertain::is_exact) {
+                return Err(EvalError::DomainError(
+                    "integer function of an uncertain value",
+                ));
+            }
+            // Floor and ceil are flat wherever they are differentiable
+            let values: Vec<f64> = arguments.iter().map(|a| a.value).collect();
+            Ok(Uncertain::exact(integer::evaluate_real(
+                *function, &values,
+            )?))
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum UncertaintyError {
+    #[error(transparent)]
+    Lexer(#[from] LexerError),
+    #[error(transparent)]
+    Parser(#[from] ParserError),
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+}
+
+/// Evaluates text where measurements are written as `9.81 +- 0.02` or `9.81 ± 0.02`
+///
+/// Every written measurement is independent of the others, a measurement that appears more than
+/// once should be bound to a variable instead.
+pub fn calculate(
+    input: &str,
+    variables: &HashMap<String, Uncertain>,
+) -> Result<Uncertain, UncertaintyError> {
+    let tokens = tokenize(&input.replace('±', "+-"))?;
+    let mut variables = variables.clone();
+    let mut replaced = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        if let [LexerToken::Number(value), LexerToken::AddOperator, LexerToken::SubOperator, LexerToken::Number(uncertainty), ..] =
+            &tokens[i..]
+        {
+            // The lexer never produces a variable with this name, so it can't shadow one
+            let name = format!("±{}", variables.len());
+            let measurement = Uncertain::new(value.value(), uncertainty.value());
+            variables.insert(name.clone(), measurement);
+            replaced.push(LexerToken::Variable(name));
+            i += 4;
+        } else {
+            replaced.push(tokens[i].clone());
+            i += 1;
+        }
+    }
+    Ok(evaluate(&parse(&replaced)?, &variables)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculated(input: &str) -> Uncertain {
+        calculate(input, &HashMap::new()).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_independent_measurements() {
+        let sum = calculated("(1 +- 0.3) + (2 ± 0.4)");
+        assert_close(sum.value(), 3.0);
+        assert_close(sum.uncertainty(), 0.5);
+
+        // Relative errors of a product add in quadrature
+        let force = calculated("2 +- 0.02 * 9.81 +- 0.0981");
+        assert_close(force.value(), 19.62);
+        assert_close(force.uncertainty(), 19.62 * 2f64.sqrt() / 100.0);
+        assert_eq!(force.to_string(), "19.62 ± 0.28");
+    }
+
+    #[test]
+    fn test_correlated_terms() {
+        let x = Uncertain::new(3.0, 0.1);
+        let variables = HashMap::from([("x".to_string(), x)]);
+        let difference = calculate("x - x", &variables).unwrap();
+        assert_eq!(difference.uncertainty(), 0.0);
+
+        let square = calculate("x * x", &variables).unwrap();
+        assert_close(square.uncertainty(), 2.0 * 3.0 * 0.1);
+
+        // Two separate measurements of the same size are independent
+        let product = calculated("3 +- 0.1 * 3 +- 0.1");
+        assert_close(product.uncertainty(), 3.0 * 0.1 * 2f64.sqrt());
+    }
+
+    #[test]
+    fn test_sums_and_products() {
+        let x = Uncertain::new(3.0, 0.1);
+        let variables = HashMap::from([("x".to_string(), x)]);
+        // x + x^2 has the derivative 1 + 2x, the terms are correlated
+        let sum = calculate("sum(x^k, k, 1, 2)", &variables).unwrap();
+        assert_close(sum.value(), 12.0);
+        assert_close(sum.uncertainty(), 7.0 * 0.1);
+        assert_close(calculated("product(k, k, 1, 4)").value(), 24.0);
+    }
+
+    #[test]
+    fn test_functions() {
+        let root = calculated("sqrt(16 +- 0.8)");
+        assert_close(root.value(), 4.0);
+        assert_close(root.uncertainty(), 0.1);
+        let log = calculated("ln(2 +- 0.5)");
+        assert_close(log.uncertainty(), 0.25);
+        assert_eq!(calculated("12345 +- 678").to_string(), "12350 ± 680");
+    }
+
+    #[test]
+    fn test_errors() {
+        let variables = HashMap::new();
+        assert!(matches!(
+            calculate("ln(-1 +- 0.1)", &variables),
+            Err(UncertaintyError::Eval(EvalError::DomainError(_)))
+        ));
+        assert!(matches!(
+            calculate("1 / (0 +- 0.1)", &variables),
+            Err(UncertaintyError::Eval(EvalError::DivisionByZero))
+        ));
+        assert!(matches!(
+            calculate("y", &variables),
+            Err(UncertaintyError::Eval(EvalError::UnboundVariable(_)))
+        ));
+        assert!(matches!(
+            calculate("sum(k^2, k, 1, 3 +- 1)", &variables),
+            Err(UncertaintyError::Eval(EvalError::DomainError(_)))
+        ));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::Number;
+
+/// How the digits of a number are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notation {
+    /// Up to 15 significant digits without trailing zeros, very large and very small numbers
+    /// switch to scientific notation
+    Auto,
+    /// A fixed number of digits after the decimal point, `3.14`
+    Fixed(usize),
+    /// A number of significant digits, `0.00123`
+    Significant(usize),
+    /// A number of significant digits with one digit before the point, `1.23e-3`
+    Scientific(usize),
+    /// A number of significant digits with an exponent that is a multiple of 3, `1.23e-3`
+    Engineering(usize),
+    /// A fraction like `5/3`, numbers that aren't a simple fraction use `Auto`
+    Fraction,
+    /// A mixed number like `1 2/3`, numbers that aren't a simple fraction use `Auto`
+    Mixed,
+}
+
+/// Options for [`Number::format`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    notation: Notation,
+    grouping: Option<char>,
+    decimal_separator: char,
+}
+
+impl Default for NumberFormat {
+    fn default() -> NumberFormat {
+        NumberFormat::new(Notation::Auto)
+    }
+}
+
+/// Fractions with a larger denominator are written as decimals instead
+const MAX_DENOMINATOR: i64 = 1_000_000;
+
+/// Switch to scientific notation outside of `[1e-5, 1e15)` in `Auto`
+const AUTO_RANGE: (i32, i32) = (-5, 15);
+
+impl NumberFormat {
+    pub fn new(notation: Notation) -> NumberFormat {
+        NumberFormat {
+            notation,
+            grouping: None,
+            decimal_separator: '.',
+        }
+    }
+
+    /// Separates groups of three digits before the decimal point, `1,234,567`
+    pub fn with_grouping(mut self, separator: char) -> NumberFormat {
+        self.grouping = Some(separator);
+        self
+    }
+
+    /// The character between the integer and fractional part, `3,14`
+    pub fn with_decimal_separator(mut self, separator: char) -> NumberFormat {
+        self.decimal_separator = separator;
+        self
+    }
+
+    /// Formats a real value
+    pub fn format_real(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+
+        let formatted = match self.notation {
+            Notation::Auto => auto(value),
+            Notation::Fixed(digits) => format!("{:.*}", digits, value),
+            Notation::Significant(digits) => {
+                let (digits, exponent) = significant_digits(value, digits);
+                positional(value < 0.0, &digits, exponent)
+            }
+            Notation::Scientific(digits) => {
+                let (digits, exponent) = significant_digits(value, digits);
+                with_exponent(value < 0.0, &digits, 1, exponent)
+            }
+            Notation::Engineering(digits) => {
+                let (digits, exponent) = significant_digits(value, digits);
+                let shift = exponent.rem_euclid(3);
+                with_exponent(value < 0.0, &digits, 1 + shift as usize, exponent - shift)
+            }
+            Notation::Fraction => match fraction(value) {
+                Some((num, 1)) => num.to_string(),
+                Some((num, den)) => format!("{}/{}", num, den),
+                None => auto(value),
+            },
+            Notation::Mixed => match fraction(value) {
+                Some((num, 1)) => num.to_string(),
+                Some((num, den)) if num.abs() < den => format!("{}/{}", num, den),
+                Some((num, den)) => format!("{} {}/{}", num / den, (num % den).abs(), den),
+                None => auto(value),
+            },
+        };
+        self.separated(&formatted)
+    }
+
+    /// Applies the digit grouping and decimal separator to a formatted number
+    fn separated(&self, formatted: &str) -> String {
+        let (sign, unsigned) = match formatted.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", formatted),
+        };
+        let end = unsigned
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(unsigned.len());
+        let (integer, rest) = unsigned.split_at(end);
+
+        let mut result = sign.to_string();
+        for (i, digit) in integer.chars().enumerate() {
+            if let Some(separator) = self.grouping {
+                if i > 0 && (integer.len() - i) % 3 == 0 {
+                    result.push(separator);
+                }
+            }
+            result.push(digit);
+        }
+        match rest.strip_prefix('.') {
+            Some(fraction) => {
+                result.push(self.decimal_separator);
+                result.push_str(fraction);
+            }
+            None => result.push_str(rest),
+        }
+        result
+    }
+}
+
+/// The digits of the value rounded to `count` significant digits, and the decimal exponent of
+/// the first one
+fn significant_digits(value: f64, count: usize) -> (String, i32) {
+    let count = count.max(1);
+    let formatted = format!("{:.*e}", count - 1, value.abs());
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    (mantissa.replace('.', ""), exponent.parse().unwrap_or(0))
+}
+
+/// Writes the digits `d.ddd * 10^exponent` without an exponent, e.g. `0.00123` or `1230`
+fn positional(negative: bool, digits: &str, exponent: i32) -> String {
+    let sign = if negative { "-" } else { "" };
+    let point = exponent + 1;
+    if point <= 0 {
+        format!("{}0.{}{}", sign, "0".repeat(-point as usize), digits)
+    } else if point as usize >= digits.len() {
+        format!(
+            "{}{}{}",
+            sign,
+            digits,
+            "0".repeat(point as usize - digits.len())
+        )
+    } else {
+        let (integer, fraction) = digits.split_at(point as usize);
+        format!("{}{}.{}", sign, integer, fraction)
+    }
+}
+
+/// Writes the digits with `integer_digits` digits before the point and an exponent, `12.3e3`
+fn with_exponent(negative: bool, digits: &str, integer_digits: usize, exponent: i32) -> String {
+    let mut digits = digits.to_string();
+    if digits.len() < integer_digits {
+        digits.push_str(&"0".repeat(integer_digits - digits.len()));
+    }
+    let mantissa = positional(negative, &digits, integer_digits as i32 - 1);
+    format!("{}e{}", mantissa, exponent)
+}
+
+/// 15 significant digits without trailing zeros, enough to hide the error in `0.1 + 0.2`
+fn auto(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    let (digits, exponent) = significant_digits(value, 15);
+    let digits = digits.trim_end_matches('0');
+    if exponent < AUTO_RANGE.0 || exponent >= AUTO_RANGE.1 {
+        let digits = if digits.is_empty() { "0" } else { digits };
+        with_exponent(value < 0.0, digits, 1, exponent)
+    } else {
+        positional(value < 0.0, digits, exponent)
+    }
+}
+
+/// The simplest fraction that equals the value up to rounding, found with continued fractions
+fn fraction(value: f64) -> Option<(i64, i64)> {
+    let tolerance = value.abs().max(1.0) * 8.0 * f64::EPSILON;
+    let (mut previous, mut current) = ((0i64, 1i64), (1i64, 0i64));
+    let mut rest = value.abs();
+    loop {
+        if rest > i64::MAX as f64 {
+            return None;
+        }
+        let term = rest.floor() as i64;
+        let next = (
+            term.checked_mul(current.0)?.checked_add(previous.0)?,
+            term.checked_mul(current.1)?.checked_add(previous.1)?,
+        );
+        if next.1 > MAX_DENOMINATOR {
+            return None;
+        }
+        (previous, current) = (current, next);
+
+        if (current.0 as f64 / current.1 as f64 - value.abs()).abs() <= tolerance {
+            let num = if value < 0.0 { -current.0 } else { current.0 };
+            return Some((num, current.1));
+        }
+        rest = 1.0 / (rest - term as f64);
+    }
+}
+
+impl Number {
+    /// Formats the number, complex numbers are written as `a + bi`, with fractions in parentheses
+    /// like `1/2 + (1/4)i` so the `i` isn't read as part of the denominator
+    pub fn format(&self, format: &NumberFormat) -> String {
+        if self.is_real() {
+            return format.format_real(self.re());
+        }
+
+        let imaginary = match format.format_real(self.im().abs()).as_str() {
+            "1" => "i".to_string(),
+            im if im.contains(['/', ' ']) => format!("({})i", im),
+            im => format!("{}i", im),
+        };
+        if self.re() == 0.0 {
+            let sign = if self.im() < 0.0 { "-" } else { "" };
+            return format!("{}{}", sign, imaginary);
+        }
+        let sign = if self.im() < 0.0 { "-" } else { "+" };
+        format!("{} {} {}", format.format_real(self.re()), sign, imaginary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted(value: f64, notation: Notation) -> String {
+        NumberFormat::new(notation).format_real(value)
+    }
+
+    #[test]
+    fn test_auto() {
+        assert_eq!(formatted(0.1 + 0.2, Notation::Auto), "0.3");
+        assert_eq!(formatted(-42.0, Notation::Auto), "-42");
+        assert_eq!(formatted(1e20, Notation::Auto), "1e20");
+        assert_eq!(formatted(1.5e-7, Notation::Auto), "1.5e-7");
+        assert_eq!(formatted(0.0001, Notation::Auto), "0.0001");
+        assert_eq!(Number::real(2.5).to_string(), "2.5");
+    }
+
+    #[test]
+    fn test_digits() {
+        assert_eq!(formatted(1.23456, Notation::Fixed(2)), "1.23");
+        assert_eq!(formatted(2.0, Notation::Fixed(3)), "2.000");
+        assert_eq!(formatted(0.00123456, Notation::Significant(3)), "0.00123");
+        assert_eq!(formatted(123456.0, Notation::Significant(2)), "120000");
+        assert_eq!(formatted(1.2, Notation::Significant(3)), "1.20");
+    }
+
+    #[test]
+    fn test_exponents() {
+        assert_eq!(formatted(12345.0, Notation::Scientific(3)), "1.23e4");
+        assert_eq!(formatted(-0.000123, Notation::Scientific(2)), "-1.2e-4");
+        assert_eq!(formatted(12345.0, Notation::Engineering(3)), "12.3e3");
+        assert_eq!(formatted(0.000123, Notation::Engineering(3)), "123e-6");
+        assert_eq!(formatted(100000.0, Notation::Engineering(1)), "100e3");
+    }
+
+    #[test]
+    fn test_fractions() {
+        assert_eq!(formatted(2.0 / 3.0, Notation::Fraction), "2/3");
+        assert_eq!(formatted(-5.0 / 3.0, Notation::Fraction), "-5/3");
+        assert_eq!(formatted(5.0 / 3.0, Notation::Mixed), "1 2/3");
+        assert_eq!(formatted(-5.0 / 3.0, Notation::Mixed), "-1 2/3");
+        assert_eq!(formatted(0.75, Notation::Mixed), "3/4");
+        assert_eq!(formatted(0.1 + 0.2, Notation::Fraction), "3/10");
+        assert_eq!(formatted(4.0, Notation::Mixed), "4");
+        assert_eq!(
+            formatted(std::f64::consts::PI, Notation::Fraction),
+            "3.14159265358979"
+        );
+    }
+
+    #[test]
+    fn test_separators() {
+        let format = NumberFormat::new(Notation::Fixed(2)).with_grouping(',');
+        assert_eq!(format.format_real(-1234567.891), "-1,234,567.89");
+        assert_eq!(format.format_real(123.0), "123.00");
+
+        let european = format.with_grouping('.').with_decimal_separator(',');
+        assert_eq!(european.format_real(1234.5), "1.234,50");
+    }
+
+    #[test]
+    fn test_complex_numbers() {
+        assert_eq!(Number::new(1.0, -2.0).to_string(), "1 - 2i");
+        assert_eq!(Number::imaginary_unit().to_string(), "i");
+        assert_eq!(Number::new(0.0, -0.5).to_string(), "-0.5i");
+        let format = NumberFormat::new(Notation::Fraction);
+        assert_eq!(Number::new(0.5, 0.25).format(&format), "1/2 + (1/4)i");
+        assert_eq!(Number::new(0.0, -2.0 / 3.0).format(&format), "-(2/3)i");
+        assert_eq!(Number::new(1.0, 3.0).format(&format), "1 + 3i");
+        let mixed = NumberFormat::new(Notation::Mixed);
+        assert_eq!(Number::new(0.0, 5.0 / 3.0).format(&mixed), "(1 2/3)i");
+    }
+}
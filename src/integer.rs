@@ -0,0 +1,361 @@
+use std::ops::RangeInclusive;
+
+use crate::parser::{Function, Node};
+use crate::{Number, NumberError};
+
+/// Integers up to this size are exact in an f64
+const MAX_EXACT: f64 = 9007199254740992.0;
+
+/// The most terms a sum or product is unrolled into
+const MAX_TERMS: i64 = 1_000_000;
+
+/// Reads an argument that must be a whole number
+pub(crate) fn integer(number: &Number) -> Result<i64, NumberError> {
+    let value = number.value();
+    if !number.is_real() || value.fract() != 0.0 {
+        return Err(NumberError::Domain("argument must be an integer"));
+    }
+    if value.abs() > MAX_EXACT {
+        return Err(NumberError::Overflow);
+    }
+    Ok(value as i64)
+}
+
+fn real(number: &Number) -> Result<f64, NumberError> {
+    if !number.is_real() {
+        return Err(NumberError::Domain("argument must be real"));
+    }
+    Ok(number.value())
+}
+
+/// Converts an exact result, falling back to the float when it doesn't fit
+fn result(value: Option<u128>, approximation: impl FnOnce() -> f64) -> Result<Number, NumberError> {
+    let value = value.map_or_else(approximation, |v| v as f64);
+    Number::real(value).checked()
+}
+
+/// Evaluates an integer function, the arguments have already been checked against its arity
+pub(crate) fn evaluate(function: Function, arguments: &[Number]) -> Result<Number, NumberError> {
+    match function {
+        Function::Factorial => factorial(integer(&arguments[0])?),
+        Function::Gcd => {
+            let value = gcd(integer(&arguments[0])?, integer(&arguments[1])?);
+            Ok(Number::real(value as f64))
+        }
+        Function::Lcm => {
+            let (a, b) = (integer(&arguments[0])?, integer(&arguments[1])?);
+            if a == 0 || b == 0 {
+                return Ok(Number::real(0.0));
+            }
+            let (a, b) = (a.unsigned_abs() as u128, b.unsigned_abs() as u128);
+            let divisor = gcd(a as i64, b as i64) as u128;
+            Number::real((a / divisor * b) as f64).checked()
+        }
+        Function::Mod => {
+            let (a, n) = (real(&arguments[0])?, real(&arguments[1])?);
+            if n == 0.0 {
+                return Err(NumberError::DivisionByZero);
+            }
+            // The remainder takes the sign of n, like a - n * floor(a / n)
+            let remainder = a.rem_euclid(n.abs());
+            let remainder = if n < 0.0 && remainder != 0.0 {
+                remainder + n
+            } else {
+                remainder
+            };
+            Number::real(remainder).checked()
+        }
+        Function::Binomial => binomial(integer(&arguments[0])?, integer(&arguments[1])?),
+        Function::Floor => Ok(Number::real(real(&arguments[0])?.floor())),
+        Function::Ceil => Ok(Number::real(real(&arguments[0])?.ceil())),
+        Function::IsPrime => {
+            let n = integer(&arguments[0])?;
+            let prime = n >= 0 && is_prime(n as u64);
+            Ok(Number::real(if prime { 1.0 } else { 0.0 }))
+        }
+        Function::NextPrime => {
+            let mut n = integer(&arguments[0])?.max(1) as u64 + 1;
+            while !is_prime(n) {
+                n += 1;
+            }
+            Number::real(n as f64).checked()
+        }
+        // The index has no value outside of the summand, see `terms`
+        Function::Sum | Function::Product => Err(NumberError::Domain(
+            "the index of a sum or product is unbound",
+        )),
+    }
+}
+
+/// The indices of `sum(f, k, a, b)` or `product(f, k, a, b)`, and whether the range is reversed
+///
+/// A range with `b < a - 1` runs from `b + 1` to `a - 1` instead, and the sum over it is negated
+/// and the product inverted. Every closed form is `F(b + 1) - F(a)` or `G(b + 1)/G(a)`, so this
+/// keeps them equal to the sum for any bounds, and `sum(k, k, 4, 1)` is `-(2 + 3)`, while `a = b + 1`
+/// is an empty range.
+pub(crate) fn range(a: i64, b: i64) -> Result<(RangeInclusive<i64>, bool), NumberError> {
+    let (range, reversed) = if b < a.saturating_sub(1) {
+        (b + 1..=a - 1, true)
+    } else {
+        (a..=b, false)
+    };
+    if range.end().saturating_sub(*range.start()) >= MAX_TERMS {
+        return Err(NumberError::Domain("too many terms"));
+    }
+    Ok((range, reversed))
+}
+
+/// The terms of `sum(f, k, a, b)` or `product(f, k, a, b)` from the evaluated bounds `a` and `b`,
+/// which is `f` with every integer of the [`range`] written in for `k`, so the evaluators that
+/// don't bind variables can unroll it like the complex one, and whether the range is reversed
+pub(crate) fn terms(
+    arguments: &[Node],
+    a: f64,
+    b: f64,
+) -> Result<(impl Iterator<Item = Node> + '_, bool), NumberError> {
+    let [summand, Node::Variable(index), ..] = arguments else {
+        return Err(NumberError::Domain("the index must be a variable"));
+    };
+    let (range, reversed) = range(integer(&Number::real(a))?, integer(&Number::real(b))?)?;
+    let terms =
+        range.map(move |k| substitute(summand, index, &Node::Number(Number::real(k as f64))));
+    Ok((terms, reversed))
+}
+
+/// Replaces the variable `name` with `value`, except inside a sum or product that binds its own
+/// index of that name
+fn substitute(node: &Node, name: &str, value: &Node) -> Node {
+    let s = |n: &Node| Box::new(substitute(n, name, value));
+    match node {
+        Node::Variable(variable) if variable == name => value.clone(),
+        Node::Add(l, r) => Node::Add(s(l), s(r)),
+        Node::Sub(l, r) => Node::Sub(s(l), s(r)),
+        Node::Mul(l, r) => Node::Mul(s(l), s(r)),
+        Node::Div(l, r) => Node::Div(s(l), s(r)),
+        Node::Pow(l, r) => Node::Pow(s(l), s(r)),
+        Node::Exp(n) => Node::Exp(s(n)),
+        Node::Log(n) => Node::Log(s(n)),
+        Node::Sin(n) => Node::Sin(s(n)),
+        Node::Cos(n) => Node::Cos(s(n)),
+        Node::Tan(n) => Node::Tan(s(n)),
+        Node::Sqrt(n) => Node::Sqrt(s(n)),
+        Node::Neg(n) => Node::Neg(s(n)),
+        Node::Function(function, arguments) => {
+            let shadowed =
+                function.binds_index() && arguments[1] == Node::Variable(name.to_string());
+            let arguments = arguments
+                .iter()
+                .enumerate()
+                .map(|(i, a)| {
+                    if shadowed && i < 2 {
+                        a.clone()
+                    } else {
+                        substitute(a, name, value)
+                    }
+                })
+                .collect();
+            Node::Function(*function, arguments)
+        }
+        Node::Number(_)
+        | Node::Variable(_)
+        | Node::PiConstant
+        | Node::EConstant
+        | Node::IConstant => node.clone(),
+    }
+}
+
+/// [`evaluate`] for the evaluators that work on plain floats
+pub(crate) fn evaluate_real(function: Function, arguments: &[f64]) -> Result<f64, NumberError> {
+    let arguments: Vec<Number> = arguments.iter().map(|a| Number::real(*a)).collect();
+    Ok(evaluate(function, &arguments)?.value())
+}
+
+pub(crate) fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a as i64
+}
+
+fn factorial(n: i64) -> Result<Number, NumberError> {
+    if n < 0 {
+        return Err(NumberError::Domain("factorial of a negative number"));
+    }
+    // 171! is larger than the largest f64
+    if n > 170 {
+        return Err(NumberError::Overflow);
+    }
+    let exact = (1..=n as u128).try_fold(1u128, |acc, k| acc.checked_mul(k));
+    result(exact, || (1..=n).map(|k| k as f64).product())
+}
+
+/// `n` choose `k`, extended to negative `n` like Mathematica's `Binomial` with
+/// `binomial(n, k) = (-1)^k binomial(k - n - 1, k)` for `k >= 0` and
+/// `binomial(n, k) = (-1)^(n - k) binomial(-k - 1, n - k)` for `k <= n`, so `binomial(n, n)` is
+/// always 1
+fn binomial(n: i64, k: i64) -> Result<Number, NumberError> {
+    let signed = |value: Number, exponent: i64| {
+        if exponent % 2 == 0 {
+            value
+        } else {
+            Number::neg(&value)
+        }
+    };
+    if n < 0 && k >= 0 {
+        return Ok(signed(binomial(k - n - 1, k)?, k));
+    }
+    if n < 0 && k <= n {
+        return Ok(signed(binomial(-k - 1, n - k)?, n - k));
+    }
+    if k < 0 || k > n {
+        return Ok(Number::real(0.0));
+    }
+
+    let k = k.min(n - k) as u128;
+    let n = n as u128;
+    // binomial(n, k) >= 2^k for k <= n/2
+    if k > 1024 {
+        return Err(NumberError::Overflow);
+    }
+    // Every partial product is itself a binomial coefficient, so the division is exact
+    let exact = (1..=k).try_fold(1u128, |acc, i| Some(acc.checked_mul(n - k + i)? / i));
+    result(exact, || {
+        (1..=k)
+            .fold(1.0, |acc, i| acc * (n - k + i) as f64 / i as f64)
+            .round()
+    })
+}
+
+fn mul_mod(a: u64, b: u64, n: u64) -> u64 {
+    (a as u128 * b as u128 % n as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exponent: u64, n: u64) -> u64 {
+    let mut result = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, n);
+        }
+        base = mul_mod(base, base, n);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Deterministic Miller-Rabin, these bases are enough for every 64 bit number
+pub(crate) fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    for p in BASES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+
+    let (mut d, mut s) = (n - 1, 0);
+    while d % 2 == 0 {
+        d /= 2;
+        s += 1;
+    }
+    BASES.iter().all(|&a| {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(function: Function, arguments: &[f64]) -> Result<f64, NumberError> {
+        evaluate_real(function, arguments)
+    }
+
+    #[test]
+    fn test_exact_values() {
+        assert_eq!(eval(Function::Factorial, &[0.0]), Ok(1.0));
+        assert_eq!(
+            eval(Function::Factorial, &[20.0]),
+            Ok(2432902008176640000.0)
+        );
+        assert_eq!(eval(Function::Gcd, &[-12.0, 18.0]), Ok(6.0));
+        assert_eq!(eval(Function::Lcm, &[4.0, 6.0]), Ok(12.0));
+        assert_eq!(eval(Function::Binomial, &[52.0, 5.0]), Ok(2598960.0));
+        assert_eq!(eval(Function::Binomial, &[-3.0, 2.0]), Ok(6.0));
+        assert_eq!(eval(Function::Binomial, &[3.0, 5.0]), Ok(0.0));
+        assert_eq!(eval(Function::Binomial, &[-3.0, -3.0]), Ok(1.0));
+        assert_eq!(eval(Function::Binomial, &[-3.0, -5.0]), Ok(6.0));
+        assert_eq!(eval(Function::Binomial, &[-3.0, -4.0]), Ok(-3.0));
+        assert_eq!(eval(Function::Binomial, &[-3.0, -2.0]), Ok(0.0));
+        assert_eq!(eval(Function::Binomial, &[3.0, -1.0]), Ok(0.0));
+        assert_eq!(eval(Function::Mod, &[-7.0, 3.0]), Ok(2.0));
+        assert_eq!(eval(Function::Mod, &[7.0, -3.0]), Ok(-2.0));
+        assert_eq!(eval(Function::Floor, &[-2.5]), Ok(-3.0));
+        assert_eq!(eval(Function::Ceil, &[2.1]), Ok(3.0));
+    }
+
+    #[test]
+    fn test_primes() {
+        assert_eq!(eval(Function::IsPrime, &[97.0]), Ok(1.0));
+        assert_eq!(eval(Function::IsPrime, &[1.0]), Ok(0.0));
+        assert_eq!(eval(Function::IsPrime, &[561.0]), Ok(0.0));
+        assert_eq!(eval(Function::NextPrime, &[13.0]), Ok(17.0));
+        assert_eq!(eval(Function::NextPrime, &[-5.0]), Ok(2.0));
+        assert!(is_prime(18446744073709551557));
+    }
+
+    #[test]
+    fn test_terms() {
+        let parsed =
+            |input: &str| crate::parser::parse(&crate::lexer::tokenize(input).unwrap()).unwrap();
+        let Node::Function(_, arguments) = parsed("sum(k*sum(k, k, 1, k), k, 1, 2)") else {
+            unreachable!()
+        };
+        let (unrolled, reversed) = terms(&arguments, 1.0, 2.0).unwrap();
+        assert_eq!(
+            unrolled.collect::<Vec<_>>(),
+            [parsed("1*sum(k, k, 1, 1)"), parsed("2*sum(k, k, 1, 2)")]
+        );
+        assert!(!reversed);
+        let (empty, reversed) = range(1, 0).unwrap();
+        assert!(empty.is_empty() && !reversed);
+        assert_eq!(range(4, 1), Ok((2..=3, true)));
+        assert!(terms(&arguments, 1.5, 2.0).is_err());
+        assert_eq!(
+            terms(&arguments, 0.0, 1e6).err(),
+            Some(NumberError::Domain("too many terms"))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            eval(Function::Factorial, &[-1.0]),
+            Err(NumberError::Domain(_))
+        ));
+        assert!(matches!(
+            eval(Function::Gcd, &[1.5, 3.0]),
+            Err(NumberError::Domain(_))
+        ));
+        assert_eq!(
+            eval(Function::Factorial, &[200.0]),
+            Err(NumberError::Overflow)
+        );
+        assert_eq!(
+            eval(Function::Mod, &[1.0, 0.0]),
+            Err(NumberError::DivisionByZero)
+        );
+    }
+}
@@ -1,5 +1,8 @@
+use std::str::FromStr;
+
 use thiserror::Error;
 
+use crate::parser::Function;
 use crate::Number;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -19,20 +22,28 @@ pub enum LexerToken {
     CosFunction,
     TanFunction,
     SqrtFunction,
+    Function(Function),
+    Comma,
+    Factorial,
+    /// The `'` in derivative notation like `y''`
+    Prime,
+    /// The `=` between the two sides of an equation
+    Equals,
     PiConstant,
     EConstant,
+    IConstant,
 }
 
 impl LexerToken {
     pub fn is_operator(&self) -> bool {
-        match self {
+        matches!(
+            self,
             LexerToken::AddOperator
-            | LexerToken::SubOperator
-            | LexerToken::MulOperator
-            | LexerToken::DivOperator
-            | LexerToken::PowOperator => true,
-            _ => false,
-        }
+                | LexerToken::SubOperator
+                | LexerToken::MulOperator
+                | LexerToken::DivOperator
+                | LexerToken::PowOperator
+        )
     }
 }
 
@@ -40,44 +51,103 @@ impl LexerToken {
 pub enum LexerError {
     #[error("Invalid character: {0}")]
     InvalidCharacter(char),
-    #[error("Invalid character: {0}")]
+    #[error("Invalid number: {0}")]
     InvalidNumber(String),
 }
 
-#[derive(Debug, PartialEq)]
-enum ParsingType {
-    Number,
-    Variable,
+fn add_current(tokens: &mut Vec<LexerToken>, current_parsing: &mut String) {
+    if current_parsing.is_empty() {
+        return;
+    }
+
+    if let Some(token) = variable_to_token(current_parsing) {
+        tokens.push(token);
+    } else {
+        tokens.push(LexerToken::Variable(current_parsing.clone()));
+    }
+    current_parsing.clear();
 }
 
-fn add_current(
-    tokens: &mut Vec<LexerToken>,
-    current_parsing: &mut String,
-    current_parsing_type: &mut Option<ParsingType>,
-) -> Result<(), LexerError> {
-    if current_parsing.is_empty() {
-        *current_parsing_type = None;
-        return Ok(());
+/// Removes `_` digit separators, which are only allowed between two digits
+fn without_separators(literal: &str, is_digit: fn(char) -> bool) -> Option<String> {
+    let chars: Vec<char> = literal.chars().collect();
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_'
+            && !(i > 0 && is_digit(chars[i - 1]) && chars.get(i + 1).is_some_and(|c| is_digit(*c)))
+        {
+            return None;
+        }
     }
+    Some(literal.replace('_', ""))
+}
 
-    if *current_parsing_type == Some(ParsingType::Variable) {
-        if let Some(token) = variable_to_token(&current_parsing) {
-            tokens.push(token);
-        } else {
-            tokens.push(LexerToken::Variable(current_parsing.clone()));
+fn parse_number(literal: &str) -> Option<Number> {
+    let radix = match literal.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0o" | "0O") => 8,
+        Some("0b" | "0B") => 2,
+        _ => {
+            let digits = without_separators(literal, |c| c.is_ascii_digit())?;
+            return Number::from_str(&digits).ok();
+        }
+    };
+
+    let digits = without_separators(&literal[2..], |c| c.is_ascii_hexdigit())?;
+    let value = u128::from_str_radix(&digits, radix).ok()?;
+    let number = Number::real(value as f64).checked().ok()?;
+    Some(number.with_literal(&value.to_string()))
+}
+
+/// Reads the numeric literal that starts at `start` and returns it with the index after it.
+///
+/// Besides plain decimals like `1.5` and `.5` this accepts scientific notation (`2.5E+10`),
+/// hex, octal and binary literals (`0xff`, `0o17`, `0b101`) and `_` digit separators.
+fn read_number(chars: &[char], start: usize) -> Result<(Number, usize), LexerError> {
+    let is_prefixed = chars[start] == '0'
+        && chars
+            .get(start + 1)
+            .is_some_and(|c| matches!(c, 'x' | 'X' | 'o' | 'O' | 'b' | 'B'));
+
+    let mut end = start;
+    if is_prefixed {
+        end += 2;
+        while chars
+            .get(end)
+            .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+        {
+            end += 1;
+        }
+    } else {
+        while chars
+            .get(end)
+            .is_some_and(|c| c.is_ascii_digit() || *c == '.' || *c == '_')
+        {
+            end += 1;
+        }
+
+        // An exponent needs digits after it, otherwise `2e` is 2 followed by the constant e
+        if chars.get(end).is_some_and(|c| matches!(c, 'e' | 'E')) {
+            let mut digits = end + 1;
+            if chars.get(digits).is_some_and(|c| matches!(c, '+' | '-')) {
+                digits += 1;
+            }
+            if chars.get(digits).is_some_and(|c| c.is_ascii_digit()) {
+                end = digits;
+                while chars
+                    .get(end)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '_')
+                {
+                    end += 1;
+                }
+            }
         }
-        current_parsing.clear();
-        *current_parsing_type = None;
-    } else if *current_parsing_type == Some(ParsingType::Number) {
-        tokens.push(LexerToken::Number(
-            Number::from_str(&current_parsing)
-                .map_err(|_| LexerError::InvalidNumber(current_parsing.clone()))?,
-        ));
-        current_parsing.clear();
-        *current_parsing_type = None;
-    }
-
-    return Ok(());
+    }
+
+    let literal: String = chars[start..end].iter().collect();
+    match parse_number(&literal) {
+        Some(number) => Ok((number, end)),
+        None => Err(LexerError::InvalidNumber(literal)),
+    }
 }
 
 fn variable_to_token(s: &str) -> Option<LexerToken> {
@@ -89,7 +159,8 @@ fn variable_to_token(s: &str) -> Option<LexerToken> {
         "sqrt" => Some(LexerToken::SqrtFunction),
         "pi" => Some(LexerToken::PiConstant),
         "e" => Some(LexerToken::EConstant),
-        _ => None,
+        "i" => Some(LexerToken::IConstant),
+        _ => Function::from_name(s).map(LexerToken::Function),
     }
 }
 
@@ -102,6 +173,10 @@ fn operator_to_token(c: char) -> Option<LexerToken> {
         '^' => Some(LexerToken::PowOperator),
         '(' => Some(LexerToken::LeftParenthesis),
         ')' => Some(LexerToken::RightParenthesis),
+        ',' => Some(LexerToken::Comma),
+        '!' => Some(LexerToken::Factorial),
+        '\'' => Some(LexerToken::Prime),
+        '=' => Some(LexerToken::Equals),
         _ => None,
     }
 }
@@ -109,47 +184,35 @@ fn operator_to_token(c: char) -> Option<LexerToken> {
 /// Tokenizes input string into a vector of LexerToken
 pub fn tokenize(input: &str) -> Result<Vec<LexerToken>, LexerError> {
     let mut tokens = vec![];
-
-    let mut current_parsing_type: Option<ParsingType> = None;
     let mut current_parsing = String::new();
 
-    for char in input.chars() {
-        if char.is_whitespace() {
-            add_current(&mut tokens, &mut current_parsing, &mut current_parsing_type)?;
-            continue;
-        }
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let char = chars[i];
 
-        if char.is_alphabetic() && current_parsing_type != Some(ParsingType::Variable) {
-            add_current(&mut tokens, &mut current_parsing, &mut current_parsing_type)?;
-            current_parsing_type = Some(ParsingType::Variable);
-        } else if (char.is_numeric() || char == '.')
-            && current_parsing_type != Some(ParsingType::Number)
-        {
-            add_current(&mut tokens, &mut current_parsing, &mut current_parsing_type)?;
-            current_parsing_type = Some(ParsingType::Number);
-        } else if operator_to_token(char).is_some() {
-            add_current(&mut tokens, &mut current_parsing, &mut current_parsing_type)?;
-        }
-
-        if current_parsing_type == Some(ParsingType::Variable) {
+        if char.is_alphabetic() {
             current_parsing.push(char);
+            i += 1;
             continue;
         }
+        add_current(&mut tokens, &mut current_parsing);
 
-        if current_parsing_type == Some(ParsingType::Number) {
-            current_parsing.push(char);
+        if char.is_ascii_digit() || char == '.' {
+            let (number, end) = read_number(&chars, i)?;
+            tokens.push(LexerToken::Number(number));
+            i = end;
             continue;
         }
 
         if let Some(token) = operator_to_token(char) {
-            add_current(&mut tokens, &mut current_parsing, &mut current_parsing_type)?;
             tokens.push(token);
-            continue;
-        } else {
+        } else if !char.is_whitespace() {
             return Err(LexerError::InvalidCharacter(char));
         }
+        i += 1;
     }
-    add_current(&mut tokens, &mut current_parsing, &mut current_parsing_type)?;
+    add_current(&mut tokens, &mut current_parsing);
 
     Ok(tokens)
 }
@@ -229,4 +292,103 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_tokenize_imaginary_unit() {
+        let input = "2*i";
+        let tokens = tokenize(input).unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                LexerToken::Number(Number::from_str("2").unwrap()),
+                LexerToken::MulOperator,
+                LexerToken::IConstant,
+            ]
+        );
+    }
+
+    fn number(value: f64) -> LexerToken {
+        LexerToken::Number(Number::real(value))
+    }
+
+    #[test]
+    fn test_tokenize_number_literals() {
+        let literals = [
+            ("1e-3", 1e-3),
+            ("2.5E+10", 2.5e10),
+            ("6.02e23", 6.02e23),
+            (".5", 0.5),
+            ("1_000_000", 1e6),
+            ("0xff", 255.0),
+            ("0XFF", 255.0),
+            ("0o17", 15.0),
+            ("0b1010_1010", 170.0),
+        ];
+        for (input, value) in literals {
+            assert_eq!(tokenize(input).unwrap(), vec![number(value)], "{}", input);
+        }
+
+        assert_eq!(
+            tokenize("2e + 1e-3").unwrap(),
+            vec![
+                number(2.0),
+                LexerToken::EConstant,
+                LexerToken::AddOperator,
+                number(1e-3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_number_literals() {
+        for input in [
+            "1.2.3", "1__000", "1_", "1_.5", "0x", "0b102", "0xfg", "1e400",
+        ] {
+            match tokenize(input) {
+                Err(LexerError::InvalidNumber(literal)) => assert_eq!(literal, input),
+                result => panic!("{} lexed as {:?}", input, result),
+            }
+        }
+        assert_eq!(
+            LexerError::InvalidNumber("1.2.3".to_string()).to_string(),
+            "Invalid number: 1.2.3"
+        );
+    }
+
+    #[test]
+    fn test_tokenize_derivatives_and_equations() {
+        let tokens = tokenize("y'' = -y").unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                LexerToken::Variable("y".to_string()),
+                LexerToken::Prime,
+                LexerToken::Prime,
+                LexerToken::Equals,
+                LexerToken::SubOperator,
+                LexerToken::Variable("y".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_integer_functions() {
+        let input = "gcd(n!, 4)";
+        let tokens = tokenize(input).unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                LexerToken::Function(Function::Gcd),
+                LexerToken::LeftParenthesis,
+                LexerToken::Variable("n".to_string()),
+                LexerToken::Factorial,
+                LexerToken::Comma,
+                number(4.0),
+                LexerToken::RightParenthesis,
+            ]
+        );
+    }
 }
@@ -1,5 +1,14 @@
+pub mod calculus;
+pub mod eval;
 pub mod lexer;
 pub mod parser;
+pub mod simplify;
+pub mod units;
 
+mod decimal;
+mod format;
+mod integer;
 mod number;
-pub(crate) use number::Number;
+pub use decimal::{Decimal, DecimalMode, RoundingMode};
+pub use format::{Notation, NumberFormat};
+pub use number::{Number, NumberError};
@@ -1,49 +1,365 @@
+use std::fmt;
+use std::str::FromStr;
+
 use thiserror::Error;
 
-#[derive(Debug, PartialEq, Clone)]
+use crate::NumberFormat;
+
+/// A complex number, numbers parsed from the input are always real
+#[derive(Debug, Clone)]
 pub struct Number {
-    value: f64,
+    re: f64,
+    im: f64,
+    /// The exact decimal a parsed number was written as, which may have more digits than fit in
+    /// the f64
+    literal: Option<String>,
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.re == other.re && self.im == other.im
+    }
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, PartialEq)]
 pub enum NumberError {
     #[error("Parsing error")]
     ParsingError,
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Overflow")]
+    Overflow,
+    #[error("Domain error: {0}")]
+    Domain(&'static str),
+    #[error("Result is not a number")]
+    NaN,
 }
 
-impl Number {
-    pub fn from_str(input: &str) -> Result<Self, NumberError> {
+impl FromStr for Number {
+    type Err = NumberError;
+
+    fn from_str(input: &str) -> Result<Self, NumberError> {
         let value = input.parse().map_err(|_| NumberError::ParsingError)?;
-        Ok(Number { value })
+        let number = Number::real(value).checked()?;
+        Ok(number.with_literal(input.trim_start_matches('+')))
+    }
+}
+
+impl fmt::Display for Number {
+    /// Writes the number with up to 15 significant digits, see [`NumberFormat`] for other formats
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(&NumberFormat::default()))
     }
+}
 
-    pub fn add(right: &Number, left: &Number) -> Number {
+impl Number {
+    /// Creates a complex number, negative zeros are normalized away so a negated real number
+    /// doesn't end up on the other side of a branch cut
+    pub fn new(re: f64, im: f64) -> Number {
         Number {
-            value: left.value + right.value,
+            re: re + 0.0,
+            im: im + 0.0,
+            literal: None,
         }
     }
 
-    pub fn sub(right: &Number, left: &Number) -> Number {
+    pub fn real(value: f64) -> Number {
+        Number::new(value, 0.0)
+    }
+
+    pub fn imaginary_unit() -> Number {
+        Number::new(0.0, 1.0)
+    }
+
+    /// The same number, remembering that it was written as the decimal `literal`
+    pub fn with_literal(self, literal: &str) -> Number {
         Number {
-            value: left.value - right.value,
+            literal: Some(literal.to_string()),
+            ..self
         }
     }
 
-    pub fn mul(right: &Number, left: &Number) -> Number {
-        Number {
-            value: left.value * right.value,
+    /// The decimal the number was written as, if it was parsed from one
+    pub fn literal(&self) -> Option<&str> {
+        self.literal.as_deref()
+    }
+
+    /// The real part of the number
+    pub fn value(&self) -> f64 {
+        self.re
+    }
+
+    pub fn re(&self) -> f64 {
+        self.re
+    }
+
+    pub fn im(&self) -> f64 {
+        self.im
+    }
+
+    pub fn is_real(&self) -> bool {
+        self.im == 0.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.re == 0.0 && self.im == 0.0
+    }
+
+    /// The absolute value |z|
+    pub fn abs(&self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The principal argument of the number, in (-pi, pi]
+    pub fn arg(&self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    /// Turns an infinite result into [`NumberError::Overflow`] and a NaN into [`NumberError::NaN`]
+    pub fn checked(self) -> Result<Number, NumberError> {
+        if self.re.is_nan() || self.im.is_nan() {
+            return Err(NumberError::NaN);
         }
+        if self.re.is_infinite() || self.im.is_infinite() {
+            return Err(NumberError::Overflow);
+        }
+        Ok(self)
     }
 
-    pub fn div(right: &Number, left: &Number) -> Number {
-        Number {
-            value: left.value / right.value,
+    pub fn checked_add(left: &Number, right: &Number) -> Result<Number, NumberError> {
+        Number::add(left, right).checked()
+    }
+
+    pub fn checked_sub(left: &Number, right: &Number) -> Result<Number, NumberError> {
+        Number::sub(left, right).checked()
+    }
+
+    pub fn checked_mul(left: &Number, right: &Number) -> Result<Number, NumberError> {
+        Number::mul(left, right).checked()
+    }
+
+    pub fn checked_div(left: &Number, right: &Number) -> Result<Number, NumberError> {
+        if right.is_zero() {
+            return Err(NumberError::DivisionByZero);
         }
+        Number::div(left, right).checked()
     }
 
-    pub fn pow(right: &Number, left: &Number) -> Number {
-        Number {
-            value: f64::powf(left.value, right.value),
+    /// `left^right` over the real numbers, a negative base with a fractional exponent is a domain
+    /// error rather than a complex principal value
+    pub fn checked_pow(left: &Number, right: &Number) -> Result<Number, NumberError> {
+        if left.is_zero() && (right.re < 0.0 || !right.is_real()) {
+            return Err(NumberError::Domain("zero to a negative power"));
+        }
+        if left.is_real() && left.re < 0.0 && right.is_real() && right.re.fract() != 0.0 {
+            return Err(NumberError::Domain(
+                "negative base with a fractional exponent",
+            ));
+        }
+        Number::pow(left, right).checked()
+    }
+
+    pub fn checked_ln(number: &Number) -> Result<Number, NumberError> {
+        if number.is_zero() {
+            return Err(NumberError::Domain("ln of zero"));
+        }
+        Number::ln(number).checked()
+    }
+
+    pub fn add(left: &Number, right: &Number) -> Number {
+        Number::new(left.re + right.re, left.im + right.im)
+    }
+
+    pub fn sub(left: &Number, right: &Number) -> Number {
+        Number::new(left.re - right.re, left.im - right.im)
+    }
+
+    pub fn mul(left: &Number, right: &Number) -> Number {
+        if left.is_real() && right.is_real() {
+            return Number::real(left.re * right.re);
+        }
+        Number::new(
+            left.re * right.re - left.im * right.im,
+            left.re * right.im + left.im * right.re,
+        )
+    }
+
+    pub fn div(left: &Number, right: &Number) -> Number {
+        if left.is_real() && right.is_real() {
+            return Number::real(left.re / right.re);
+        }
+        let denominator = right.re * right.re + right.im * right.im;
+        Number::new(
+            (left.re * right.re + left.im * right.im) / denominator,
+            (left.im * right.re - left.re * right.im) / denominator,
+        )
+    }
+
+    /// Principal value of `left^right`, defined as `exp(right * ln(left))`
+    ///
+    /// Real powers of non-negative bases and integer powers of real bases stay real.
+    pub fn pow(left: &Number, right: &Number) -> Number {
+        if left.is_real() && right.is_real() && (left.re >= 0.0 || right.re.fract() == 0.0) {
+            return Number::real(f64::powf(left.re, right.re));
+        }
+        if left.is_zero() {
+            return Number::real(0.0);
+        }
+        Number::exp(&Number::mul(right, &Number::ln(left)))
+    }
+
+    pub fn neg(number: &Number) -> Number {
+        Number::new(-number.re, -number.im)
+    }
+
+    pub fn exp(number: &Number) -> Number {
+        if number.is_real() {
+            return Number::real(number.re.exp());
+        }
+        let magnitude = number.re.exp();
+        Number::new(magnitude * number.im.cos(), magnitude * number.im.sin())
+    }
+
+    /// Principal natural logarithm `ln|z| + i*arg(z)`, with the branch cut along the negative real axis
+    pub fn ln(number: &Number) -> Number {
+        if number.is_real() && number.re >= 0.0 {
+            return Number::real(number.re.ln());
+        }
+        Number::new(number.abs().ln(), number.arg())
+    }
+
+    /// Principal square root, the result always has a non-negative real part
+    pub fn sqrt(number: &Number) -> Number {
+        if number.is_real() && number.re >= 0.0 {
+            return Number::real(number.re.sqrt());
+        }
+        let abs = number.abs();
+        let re = ((abs + number.re) / 2.0).sqrt();
+        let im = ((abs - number.re) / 2.0).sqrt();
+        Number::new(re, if number.im < 0.0 { -im } else { im })
+    }
+
+    pub fn sin(number: &Number) -> Number {
+        if number.is_real() {
+            return Number::real(number.re.sin());
+        }
+        Number::new(
+            number.re.sin() * number.im.cosh(),
+            number.re.cos() * number.im.sinh(),
+        )
+    }
+
+    pub fn cos(number: &Number) -> Number {
+        if number.is_real() {
+            return Number::real(number.re.cos());
+        }
+        Number::new(
+            number.re.cos() * number.im.cosh(),
+            -number.re.sin() * number.im.sinh(),
+        )
+    }
+
+    pub fn tan(number: &Number) -> Number {
+        if number.is_real() {
+            return Number::real(number.re.tan());
         }
+        Number::div(&Number::sin(number), &Number::cos(number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Number, re: f64, im: f64) {
+        assert!(
+            (actual.re - re).abs() < 1e-12 && (actual.im - im).abs() < 1e-12,
+            "{:?} != {} + {}i",
+            actual,
+            re,
+            im
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = Number::new(1.0, 2.0);
+        let b = Number::new(3.0, -1.0);
+
+        assert_close(Number::add(&a, &b), 4.0, 1.0);
+        assert_close(Number::sub(&a, &b), -2.0, 3.0);
+        assert_close(Number::mul(&a, &b), 5.0, 5.0);
+        assert_close(Number::div(&a, &b), 0.1, 0.7);
+    }
+
+    #[test]
+    fn test_principal_branches() {
+        let minus_one = Number::real(-1.0);
+
+        assert_close(Number::sqrt(&minus_one), 0.0, 1.0);
+        assert_close(Number::sqrt(&Number::new(-4.0, -1e-300)), 0.0, -2.0);
+        assert_close(
+            Number::ln(&Number::real(-2.0)),
+            2f64.ln(),
+            std::f64::consts::PI,
+        );
+        assert_close(
+            Number::pow(&Number::real(-8.0), &Number::real(1.0 / 3.0)),
+            1.0,
+            3f64.sqrt(),
+        );
+        assert_close(
+            Number::pow(&Number::real(-2.0), &Number::real(3.0)),
+            -8.0,
+            0.0,
+        );
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let zero = Number::real(0.0);
+        let one = Number::real(1.0);
+
+        assert_eq!(
+            Number::checked_div(&one, &zero),
+            Err(NumberError::DivisionByZero)
+        );
+        assert_eq!(
+            Number::checked_mul(&Number::real(1e200), &Number::real(1e200)),
+            Err(NumberError::Overflow)
+        );
+        assert!(matches!(
+            Number::checked_pow(&zero, &Number::real(-1.0)),
+            Err(NumberError::Domain(_))
+        ));
+        assert!(matches!(
+            Number::checked_pow(&Number::real(-8.0), &Number::real(0.5)),
+            Err(NumberError::Domain(_))
+        ));
+        assert_eq!(
+            Number::checked_sub(&Number::real(f64::INFINITY), &Number::real(f64::INFINITY)),
+            Err(NumberError::NaN)
+        );
+        assert_eq!(Number::from_str("1e400"), Err(NumberError::Overflow));
+        assert_eq!(
+            Number::checked_pow(&Number::real(-2.0), &Number::real(3.0)),
+            Ok(Number::real(-8.0))
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let i = Number::imaginary_unit();
+        let pi = Number::real(std::f64::consts::PI);
+
+        assert_close(Number::exp(&Number::mul(&i, &pi)), -1.0, 0.0);
+        assert_close(
+            Number::pow(&i, &i),
+            (-std::f64::consts::FRAC_PI_2).exp(),
+            0.0,
+        );
+        assert_close(Number::sin(&i), 0.0, 1f64.sinh());
+        assert_close(Number::cos(&i), 1f64.cosh(), 0.0);
+        assert_close(Number::tan(&i), 0.0, 1f64.tanh());
     }
 }
@@ -0,0 +1,62 @@
+use super::{LexerToken, NodeToken, ParserError};
+
+/// Parses derivative notation like `y''`, which becomes the variable `y''`
+///
+/// Only variables can be primed, so `(y)'` or `2'` is an error. This expects that parentheses have
+/// already been parsed.
+pub(super) fn parse(node_tokens: &[NodeToken]) -> Result<Vec<NodeToken>, ParserError> {
+    let mut output: Vec<NodeToken> = vec![];
+
+    for node_token in node_tokens {
+        if *node_token != NodeToken::Token(LexerToken::Prime) {
+            output.push(node_token.clone());
+            continue;
+        }
+
+        match output.last_mut() {
+            Some(NodeToken::Token(LexerToken::Variable(name))) => name.push('\''),
+            _ => return Err(ParserError::InvalidExpression),
+        }
+    }
+
+    Ok(output)
+}
+
+/// Splits a variable name in derivative notation into the function and the order of the
+/// derivative, so `y''` is `("y", 2)` and a plain `y` is `("y", 0)`
+pub fn derivative_order(name: &str) -> (&str, usize) {
+    let function = name.trim_end_matches('\'');
+    (function, name.len() - function.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_primes() {
+        let tokens = vec![
+            NodeToken::Token(LexerToken::Variable("y".to_string())),
+            NodeToken::Token(LexerToken::Prime),
+            NodeToken::Token(LexerToken::Prime),
+            NodeToken::Token(LexerToken::AddOperator),
+            NodeToken::Token(LexerToken::Variable("y".to_string())),
+        ];
+
+        assert_eq!(
+            parse(&tokens).unwrap(),
+            vec![
+                NodeToken::Token(LexerToken::Variable("y''".to_string())),
+                NodeToken::Token(LexerToken::AddOperator),
+                NodeToken::Token(LexerToken::Variable("y".to_string())),
+            ]
+        );
+        assert!(parse(&[NodeToken::Token(LexerToken::Prime)]).is_err());
+    }
+
+    #[test]
+    fn test_derivative_order() {
+        assert_eq!(derivative_order("y''"), ("y", 2));
+        assert_eq!(derivative_order("y"), ("y", 0));
+    }
+}
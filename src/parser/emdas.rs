@@ -4,7 +4,7 @@ use super::{LexerToken, Node, NodeToken, ParserError};
 ///
 /// This expects that parentheses and functions have already been parsed.
 pub(super) fn parse(node_tokens: &[NodeToken]) -> Result<Node, ParserError> {
-    if node_tokens.len() == 0 {
+    if node_tokens.is_empty() {
         return Err(ParserError::BranchEvaluatedToNone);
     }
 
@@ -12,9 +12,13 @@ pub(super) fn parse(node_tokens: &[NodeToken]) -> Result<Node, ParserError> {
         return match &node_tokens[0] {
             NodeToken::Node(node) => Ok(node.clone()),
             NodeToken::Token(LexerToken::Number(number)) => Ok(Node::Number(number.clone())),
+            NodeToken::Token(LexerToken::Variable(name)) => Ok(Node::Variable(name.clone())),
             NodeToken::Token(LexerToken::EConstant) => Ok(Node::EConstant),
             NodeToken::Token(LexerToken::PiConstant) => Ok(Node::PiConstant),
+            NodeToken::Token(LexerToken::IConstant) => Ok(Node::IConstant),
             NodeToken::Token(token) => Err(ParserError::InvalidToken(token.clone())),
+            // A comma outside of a function call
+            NodeToken::Arguments(_) => Err(ParserError::InvalidExpression),
         };
     }
 
@@ -69,15 +73,10 @@ pub(super) fn parse(node_tokens: &[NodeToken]) -> Result<Node, ParserError> {
     }
 
     for i in 1..node_tokens.len() {
-        if let NodeToken::Token(token) = &node_tokens[i] {
-            match token {
-                LexerToken::PowOperator => {
-                    let left = parse(&node_tokens[..i])?;
-                    let right = parse(&node_tokens[i + 1..])?;
-                    return Ok(Node::Pow(Box::new(left), Box::new(right)));
-                }
-                _ => {}
-            }
+        if let NodeToken::Token(LexerToken::PowOperator) = &node_tokens[i] {
+            let left = parse(&node_tokens[..i])?;
+            let right = parse(&node_tokens[i + 1..])?;
+            return Ok(Node::Pow(Box::new(left), Box::new(right)));
         }
     }
 
@@ -88,6 +87,7 @@ pub(super) fn parse(node_tokens: &[NodeToken]) -> Result<Node, ParserError> {
 mod test {
     use super::*;
     use crate::Number;
+    use std::str::FromStr;
 
     fn to_node_tokens(tokens: Vec<LexerToken>) -> Vec<NodeToken> {
         tokens.into_iter().map(NodeToken::Token).collect()
@@ -186,6 +186,25 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_parses_variables() {
+        let tokens = to_node_tokens(vec![
+            LexerToken::Variable("x".to_string()),
+            LexerToken::MulOperator,
+            LexerToken::Variable("y".to_string()),
+        ]);
+
+        let node = parse(&tokens).unwrap();
+
+        assert_eq!(
+            node,
+            Node::Mul(
+                Box::new(Node::Variable("x".to_string())),
+                Box::new(Node::Variable("y".to_string()))
+            )
+        );
+    }
+
     #[test]
     fn test_parses_negation() {
         let tokens = to_node_tokens(vec![
@@ -0,0 +1,99 @@
+use std::fmt;
+
+use super::{parse, LexerToken, Node, ParserError};
+
+/// Two expressions that are equal, like `x^2 + y^2 = 25` or `y' = -k*y`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Equation {
+    lhs: Node,
+    rhs: Node,
+}
+
+impl Equation {
+    pub fn new(lhs: Node, rhs: Node) -> Equation {
+        Equation { lhs, rhs }
+    }
+
+    pub fn lhs(&self) -> &Node {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &Node {
+        &self.rhs
+    }
+}
+
+impl fmt::Display for Equation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}", self.lhs, self.rhs)
+    }
+}
+
+/// Parses the tokens of an equation, which has exactly one `=` outside of parentheses
+pub fn parse_equation(tokens: &[LexerToken]) -> Result<Equation, ParserError> {
+    let mut depth = 0;
+    let mut equals = None;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            LexerToken::LeftParenthesis => depth += 1,
+            LexerToken::RightParenthesis => depth -= 1,
+            LexerToken::Equals if depth == 0 && equals.is_none() => equals = Some(i),
+            LexerToken::Equals => return Err(ParserError::InvalidToken(token.clone())),
+            _ => {}
+        }
+    }
+
+    let Some(i) = equals else {
+        return Err(ParserError::InvalidExpression);
+    };
+    if i == 0 || i == tokens.len() - 1 {
+        return Err(ParserError::BranchEvaluatedToNone);
+    }
+    Ok(Equation::new(
+        parse(&tokens[..i])?,
+        parse(&tokens[i + 1..])?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    fn parsed(input: &str) -> Result<Equation, ParserError> {
+        parse_equation(&tokenize(input).unwrap())
+    }
+
+    #[test]
+    fn test_parse_equation() {
+        let equation = parsed("x^2 + y^2 = 25").unwrap();
+        assert_eq!(
+            equation.lhs(),
+            &parse(&tokenize("x^2 + y^2").unwrap()).unwrap()
+        );
+        assert_eq!(equation.to_string(), "x^2 + y^2 = 25");
+
+        assert_eq!(
+            parsed("y' = -y").unwrap().lhs(),
+            &Node::Variable("y'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_equations() {
+        assert!(matches!(
+            parsed("x + 1"),
+            Err(ParserError::InvalidExpression)
+        ));
+        assert!(matches!(
+            parsed("x = y = 1"),
+            Err(ParserError::InvalidToken(LexerToken::Equals))
+        ));
+        assert!(parsed("(x = 1)").is_err());
+        assert!(matches!(
+            parsed("= 1"),
+            Err(ParserError::BranchEvaluatedToNone)
+        ));
+        assert!(parsed("x =").is_err());
+    }
+}
@@ -24,8 +24,10 @@ pub fn parse(node: Node) -> Node {
         Node::Tan(n) => Node::Tan(Box::new(parse(*n))),
         Node::Sqrt(n) => Node::Sqrt(Box::new(parse(*n))),
         Node::Neg(n) => Node::Neg(Box::new(parse(*n))),
+        Node::Function(f, args) => Node::Function(f, args.into_iter().map(parse).collect()),
         Node::PiConstant => Node::PiConstant,
         Node::EConstant => Node::EConstant,
+        Node::IConstant => Node::IConstant,
         Node::Number(n) => Node::Number(n),
         Node::Variable(v) => Node::Variable(v),
     }
@@ -35,6 +37,7 @@ pub fn parse(node: Node) -> Node {
 mod tests {
     use super::*;
     use crate::Number;
+    use std::str::FromStr;
 
     #[test]
     fn test_parse_exp() {
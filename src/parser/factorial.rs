@@ -0,0 +1,56 @@
+use super::{emdas, Function, LexerToken, Node, NodeToken, ParserError};
+
+/// Parses the postfix factorial `n!`, which binds tighter than every other operator
+///
+/// This expects that parentheses and functions have already been parsed.
+pub(super) fn parse(node_tokens: &[NodeToken]) -> Result<Vec<NodeToken>, ParserError> {
+    let mut output: Vec<NodeToken> = vec![];
+
+    for node_token in node_tokens {
+        if *node_token != NodeToken::Token(LexerToken::Factorial) {
+            output.push(node_token.clone());
+            continue;
+        }
+
+        let operand = match output.pop() {
+            Some(NodeToken::Token(token)) if token.is_operator() => {
+                return Err(ParserError::InvalidExpression)
+            }
+            Some(operand) => emdas::parse(&[operand])?,
+            None => return Err(ParserError::InvalidExpression),
+        };
+        output.push(NodeToken::Node(Node::Function(
+            Function::Factorial,
+            vec![operand],
+        )));
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Number;
+
+    #[test]
+    fn test_parse_factorial() {
+        let tokens = vec![
+            NodeToken::Token(LexerToken::Number(Number::real(3.0))),
+            NodeToken::Token(LexerToken::Factorial),
+            NodeToken::Token(LexerToken::Factorial),
+        ];
+
+        let node_tokens = parse(&tokens).unwrap();
+
+        let three = Node::Number(Number::real(3.0));
+        let factorial = Node::Function(Function::Factorial, vec![three]);
+        assert_eq!(
+            node_tokens,
+            vec![NodeToken::Node(Node::Function(
+                Function::Factorial,
+                vec![factorial]
+            ))]
+        );
+    }
+}
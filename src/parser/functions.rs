@@ -50,6 +50,20 @@ pub(super) fn parse(node_tokens: &[NodeToken]) -> Result<Vec<NodeToken>, ParserE
                     return Err(ParserError::InvalidFunctionCall);
                 }
             }
+            NT::Token(LT::Function(function)) => {
+                let arguments = match node_tokens[i + 1].clone() {
+                    NT::Node(node) => vec![node],
+                    NT::Arguments(arguments) => arguments,
+                    NT::Token(_) => return Err(ParserError::InvalidFunctionCall),
+                };
+                if arguments.len() != function.arity()
+                    || function.binds_index() && !matches!(arguments[1], Node::Variable(_))
+                {
+                    return Err(ParserError::InvalidFunctionCall);
+                }
+                skip_next = true;
+                output.push(NT::Node(Node::Function(function, arguments)));
+            }
             _ => {
                 if skip_next {
                     skip_next = false;
@@ -71,6 +85,7 @@ pub(super) fn parse(node_tokens: &[NodeToken]) -> Result<Vec<NodeToken>, ParserE
 mod tests {
     use super::*;
     use crate::Number;
+    use std::str::FromStr;
 
     #[test]
     fn test_parse_functions() {
@@ -1,11 +1,16 @@
 use crate::lexer::LexerToken;
 use thiserror::Error;
 
+mod equation;
 mod node;
-pub use node::Node;
+pub use derivative::derivative_order;
+pub use equation::{parse_equation, Equation};
+pub use node::{Function, Node};
 
+mod derivative;
 mod emdas;
 mod exp;
+mod factorial;
 mod functions;
 mod parentheses;
 
@@ -24,9 +29,11 @@ pub enum ParserError {
 /// Parses the tokens into a Node
 ///
 /// This function is pretty slow, but since the tree only needs to be built once, it's not a big deal.
-pub fn parse(tokens: &Vec<LexerToken>) -> Result<Node, ParserError> {
+pub fn parse(tokens: &[LexerToken]) -> Result<Node, ParserError> {
     let node_tokens = parentheses::parse(tokens)?;
+    let node_tokens = derivative::parse(&node_tokens)?;
     let node_tokens = functions::parse(&node_tokens)?;
+    let node_tokens = factorial::parse(&node_tokens)?;
     let node = emdas::parse(&node_tokens)?;
     let node = exp::parse(node);
 
@@ -37,6 +44,8 @@ pub fn parse(tokens: &Vec<LexerToken>) -> Result<Node, ParserError> {
 pub enum NodeToken {
     Token(LexerToken),
     Node(Node),
+    /// Comma separated expressions in parentheses, the arguments of a function call
+    Arguments(Vec<Node>),
 }
 
 impl NodeToken {
@@ -59,6 +68,92 @@ impl NodeToken {
 mod tests {
     use super::*;
     use crate::Number;
+    use std::str::FromStr;
+
+    fn parsed(input: &str) -> Result<Node, ParserError> {
+        parse(&crate::lexer::tokenize(input).unwrap())
+    }
+
+    fn number(value: f64) -> Node {
+        Node::Number(Number::real(value))
+    }
+
+    #[test]
+    fn test_parse_function_calls() {
+        assert_eq!(
+            parsed("gcd(12, 2 * 9)").unwrap(),
+            Node::Function(
+                Function::Gcd,
+                vec![
+                    number(12.0),
+                    Node::Mul(Box::new(number(2.0)), Box::new(number(9.0)))
+                ]
+            )
+        );
+        assert_eq!(
+            parsed("floor(mod(7, 3))").unwrap(),
+            Node::Function(
+                Function::Floor,
+                vec![Node::Function(
+                    Function::Mod,
+                    vec![number(7.0), number(3.0)]
+                )]
+            )
+        );
+        assert!(matches!(
+            parsed("gcd(1)"),
+            Err(ParserError::InvalidFunctionCall)
+        ));
+        assert!(matches!(
+            parsed("sin(1, 2)"),
+            Err(ParserError::InvalidFunctionCall)
+        ));
+        assert!(parsed("(1, 2)").is_err());
+        assert!(matches!(
+            parsed("sum(k, 2, 1, 10)"),
+            Err(ParserError::InvalidFunctionCall)
+        ));
+    }
+
+    #[test]
+    fn test_parse_factorial() {
+        let factorial = |n: Node| Node::Function(Function::Factorial, vec![n]);
+
+        assert_eq!(
+            parsed("2^3!").unwrap(),
+            Node::Pow(Box::new(number(2.0)), Box::new(factorial(number(3.0))))
+        );
+        assert_eq!(
+            parsed("-(n + 1)!").unwrap(),
+            Node::Neg(Box::new(factorial(Node::Add(
+                Box::new(Node::Variable("n".to_string())),
+                Box::new(number(1.0))
+            ))))
+        );
+        assert_eq!(
+            parsed("n! / 2").unwrap(),
+            Node::Div(
+                Box::new(factorial(Node::Variable("n".to_string()))),
+                Box::new(number(2.0))
+            )
+        );
+        assert!(parsed("!3").is_err());
+    }
+
+    #[test]
+    fn test_parse_derivative_notation() {
+        let variable = |name: &str| Box::new(Node::Variable(name.to_string()));
+
+        assert_eq!(
+            parsed("y'' + 2*y'").unwrap(),
+            Node::Add(
+                variable("y''"),
+                Box::new(Node::Mul(Box::new(number(2.0)), variable("y'")))
+            )
+        );
+        assert!(parsed("(y)'").is_err());
+        assert!(parsed("'y").is_err());
+    }
 
     #[test]
     fn test_parse_parentheses_pemdas() {
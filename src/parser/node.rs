@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::Number;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -6,6 +8,7 @@ pub enum Node {
     Variable(String),
     PiConstant,
     EConstant,
+    IConstant,
     Add(Box<Node>, Box<Node>),
     Sub(Box<Node>, Box<Node>),
     Mul(Box<Node>, Box<Node>),
@@ -18,4 +21,211 @@ pub enum Node {
     Tan(Box<Node>),
     Sqrt(Box<Node>),
     Neg(Box<Node>),
+    Function(Function, Vec<Node>),
+}
+
+/// Integer and combinatorial functions, called with a fixed number of arguments
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Function {
+    /// `n!`, also written `factorial(n)`
+    Factorial,
+    Gcd,
+    Lcm,
+    /// `mod(a, n)`, the remainder with the sign of `n`
+    Mod,
+    Binomial,
+    Floor,
+    Ceil,
+    /// 1 for primes and 0 for everything else
+    IsPrime,
+    /// The smallest prime larger than the argument
+    NextPrime,
+    /// `sum(f, k, a, b)`, the sum of `f` over the integers `k` from `a` to `b`, which for
+    /// `b < a - 1` is minus the sum from `b + 1` to `a - 1`
+    Sum,
+    /// `product(f, k, a, b)`, the product of `f` over the integers `k` from `a` to `b`, which for
+    /// `b < a - 1` is the inverse of the product from `b + 1` to `a - 1`
+    Product,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "factorial" => Some(Function::Factorial),
+            "gcd" => Some(Function::Gcd),
+            "lcm" => Some(Function::Lcm),
+            "mod" => Some(Function::Mod),
+            "binomial" => Some(Function::Binomial),
+            "floor" => Some(Function::Floor),
+            "ceil" => Some(Function::Ceil),
+            "isprime" => Some(Function::IsPrime),
+            "nextprime" => Some(Function::NextPrime),
+            "sum" => Some(Function::Sum),
+            "product" => Some(Function::Product),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Factorial => "factorial",
+            Function::Gcd => "gcd",
+            Function::Lcm => "lcm",
+            Function::Mod => "mod",
+            Function::Binomial => "binomial",
+            Function::Floor => "floor",
+            Function::Ceil => "ceil",
+            Function::IsPrime => "isprime",
+            Function::NextPrime => "nextprime",
+            Function::Sum => "sum",
+            Function::Product => "product",
+        }
+    }
+
+    /// The number of arguments the function takes
+    pub fn arity(&self) -> usize {
+        match self {
+            Function::Sum | Function::Product => 4,
+            Function::Gcd | Function::Lcm | Function::Mod | Function::Binomial => 2,
+            Function::Factorial
+            | Function::Floor
+            | Function::Ceil
+            | Function::IsPrime
+            | Function::NextPrime => 1,
+        }
+    }
+
+    /// Whether the second argument is an index variable that only has a value inside the first,
+    /// like the `k` in `sum(k^2, k, 1, n)`
+    pub fn binds_index(&self) -> bool {
+        matches!(self, Function::Sum | Function::Product)
+    }
+}
+
+/// How tightly a node binds when it's written out, higher binds tighter
+fn precedence(node: &Node) -> u8 {
+    match node {
+        Node::Add(..) | Node::Sub(..) => 1,
+        Node::Number(number) if !number.is_real() => 1,
+        Node::Mul(..) | Node::Div(..) => 2,
+        Node::Neg(_) => 3,
+        Node::Number(number) if number.value() < 0.0 => 3,
+        Node::Pow(..) | Node::Exp(_) => 4,
+        _ => 5,
+    }
+}
+
+/// Writes an operand, in parentheses if it binds less tightly than `min`
+fn operand(f: &mut fmt::Formatter<'_>, node: &Node, min: u8) -> fmt::Result {
+    if precedence(node) < min {
+        write!(f, "({})", node)
+    } else {
+        write!(f, "{}", node)
+    }
+}
+
+/// Right hand operands that start with a minus sign are put in parentheses, `x - (-1)`
+fn right_operand(f: &mut fmt::Formatter<'_>, node: &Node, min: u8) -> fmt::Result {
+    operand(f, node, if precedence(node) == 3 { 4 } else { min })
+}
+
+/// Writes the node with as few parentheses as the parser needs to read it back, `2*x + 1`
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let function =
+            |f: &mut fmt::Formatter<'_>, name: &str, n: &Node| write!(f, "{}({})", name, n);
+        match self {
+            Node::Number(number) => match number.literal() {
+                Some(literal) => write!(f, "{}", literal),
+                None => write!(f, "{}", number),
+            },
+            Node::Variable(name) => write!(f, "{}", name),
+            Node::PiConstant => write!(f, "pi"),
+            Node::EConstant => write!(f, "e"),
+            Node::IConstant => write!(f, "i"),
+            Node::Add(l, r) => {
+                operand(f, l, 1)?;
+                write!(f, " + ")?;
+                right_operand(f, r, 2)
+            }
+            Node::Sub(l, r) => {
+                operand(f, l, 1)?;
+                write!(f, " - ")?;
+                right_operand(f, r, 2)
+            }
+            Node::Mul(l, r) => {
+                operand(f, l, 2)?;
+                write!(f, "*")?;
+                right_operand(f, r, 3)
+            }
+            Node::Div(l, r) => {
+                operand(f, l, 2)?;
+                write!(f, "/")?;
+                right_operand(f, r, 3)
+            }
+            Node::Pow(l, r) => {
+                operand(f, l, 5)?;
+                write!(f, "^")?;
+                right_operand(f, r, 4)
+            }
+            Node::Exp(n) => {
+                write!(f, "e^")?;
+                right_operand(f, n, 4)
+            }
+            Node::Log(n) => function(f, "ln", n),
+            Node::Sin(n) => function(f, "sin", n),
+            Node::Cos(n) => function(f, "cos", n),
+            Node::Tan(n) => function(f, "tan", n),
+            Node::Sqrt(n) => function(f, "sqrt", n),
+            Node::Neg(n) => {
+                write!(f, "-")?;
+                operand(f, n, if precedence(n) == 3 { 4 } else { 2 })
+            }
+            Node::Function(Function::Factorial, arguments) => {
+                operand(f, &arguments[0], 5)?;
+                write!(f, "!")
+            }
+            Node::Function(function, arguments) => {
+                write!(f, "{}(", function.name())?;
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", argument)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn written(input: &str) -> String {
+        parse(&tokenize(input).unwrap()).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(written("2 * x + 1"), "2*x + 1");
+        assert_eq!(written("(a + b) * (c - d)"), "(a + b)*(c - d)");
+        assert_eq!(written("a - (b - c)"), "a - (b - c)");
+        assert_eq!(written("a / (b * c)"), "a/(b*c)");
+        assert_eq!(written("(-x)^2 + -x^2"), "(-x)^2 + (-x^2)");
+        assert_eq!(written("x^(y^2) * (x^y)^2"), "x^y^2*(x^y)^2");
+        assert_eq!(written("e^(2*x) + sin(x)/ln(x)"), "e^(2*x) + sin(x)/ln(x)");
+        assert_eq!(written("gcd(a, 6) + (n - 1)!"), "gcd(a, 6) + (n - 1)!");
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for input in ["x^2 + 2*x + 1", "-(a + b)/c", "a - (-1)", "(x/y)/z", "2^-x"] {
+            let node = parse(&tokenize(input).unwrap()).unwrap();
+            let reparsed = parse(&tokenize(&node.to_string()).unwrap()).unwrap();
+            assert_eq!(reparsed, node, "{} was written as {}", input, node);
+        }
+    }
 }
@@ -10,6 +10,9 @@ pub(super) fn parse(tokens: &[LexerToken]) -> Result<Vec<NodeToken>, ParserError
     for token in tokens {
         match token {
             LexerToken::LeftParenthesis => {
+                if parentheses_count > 0 {
+                    current_parsing.push(token.clone());
+                }
                 parentheses_count += 1;
             }
             LexerToken::RightParenthesis => {
@@ -20,9 +23,10 @@ pub(super) fn parse(tokens: &[LexerToken]) -> Result<Vec<NodeToken>, ParserError
                 }
 
                 if parentheses_count == 0 {
-                    let node = full_parse(&current_parsing)?;
-                    node_tokens.push(NodeToken::Node(node));
+                    node_tokens.push(parse_group(&current_parsing)?);
                     current_parsing.clear();
+                } else {
+                    current_parsing.push(token.clone());
                 }
             }
             _ => {
@@ -42,6 +46,32 @@ pub(super) fn parse(tokens: &[LexerToken]) -> Result<Vec<NodeToken>, ParserError
     Ok(node_tokens)
 }
 
+/// Parses the tokens between a pair of parentheses, which are either a single expression or the
+/// comma separated arguments of a function call
+fn parse_group(tokens: &[LexerToken]) -> Result<NodeToken, ParserError> {
+    let mut arguments = vec![];
+    let mut start = 0;
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            LexerToken::LeftParenthesis => depth += 1,
+            LexerToken::RightParenthesis => depth -= 1,
+            LexerToken::Comma if depth == 0 => {
+                arguments.push(full_parse(&tokens[start..i])?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    let last = full_parse(&tokens[start..])?;
+    if arguments.is_empty() {
+        return Ok(NodeToken::Node(last));
+    }
+    arguments.push(last);
+    Ok(NodeToken::Arguments(arguments))
+}
+
 #[cfg(test)]
 mod tests {
     // TODO: Write tests, this function is a little bit tricky to test since it depends on the full_parse function. I will instead write tests for the full_parse function that uses parentheses::parse
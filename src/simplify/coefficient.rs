@@ -0,0 +1,277 @@
+use crate::parser::Node;
+use crate::{Number, NumberError};
+
+/// Numeric coefficient of a term, kept as an exact fraction for as long as it fits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Coefficient {
+    Rational(i64, i64),
+    Real(f64),
+}
+
+/// Integers up to this size are exact in an f64
+const MAX_EXACT: f64 = 9007199254740992.0;
+
+/// Integers that don't fit in an f64 keep their digits, so they can be read back exactly
+fn integer_node(value: i128) -> Node {
+    let number = Number::real(value as f64);
+    if value.unsigned_abs() <= MAX_EXACT as u128 {
+        Node::Number(number)
+    } else {
+        Node::Number(number.with_literal(&value.to_string()))
+    }
+}
+
+fn gcd(mut a: i64, mut b: i64) -> i64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+impl Coefficient {
+    pub fn integer(value: i64) -> Coefficient {
+        Coefficient::Rational(value, 1)
+    }
+
+    /// Creates a normalized fraction, falling back to a float if it doesn't fit. The denominator
+    /// must not be zero.
+    pub fn rational(num: i64, den: i64) -> Coefficient {
+        let divisor = gcd(num, den);
+        let (num, den) = (num / divisor, den / divisor);
+        match (den < 0).then(|| (num.checked_neg(), den.checked_neg())) {
+            None => Coefficient::Rational(num, den),
+            Some((Some(num), Some(den))) => Coefficient::Rational(num, den),
+            Some(_) => Coefficient::Real(num as f64 / den as f64),
+        }
+    }
+
+    /// Integral values are exact, anything else like 0.1 stays a float
+    pub fn from_f64(value: f64) -> Coefficient {
+        if value.fract() == 0.0 && value.abs() <= MAX_EXACT {
+            Coefficient::integer(value as i64)
+        } else {
+            Coefficient::Real(value)
+        }
+    }
+
+    /// Reads a numeric node, which is a real number, a fraction of integers or a negation of those.
+    /// Integers written out in full are read from their digits, so they stay exact beyond 2^53.
+    pub fn from_node(node: &Node) -> Option<Coefficient> {
+        match node {
+            Node::Number(number) if number.is_real() => Some(
+                match number.literal().and_then(|literal| literal.parse().ok()) {
+                    Some(value) => Coefficient::integer(value),
+                    None => Coefficient::from_f64(number.value()),
+                },
+            ),
+            Node::Neg(n) => Some(Coefficient::from_node(n)?.neg()),
+            Node::Div(l, r) => {
+                let l = Coefficient::from_node(l)?;
+                let r = Coefficient::from_node(r)?;
+                match (l, r) {
+                    (Coefficient::Rational(a, 1), Coefficient::Rational(b, 1)) if b != 0 => {
+                        Some(Coefficient::rational(a, b))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Coefficient::Rational(num, den) => num as f64 / den as f64,
+            Coefficient::Real(value) => value,
+        }
+    }
+
+    /// The value as a node, fractions become a division of two integers
+    pub fn to_node(self) -> Node {
+        match self {
+            Coefficient::Rational(num, 1) => integer_node(num.into()),
+            Coefficient::Rational(num, den) => {
+                let fraction = Node::Div(
+                    Box::new(integer_node(num.unsigned_abs().into())),
+                    Box::new(integer_node(den.into())),
+                );
+                if num < 0 {
+                    Node::Neg(Box::new(fraction))
+                } else {
+                    fraction
+                }
+            }
+            Coefficient::Real(value) => Node::Number(Number::real(value)),
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.to_f64() == 0.0
+    }
+
+    pub fn is_one(self) -> bool {
+        self.to_f64() == 1.0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.to_f64() < 0.0
+    }
+
+    pub fn as_integer(self) -> Option<i64> {
+        match self {
+            Coefficient::Rational(num, 1) => Some(num),
+            _ => None,
+        }
+    }
+
+    pub fn neg(self) -> Coefficient {
+        match self {
+            Coefficient::Rational(num, den) => match num.checked_neg() {
+                Some(num) => Coefficient::Rational(num, den),
+                None => Coefficient::Real(-(num as f64) / den as f64),
+            },
+            Coefficient::Real(value) => Coefficient::Real(-value),
+        }
+    }
+
+    pub fn abs(self) -> Coefficient {
+        if self.is_negative() {
+            self.neg()
+        } else {
+            self
+        }
+    }
+
+    /// Falls back to floating point arithmetic, which must not overflow to infinity. Fractions
+    /// that overflow are an overflow too, rounding them would make the folding inexact.
+    fn real(
+        f: fn(&Number, &Number) -> Result<Number, NumberError>,
+        left: Coefficient,
+        right: Coefficient,
+    ) -> Result<Coefficient, NumberError> {
+        if let (Coefficient::Rational(..), Coefficient::Rational(..)) = (left, right) {
+            return Err(NumberError::Overflow);
+        }
+        let result = f(&Number::real(left.to_f64()), &Number::real(right.to_f64()))?;
+        Ok(Coefficient::Real(result.value()))
+    }
+
+    pub fn add(self, other: Coefficient) -> Result<Coefficient, NumberError> {
+        if let (Coefficient::Rational(a, b), Coefficient::Rational(c, d)) = (self, other) {
+            let num = a
+                .checked_mul(d)
+                .zip(c.checked_mul(b))
+                .and_then(|(x, y)| x.checked_add(y));
+            if let (Some(num), Some(den)) = (num, b.checked_mul(d)) {
+                return Ok(Coefficient::rational(num, den));
+            }
+        }
+        Coefficient::real(Number::checked_add, self, other)
+    }
+
+    pub fn mul(self, other: Coefficient) -> Result<Coefficient, NumberError> {
+        if let (Coefficient::Rational(a, b), Coefficient::Rational(c, d)) = (self, other) {
+            if let (Some(num), Some(den)) = (a.checked_mul(c), b.checked_mul(d)) {
+                return Ok(Coefficient::rational(num, den));
+            }
+        }
+        Coefficient::real(Number::checked_mul, self, other)
+    }
+
+    pub fn recip(self) -> Result<Coefficient, NumberError> {
+        match self {
+            _ if self.is_zero() => Err(NumberError::DivisionByZero),
+            Coefficient::Rational(num, den) => Ok(Coefficient::rational(den, num)),
+            Coefficient::Real(_) => {
+                Coefficient::real(Number::checked_div, Coefficient::integer(1), self)
+            }
+        }
+    }
+
+    /// Raises to an integer power, negative powers of zero are a division by zero
+    pub fn powi(self, exponent: i64) -> Result<Coefficient, NumberError> {
+        if exponent < 0 {
+            let exponent = exponent.checked_neg().ok_or(NumberError::Overflow)?;
+            return self.recip()?.powi(exponent);
+        }
+        let mut base = self;
+        let mut result = Coefficient::integer(1);
+        let mut exponent = exponent;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(base)?;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.mul(base)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// The exact square root of a fraction of perfect squares
+    pub fn exact_sqrt(self) -> Option<Coefficient> {
+        let Coefficient::Rational(num, den) = self else {
+            return None;
+        };
+        let root = |n: i64| -> Option<i64> {
+            let r = (n as f64).sqrt().round() as i64;
+            (n >= 0 && r.checked_mul(r) == Some(n)).then_some(r)
+        };
+        Some(Coefficient::rational(root(num)?, root(den)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fractions_stay_exact() {
+        let third = Coefficient::rational(1, 3);
+
+        assert_eq!(
+            third.add(third).unwrap().add(third),
+            Ok(Coefficient::integer(1))
+        );
+        assert_eq!(
+            third.mul(Coefficient::integer(6)),
+            Ok(Coefficient::integer(2))
+        );
+        assert_eq!(Coefficient::rational(2, -4), Coefficient::Rational(-1, 2));
+        assert_eq!(third.powi(-2), Ok(Coefficient::integer(9)));
+        assert_eq!(
+            Coefficient::rational(9, 4).exact_sqrt(),
+            Some(Coefficient::rational(3, 2))
+        );
+    }
+
+    #[test]
+    fn test_falls_back_to_floats() {
+        assert_eq!(Coefficient::from_f64(0.5), Coefficient::Real(0.5));
+        assert_eq!(
+            Coefficient::Real(0.5).add(Coefficient::integer(1)),
+            Ok(Coefficient::Real(1.5))
+        );
+        assert_eq!(
+            Coefficient::integer(i64::MAX).add(Coefficient::integer(1)),
+            Err(NumberError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_reports_errors_instead_of_infinity() {
+        assert_eq!(
+            Coefficient::integer(0).recip(),
+            Err(NumberError::DivisionByZero)
+        );
+        assert_eq!(
+            Coefficient::integer(0).powi(-1),
+            Err(NumberError::DivisionByZero)
+        );
+        assert_eq!(
+            Coefficient::integer(10).powi(400),
+            Err(NumberError::Overflow)
+        );
+    }
+}
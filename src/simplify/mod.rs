@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+pub(crate) mod coefficient;
+mod product;
+mod sum;
+
+use coefficient::Coefficient;
+use product::Product;
+use sum::Sum;
+
+use crate::calculus;
+use crate::eval::evaluate;
+use crate::integer;
+use crate::parser::{Function, Node};
+use crate::Number;
+
+/// Simplifies an expression bottom up.
+///
+/// Sums and products are brought into a normal form where like terms and powers of the same base
+/// are combined, so `pi + pi` becomes `2*pi` and `e^2 * e^3` becomes `e^5`. Numbers are folded
+/// exactly as fractions, or left unfolded when they don't fit in an i64, while pi, e and i stay symbolic until the expression is evaluated.
+pub fn simplify(node: &Node) -> Node {
+    match node {
+        Node::Add(l, r) => Sum::from_node(&simplify(l))
+            .add(Sum::from_node(&simplify(r)))
+            .to_node(),
+        Node::Sub(l, r) => Sum::from_node(&simplify(l))
+            .add(Sum::from_node(&simplify(r)).neg())
+            .to_node(),
+        Node::Neg(n) => Sum::from_node(&simplify(n)).neg().to_node(),
+        Node::Mul(l, r) => Product::from_node(&simplify(l))
+            .mul(&Product::from_node(&simplify(r)))
+            .to_node(),
+        Node::Div(l, r) => {
+            let (l, r) = (simplify(l), simplify(r));
+            match Product::from_node(&r).recip() {
+                Some(recip) => Product::from_node(&l).mul(&recip).to_node(),
+                None => Node::Div(Box::new(l), Box::new(r)),
+            }
+        }
+        Node::Pow(l, r) => simplify_pow(simplify(l), simplify(r)),
+        Node::Exp(n) => simplify_exp(simplify(n)),
+        Node::Log(n) => simplify_log(simplify(n)),
+        Node::Sin(n) => simplify_sin(simplify(n)),
+        Node::Cos(n) => simplify_cos(simplify(n)),
+        Node::Tan(n) => simplify_tan(simplify(n)),
+        Node::Sqrt(n) => Product::from_node(&Node::Sqrt(Box::new(simplify(n)))).to_node(),
+        Node::Function(function, arguments) => {
+            simplify_function(*function, arguments.iter().map(simplify).collect())
+        }
+        Node::Number(_)
+        | Node::Variable(_)
+        | Node::PiConstant
+        | Node::EConstant
+        | Node::IConstant => node.clone(),
+    }
+}
+
+fn simplify_pow(l: Node, r: Node) -> Node {
+    if let Some(n) = Coefficient::from_node(&r).and_then(Coefficient::as_integer) {
+        if let Some(product) = Product::from_node(&l).powi(n) {
+            return product.to_node();
+        }
+    }
+    Product::from_node(&Node::Pow(Box::new(l), Box::new(r))).to_node()
+}
+
+fn simplify_exp(n: Node) -> Node {
+    match n {
+        Node::Log(inner) => *inner,
+        n if Coefficient::from_node(&n).is_some_and(Coefficient::is_zero) => {
+            Coefficient::integer(1).to_node()
+        }
+        n => Node::Exp(Box::new(n)),
+    }
+}
+
+/// `ln(e^u)` is only `u` on the principal branch, when the imaginary part of u is in (-pi, pi)
+fn simplify_log(n: Node) -> Node {
+    match n {
+        Node::Exp(inner) if is_principal(&inner) => *inner,
+        Node::EConstant => Coefficient::integer(1).to_node(),
+        n if Coefficient::from_node(&n).is_some_and(Coefficient::is_one) => {
+            Coefficient::integer(0).to_node()
+        }
+        n => Node::Log(Box::new(n)),
+    }
+}
+
+/// Whether a constant has an imaginary part strictly between -pi and pi, variables may be complex
+fn is_principal(n: &Node) -> bool {
+    product::is_constant(n)
+        && evaluate(n, &HashMap::new()).is_ok_and(|value| value.im().abs() < PI * (1.0 - 1e-9))
+}
+
+/// Folds integer functions of numbers exactly, results that don't fit in an f64 are left alone,
+/// and sums and products get their closed forms
+fn simplify_function(function: Function, arguments: Vec<Node>) -> Node {
+    if let [node, Node::Variable(index), a, b] = arguments.as_slice() {
+        match function {
+            Function::Sum => return calculus::sum(node, index, a, b),
+            Function::Product => return calculus::product(node, index, a, b),
+            _ => {}
+        }
+    }
+
+    let numbers: Option<Vec<Coefficient>> = arguments.iter().map(Coefficient::from_node).collect();
+    if let Some(value) = numbers.and_then(|numbers| fold_function(function, &numbers)) {
+        return value.to_node();
+    }
+
+    let integer_argument = |i: usize| Coefficient::from_node(&arguments[i])?.as_integer();
+    match function {
+        Function::Binomial if integer_argument(1) == Some(0) || arguments[0] == arguments[1] => {
+            Coefficient::integer(1).to_node()
+        }
+        Function::Binomial if integer_argument(1) == Some(1) => arguments[0].clone(),
+        // Floor and ceil of a whole number don't change it
+        Function::Floor | Function::Ceil => match &arguments[0] {
+            Node::Function(Function::Floor | Function::Ceil, _) => arguments[0].clone(),
+            _ => Node::Function(function, arguments),
+        },
+        _ => Node::Function(function, arguments),
+    }
+}
+
+fn fold_function(function: Function, arguments: &[Coefficient]) -> Option<Coefficient> {
+    match (function, arguments) {
+        (Function::Floor, [Coefficient::Rational(num, den)]) => {
+            Some(Coefficient::integer(num.div_euclid(*den)))
+        }
+        (Function::Ceil, [Coefficient::Rational(num, den)]) => {
+            Some(Coefficient::integer(-(-num).div_euclid(*den)))
+        }
+        // a - n * floor(a / n) keeps fractions exact
+        (Function::Mod, [a @ Coefficient::Rational(..), n @ Coefficient::Rational(..)]) => {
+            let quotient = fold_function(Function::Floor, &[a.mul(n.recip().ok()?).ok()?])?;
+            a.add(n.mul(quotient).ok()?.neg()).ok()
+        }
+        _ => {
+            let arguments: Vec<Number> =
+                arguments.iter().map(|a| Number::real(a.to_f64())).collect();
+            let value = integer::evaluate(function, &arguments).ok()?.value();
+            let value = Coefficient::from_f64(value);
+            // Integers that were rounded to fit into an f64 aren't exact
+            (value.as_integer().is_some() || function == Function::Mod).then_some(value)
+        }
+    }
+}
+
+/// The angle as a whole number of twelfths of pi, for the angles with known exact values
+fn twelfths_of_pi(n: &Node) -> Option<i64> {
+    let product = Product::from_node(n);
+    if product.is_zero() {
+        return Some(0);
+    }
+    if product.factors != [(Node::PiConstant, Coefficient::integer(1).to_node())] {
+        return None;
+    }
+    match product.coefficient {
+        Coefficient::Rational(num, den) if 12 % den == 0 => {
+            Some(num.checked_mul(12 / den)?.rem_euclid(24))
+        }
+        _ => None,
+    }
+}
+
+/// sin(k * pi/12) for the k where it is 0, 1/2, sqrt(2)/2, sqrt(3)/2 or 1
+fn exact_sin(twelfths: i64) -> Option<Product> {
+    let negative = twelfths >= 12;
+    let k = twelfths % 12;
+    let k = if k > 6 { 12 - k } else { k };
+
+    let root = |n: i64| Node::Sqrt(Box::new(Coefficient::integer(n).to_node()));
+    let value = match k {
+        0 => Product::from_coefficient(Coefficient::integer(0)),
+        2 => Product::from_coefficient(Coefficient::rational(1, 2)),
+        3 => Product::from_node(&root(2))
+            .mul(&Product::from_coefficient(Coefficient::rational(1, 2))),
+        4 => Product::from_node(&root(3))
+            .mul(&Product::from_coefficient(Coefficient::rational(1, 2))),
+        6 => Product::from_coefficient(Coefficient::integer(1)),
+        _ => return None,
+    };
+    Some(match negative {
+        true => value.mul(&Product::from_coefficient(Coefficient::integer(-1))),
+        false => value,
+    })
+}
+
+fn simplify_sin(n: Node) -> Node {
+    match twelfths_of_pi(&n).and_then(exact_sin) {
+        Some(value) => value.to_node(),
+        None => Node::Sin(Box::new(n)),
+    }
+}
+
+fn simplify_cos(n: Node) -> Node {
+    match twelfths_of_pi(&n).and_then(|k| exact_sin((k + 6) % 24)) {
+        Some(value) => value.to_node(),
+        None => Node::Cos(Box::new(n)),
+    }
+}
+
+fn simplify_tan(n: Node) -> Node {
+    let twelfths = twelfths_of_pi(&n);
+    let sin = twelfths.and_then(exact_sin);
+    let cos = twelfths
+        .and_then(|k| exact_sin((k + 6) % 24))
+        .filter(|cos| !cos.is_zero());
+    match (sin, cos.and_then(|cos| cos.recip())) {
+        (Some(sin), Some(recip)) => sin.mul(&recip).to_node(),
+        _ => Node::Tan(Box::new(n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+    use crate::Number;
+
+    fn simplified(input: &str) -> Node {
+        simplify(&parse(&tokenize(input).unwrap()).unwrap())
+    }
+
+    fn parsed(input: &str) -> Node {
+        parse(&tokenize(input).unwrap()).unwrap()
+    }
+
+    fn number(value: f64) -> Node {
+        Node::Number(Number::real(value))
+    }
+
+    #[test]
+    fn test_folds_constants() {
+        assert_eq!(simplified("1 + 2 * 3"), number(7.0));
+        assert_eq!(simplified("2^3 - 8/4"), number(6.0));
+        assert_eq!(simplified("1/3"), parsed("1/3"));
+    }
+
+    #[test]
+    fn test_folds_large_integers_exactly() {
+        assert_eq!(simplified("2^53+1 - 2^53"), number(1.0));
+        assert_eq!(simplified("3^39").to_string(), "4052555153018976267");
+        assert_eq!(simplified("3^39 - 4052555153018976266"), number(1.0));
+        // 10^20 doesn't fit in an i64, so it isn't folded at all
+        assert_eq!(simplified("10^20+1").to_string(), "1 + 10^20");
+    }
+
+    #[test]
+    fn test_removes_identities() {
+        assert_eq!(simplified("x * 1 + 0"), parsed("x"));
+        assert_eq!(simplified("x^1 * y^0"), parsed("x"));
+        assert_eq!(simplified("0 * sin(x)"), number(0.0));
+        assert_eq!(simplified("x - x"), number(0.0));
+        assert_eq!(simplified("-(-x)"), parsed("x"));
+    }
+
+    #[test]
+    fn test_powers_of_i() {
+        assert_eq!(simplified("i^2"), number(-1.0));
+        assert_eq!(simplified("i * i"), number(-1.0));
+        assert_eq!(simplified("i^3"), Node::Neg(Box::new(Node::IConstant)));
+        assert_eq!(simplified("i^4"), number(1.0));
+        assert_eq!(simplified("i^-1"), Node::Neg(Box::new(Node::IConstant)));
+        assert_eq!(simplified("x + i^2"), parsed("x - 1"));
+    }
+
+    #[test]
+    fn test_keeps_pi_and_e_exact() {
+        assert_eq!(simplified("pi + pi"), parsed("2 * pi"));
+        assert_eq!(simplified("pi/2 * 2"), Node::PiConstant);
+        assert_eq!(simplified("e^2 * e^3"), parsed("e^5"));
+        assert_eq!(simplified("pi/3 + pi/6"), parsed("pi/2"));
+        assert_eq!(simplified("2*pi - pi - pi"), number(0.0));
+        assert_eq!(simplified("e * e / e^3"), parsed("1 / e"));
+        assert_eq!(simplified("pi * x + x * pi"), parsed("2 * pi * x"));
+    }
+
+    #[test]
+    fn test_collects_terms_and_powers() {
+        assert_eq!(simplified("x + 1 + x + x^2"), parsed("x^2 + 2 * x + 1"));
+        assert_eq!(simplified("x * y * x / y^3"), parsed("x^2 / y^2"));
+        assert_eq!(simplified("1/3 + 1/6"), parsed("1/2"));
+        assert_eq!(simplified("(2 * x)^3"), parsed("8 * x^3"));
+        assert_eq!(simplified("sqrt(x) * sqrt(x)"), parsed("x"));
+        assert_eq!(simplified("x / 0"), parsed("x / 0"));
+    }
+
+    #[test]
+    fn test_leaves_undefined_and_overflowing_folds() {
+        assert_eq!(simplified("1/0"), parsed("1/0"));
+        assert_eq!(simplified("2 * 0^-1"), parsed("2/0"));
+        assert_eq!(simplified("0/0"), parsed("0/0"));
+        assert_eq!(simplified("10^400"), parsed("10^400"));
+        assert_eq!(simplified("1/(1/0)"), parsed("1/(1/0)"));
+        assert_eq!(simplified("(1/0)^-1"), parsed("1/(1/0)"));
+        assert_eq!(simplified("x + 2/(1/0)"), parsed("x + 2/(1/0)"));
+    }
+
+    #[test]
+    fn test_exact_function_values() {
+        assert_eq!(simplified("sin(pi)"), number(0.0));
+        assert_eq!(simplified("cos(2*pi/3)"), parsed("-(1/2)"));
+        assert_eq!(simplified("sin(pi/4)"), parsed("sqrt(2)/2"));
+        assert_eq!(simplified("tan(pi/3)"), parsed("sqrt(3)"));
+        assert_eq!(simplified("tan(pi/2)"), parsed("tan(pi/2)"));
+        assert_eq!(simplified("sqrt(9/4)"), parsed("3/2"));
+        assert_eq!(simplified("ln(e^(pi/2))"), parsed("pi/2"));
+        assert_eq!(simplified("ln(e^(i*pi/2))"), parsed("pi * i / 2"));
+        assert_eq!(simplified("e^0 + ln(1)"), number(1.0));
+    }
+
+    #[test]
+    fn test_log_of_exp_stays_on_the_principal_branch() {
+        assert_eq!(simplified("ln(e^(2*pi*i))"), parsed("ln(e^(2*pi*i))"));
+        assert_eq!(simplified("ln(e^x)"), parsed("ln(e^x)"));
+        let node = simplified("ln(e^(2*pi*i))");
+        assert_eq!(
+            evaluate(&node, &HashMap::new()).map(|n| n.abs() < 1e-9),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn test_integer_functions() {
+        assert_eq!(simplified("5!"), number(120.0));
+        assert_eq!(simplified("gcd(12, 18) + lcm(4, 6)"), number(18.0));
+        assert_eq!(simplified("floor(7/2) + ceil(-7/2)"), number(0.0));
+        assert_eq!(simplified("mod(7/2, 1)"), parsed("1/2"));
+        assert_eq!(simplified("30!"), parsed("30!"));
+        assert_eq!(
+            simplified("binomial(n, 0) + binomial(n, 1)"),
+            parsed("n + 1")
+        );
+        assert_eq!(simplified("floor(ceil(x))"), parsed("ceil(x)"));
+
+        // binomial(n, n) is 1 for negative n too, so the rewrite keeps the value
+        let node = parsed("binomial(n, n)");
+        assert_eq!(simplify(&node), number(1.0));
+        for n in [-3.0, 0.0, 4.0] {
+            let variables = HashMap::from([("n".to_string(), Number::real(n))]);
+            assert_eq!(evaluate(&node, &variables), Ok(Number::real(1.0)));
+        }
+    }
+
+    #[test]
+    fn test_factorial_ratios() {
+        assert_eq!(simplified("n!/(n-1)!"), parsed("n"));
+        assert_eq!(simplified("(n-2)!/n!"), parsed("1 / (n * (n - 1))"));
+        assert_eq!(simplified("(n+1)! / n! - n"), number(1.0));
+    }
+}
@@ -0,0 +1,378 @@
+use std::cmp::Ordering;
+
+use super::coefficient::Coefficient;
+use super::simplify;
+use crate::parser::{Function, Node};
+
+/// A product `coefficient * base_1^exponent_1 * base_2^exponent_2 * ...` with every base
+/// appearing once, `e^x` is stored with the base `e` so it combines with other powers of e.
+///
+/// The factors are kept sorted, so two products with the same factors compare equal.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Product {
+    pub coefficient: Coefficient,
+    pub factors: Vec<(Node, Node)>,
+}
+
+fn one() -> Node {
+    Coefficient::integer(1).to_node()
+}
+
+fn rank(node: &Node) -> u8 {
+    match node {
+        Node::Number(_) => 0,
+        Node::PiConstant => 1,
+        Node::EConstant => 2,
+        Node::IConstant => 3,
+        Node::Variable(_) => 4,
+        Node::Exp(_)
+        | Node::Log(_)
+        | Node::Sin(_)
+        | Node::Cos(_)
+        | Node::Tan(_)
+        | Node::Sqrt(_)
+        | Node::Function(..) => 5,
+        _ => 6,
+    }
+}
+
+/// Canonical order of nodes: numbers, then constants, variables, functions and everything else
+pub(super) fn compare_nodes(a: &Node, b: &Node) -> Ordering {
+    rank(a).cmp(&rank(b)).then_with(|| match (a, b) {
+        (Node::Variable(a), Node::Variable(b)) => a.cmp(b),
+        _ => format!("{:?}", a).cmp(&format!("{:?}", b)),
+    })
+}
+
+/// Whether a node is a constant like 2, pi or sqrt(2), as opposed to something with a variable
+pub(super) fn is_constant(node: &Node) -> bool {
+    match node {
+        Node::Variable(_) => false,
+        Node::Number(_) | Node::PiConstant | Node::EConstant | Node::IConstant => true,
+        Node::Add(l, r) | Node::Sub(l, r) | Node::Mul(l, r) | Node::Div(l, r) | Node::Pow(l, r) => {
+            is_constant(l) && is_constant(r)
+        }
+        Node::Exp(n)
+        | Node::Log(n)
+        | Node::Sin(n)
+        | Node::Cos(n)
+        | Node::Tan(n)
+        | Node::Sqrt(n)
+        | Node::Neg(n) => is_constant(n),
+        Node::Function(_, arguments) => arguments.iter().all(is_constant),
+    }
+}
+
+impl Product {
+    pub fn from_coefficient(coefficient: Coefficient) -> Product {
+        Product {
+            coefficient,
+            factors: vec![],
+        }
+    }
+
+    /// Splits a simplified node into its coefficient and factors
+    pub fn from_node(node: &Node) -> Product {
+        if let Some(coefficient) = Coefficient::from_node(node) {
+            return Product::from_coefficient(coefficient);
+        }
+
+        match node {
+            Node::Mul(l, r) => Product::from_node(l).mul(&Product::from_node(r)),
+            Node::Div(l, r) => match Product::from_node(r).recip() {
+                Some(r) => Product::from_node(l).mul(&r),
+                None => Product::factor(node.clone(), one()),
+            },
+            Node::Neg(n) => {
+                let mut product = Product::from_node(n);
+                product.coefficient = product.coefficient.neg();
+                product
+            }
+            Node::Pow(base, exponent) => Product::factor(*base.clone(), *exponent.clone()),
+            Node::Exp(exponent) => Product::factor(Node::EConstant, *exponent.clone()),
+            Node::Sqrt(base) => {
+                Product::factor(*base.clone(), Coefficient::rational(1, 2).to_node())
+            }
+            _ => Product::factor(node.clone(), one()),
+        }
+    }
+
+    fn factor(base: Node, exponent: Node) -> Product {
+        Product::from_coefficient(Coefficient::integer(1)).with_factor(base, exponent)
+    }
+
+    /// Multiplies in `base^exponent`, combining it with an existing factor of the same base
+    fn with_factor(mut self, base: Node, exponent: Node) -> Product {
+        match self.factors.iter().position(|(b, _)| *b == base) {
+            Some(i) => {
+                let (_, existing) = self.factors.remove(i);
+                let exponent = simplify(&Node::Add(Box::new(existing), Box::new(exponent)));
+                self.push_factor(base, exponent);
+            }
+            None => match self.factorial_ratio(&base, &exponent) {
+                Some(product) => return product,
+                None => self.push_factor(base, exponent),
+            },
+        }
+        self
+    }
+
+    /// Cancels `a!^e / b!^e` when `a - b` is a small integer, `n! / (n - 1)!` becomes `n`
+    fn factorial_ratio(&self, base: &Node, exponent: &Node) -> Option<Product> {
+        const MAX_TERMS: i64 = 16;
+
+        let Node::Function(Function::Factorial, arguments) = base else {
+            return None;
+        };
+        let e = Coefficient::from_node(exponent)?.as_integer()?;
+        let (i, b) = self
+            .factors
+            .iter()
+            .enumerate()
+            .find_map(|(i, (b, exponent))| match b {
+                Node::Function(Function::Factorial, b)
+                    if *exponent == Coefficient::integer(-e).to_node() =>
+                {
+                    Some((i, &b[0]))
+                }
+                _ => None,
+            })?;
+        let a = &arguments[0];
+        let k = Coefficient::from_node(&simplify(&Node::Sub(
+            Box::new(a.clone()),
+            Box::new(b.clone()),
+        )))?
+        .as_integer()?;
+        if k == 0 || k.abs() > MAX_TERMS {
+            return None;
+        }
+
+        // a! / b! is (b + 1) * ... * a, or the reciprocal of (a + 1) * ... * b
+        let (low, e) = if k > 0 { (b, e) } else { (a, -e) };
+        let mut product = self.clone();
+        product.factors.remove(i);
+        for j in 1..=k.abs() {
+            let term = simplify(&Node::Add(
+                Box::new(low.clone()),
+                Box::new(Coefficient::integer(j).to_node()),
+            ));
+            product = product.mul(&Product::from_node(&term).powi(e)?);
+        }
+        Some(product)
+    }
+
+    fn push_factor(&mut self, base: Node, exponent: Node) {
+        let numeric_exponent = Coefficient::from_node(&exponent);
+        if numeric_exponent.is_some_and(|e| e.is_zero()) {
+            return;
+        }
+
+        let numeric_base = Coefficient::from_node(&base);
+        if numeric_base.is_some_and(|b| b.is_one()) {
+            return;
+        }
+
+        if numeric_exponent == Some(Coefficient::rational(1, 2)) {
+            if let Some(root) = numeric_base.and_then(|b| b.exact_sqrt()) {
+                if self.fold(root) {
+                    return;
+                }
+            }
+        }
+
+        if let Some(n) = numeric_exponent.and_then(|e| e.as_integer()) {
+            // Integer powers of numbers fold into the coefficient, unless that divides by zero
+            // or overflows
+            if let Some(Ok(value)) = numeric_base.map(|b| b.powi(n)) {
+                if self.fold(value) {
+                    return;
+                }
+            }
+
+            // Powers of i cycle through i, -1, -i, 1
+            if base == Node::IConstant {
+                let n = n.rem_euclid(4);
+                if n >= 2 {
+                    self.coefficient = self.coefficient.neg();
+                }
+                if n % 2 == 1 {
+                    self.insert(Node::IConstant, one());
+                }
+                return;
+            }
+        }
+
+        self.insert(base, exponent);
+    }
+
+    /// Multiplies a number into the coefficient, false if that would overflow
+    fn fold(&mut self, value: Coefficient) -> bool {
+        match self.coefficient.mul(value) {
+            Ok(coefficient) => {
+                self.coefficient = coefficient;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn insert(&mut self, base: Node, exponent: Node) {
+        let index = self
+            .factors
+            .iter()
+            .position(|(b, _)| compare_nodes(b, &base) == Ordering::Greater)
+            .unwrap_or(self.factors.len());
+        self.factors.insert(index, (base, exponent));
+    }
+
+    pub fn mul(&self, other: &Product) -> Product {
+        let mut product = Product::from_coefficient(self.coefficient);
+        if !product.fold(other.coefficient) {
+            product.insert(other.coefficient.to_node(), one());
+        }
+        for (base, exponent) in self.factors.iter().chain(&other.factors) {
+            product = product.with_factor(base.clone(), exponent.clone());
+        }
+        product
+    }
+
+    /// The reciprocal, dividing by a zero coefficient is left as an explicit `0^-1` factor.
+    ///
+    /// None when the product already divides by zero, turning `0^-1` back into 0 would give
+    /// `1/(1/0)` a value.
+    pub fn recip(&self) -> Option<Product> {
+        if self.divides_by_zero() {
+            return None;
+        }
+        let mut product = match self.coefficient.recip() {
+            Ok(coefficient) => Product::from_coefficient(coefficient),
+            Err(_) => {
+                let mut product = Product::from_coefficient(Coefficient::integer(1));
+                let exponent = Coefficient::integer(-1).to_node();
+                product.insert(self.coefficient.to_node(), exponent);
+                product
+            }
+        };
+        for (base, exponent) in &self.factors {
+            let exponent = simplify(&Node::Neg(Box::new(exponent.clone())));
+            product = product.with_factor(base.clone(), exponent);
+        }
+        Some(product)
+    }
+
+    /// Raises the product to an integer power, None if that overflows or inverts a division by
+    /// zero
+    pub fn powi(&self, n: i64) -> Option<Product> {
+        if n < 0 && self.divides_by_zero() {
+            return None;
+        }
+        let coefficient = self.coefficient.powi(n).ok()?;
+        let mut product = Product::from_coefficient(coefficient);
+        for (base, exponent) in &self.factors {
+            let exponent = simplify(&Node::Mul(
+                Box::new(exponent.clone()),
+                Box::new(Coefficient::integer(n).to_node()),
+            ));
+            product = product.with_factor(base.clone(), exponent);
+        }
+        Some(product)
+    }
+
+    /// Whether one of the factors is a power of zero, which is only left unfolded when it
+    /// divides by zero
+    fn divides_by_zero(&self) -> bool {
+        self.factors
+            .iter()
+            .any(|(base, _)| Coefficient::from_node(base).is_some_and(Coefficient::is_zero))
+    }
+
+    /// Whether the product is zero, which it isn't when it divides by zero
+    pub fn is_zero(&self) -> bool {
+        self.coefficient.is_zero()
+            && self
+                .factors
+                .iter()
+                .all(|(base, _)| Coefficient::from_node(base).is_none())
+    }
+
+    /// Whether none of the factors contain a variable
+    pub fn is_constant(&self) -> bool {
+        self.factors.iter().all(|(base, _)| is_constant(base))
+    }
+
+    /// Builds the node, with positive powers in the numerator and negative ones in the denominator.
+    ///
+    /// The sign of the coefficient is left out, the caller decides if it becomes a negation or a
+    /// subtraction.
+    pub fn to_unsigned_node(&self) -> Node {
+        if self.is_zero() {
+            return Coefficient::integer(0).to_node();
+        }
+
+        let mut numerator = vec![];
+        let mut denominator = vec![];
+
+        let coefficient = self.coefficient.abs();
+        match coefficient {
+            Coefficient::Rational(num, den) => {
+                if num != 1 || self.factors.is_empty() {
+                    numerator.push(Coefficient::integer(num).to_node());
+                }
+                if den != 1 {
+                    denominator.push(Coefficient::integer(den).to_node());
+                }
+            }
+            Coefficient::Real(_) => {
+                if !coefficient.is_one() || self.factors.is_empty() {
+                    numerator.push(coefficient.to_node());
+                }
+            }
+        }
+
+        for (base, exponent) in &self.factors {
+            match Coefficient::from_node(exponent) {
+                Some(e) if e.is_negative() => denominator.push(power_node(base, e.neg().to_node())),
+                _ => numerator.push(power_node(base, exponent.clone())),
+            }
+        }
+
+        let numerator = multiply(numerator).unwrap_or_else(one);
+        match multiply(denominator) {
+            Some(denominator) => Node::Div(Box::new(numerator), Box::new(denominator)),
+            None => numerator,
+        }
+    }
+
+    pub fn to_node(&self) -> Node {
+        if self.factors.is_empty() {
+            return self.coefficient.to_node();
+        }
+        let node = self.to_unsigned_node();
+        if self.coefficient.is_negative() {
+            Node::Neg(Box::new(node))
+        } else {
+            node
+        }
+    }
+}
+
+fn multiply(nodes: Vec<Node>) -> Option<Node> {
+    nodes
+        .into_iter()
+        .reduce(|acc, node| Node::Mul(Box::new(acc), Box::new(node)))
+}
+
+/// Writes `base^exponent` the way it was most likely written, as exp(x) or sqrt(x) where possible
+fn power_node(base: &Node, exponent: Node) -> Node {
+    let numeric = Coefficient::from_node(&exponent);
+    if numeric.is_some_and(|e| e.is_one()) {
+        return base.clone();
+    }
+    if *base == Node::EConstant {
+        return Node::Exp(Box::new(exponent));
+    }
+    if numeric == Some(Coefficient::rational(1, 2)) {
+        return Node::Sqrt(Box::new(base.clone()));
+    }
+    Node::Pow(Box::new(base.clone()), Box::new(exponent))
+}
@@ -0,0 +1,103 @@
+use std::cmp::Ordering;
+
+use super::coefficient::Coefficient;
+use super::product::{compare_nodes, Product};
+use crate::parser::Node;
+
+/// A sum of products where like terms, terms with the same factors, are combined
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Sum {
+    terms: Vec<Product>,
+}
+
+impl Sum {
+    /// Splits a simplified node into its terms
+    pub fn from_node(node: &Node) -> Sum {
+        let mut sum = Sum { terms: vec![] };
+        sum.collect(node, false);
+        sum
+    }
+
+    fn collect(&mut self, node: &Node, negated: bool) {
+        match node {
+            Node::Add(l, r) => {
+                self.collect(l, negated);
+                self.collect(r, negated);
+            }
+            Node::Sub(l, r) => {
+                self.collect(l, negated);
+                self.collect(r, !negated);
+            }
+            Node::Neg(n) => self.collect(n, !negated),
+            _ => {
+                let mut term = Product::from_node(node);
+                if negated {
+                    term.coefficient = term.coefficient.neg();
+                }
+                self.push(term);
+            }
+        }
+    }
+
+    fn push(&mut self, term: Product) {
+        let existing = self.terms.iter_mut().find(|t| t.factors == term.factors);
+        match existing.map(|t| (t.coefficient.add(term.coefficient), t)) {
+            Some((Ok(coefficient), existing)) => existing.coefficient = coefficient,
+            // Like terms whose sum overflows are kept apart
+            _ => self.terms.push(term),
+        }
+    }
+
+    pub fn add(mut self, other: Sum) -> Sum {
+        for term in other.terms {
+            self.push(term);
+        }
+        self
+    }
+
+    pub fn neg(mut self) -> Sum {
+        for term in &mut self.terms {
+            term.coefficient = term.coefficient.neg();
+        }
+        self
+    }
+
+    /// Builds the node with the terms in a canonical order, negative terms become subtractions
+    pub fn to_node(&self) -> Node {
+        let mut terms: Vec<&Product> = self.terms.iter().filter(|t| !t.is_zero()).collect();
+        terms.sort_by(|a, b| compare_terms(a, b));
+
+        let mut terms = terms.into_iter();
+        let Some(first) = terms.next() else {
+            return Coefficient::integer(0).to_node();
+        };
+        terms.fold(first.to_node(), |acc, term| {
+            let node = Box::new(term.to_unsigned_node());
+            if term.coefficient.is_negative() {
+                Node::Sub(Box::new(acc), node)
+            } else {
+                Node::Add(Box::new(acc), node)
+            }
+        })
+    }
+}
+
+/// Terms with variables come first, ordered by their factors with higher powers first, so
+/// polynomials read `x^2 + x + 1`
+fn compare_terms(a: &Product, b: &Product) -> Ordering {
+    a.is_constant().cmp(&b.is_constant()).then_with(|| {
+        for ((a_base, a_exponent), (b_base, b_exponent)) in a.factors.iter().zip(&b.factors) {
+            let order = compare_nodes(a_base, b_base).then_with(|| {
+                let a_exponent = Coefficient::from_node(a_exponent).map(Coefficient::to_f64);
+                let b_exponent = Coefficient::from_node(b_exponent).map(Coefficient::to_f64);
+                b_exponent
+                    .partial_cmp(&a_exponent)
+                    .unwrap_or(Ordering::Equal)
+            });
+            if order != Ordering::Equal {
+                return order;
+            }
+        }
+        a.factors.len().cmp(&b.factors.len())
+    })
+}
@@ -0,0 +1,122 @@
+use std::fmt;
+
+/// Symbols of the SI base units, in the order of the exponents in a [`Dimension`]
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+/// The exponents of the SI base units length, mass, time, current, temperature, amount and
+/// luminous intensity, a velocity is `m^1 * s^-1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dimension([i8; 7]);
+
+impl Dimension {
+    pub const fn new(exponents: [i8; 7]) -> Dimension {
+        Dimension(exponents)
+    }
+
+    pub fn dimensionless() -> Dimension {
+        Dimension::default()
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.0.iter().all(|e| *e == 0)
+    }
+
+    /// The dimension of a product, None if an exponent overflows
+    pub fn mul(&self, other: &Dimension) -> Option<Dimension> {
+        self.combine(other, i8::checked_add)
+    }
+
+    /// The dimension of a quotient, None if an exponent overflows
+    pub fn div(&self, other: &Dimension) -> Option<Dimension> {
+        self.combine(other, i8::checked_sub)
+    }
+
+    fn combine(&self, other: &Dimension, f: fn(i8, i8) -> Option<i8>) -> Option<Dimension> {
+        let mut exponents = [0; 7];
+        for (exponent, (a, b)) in exponents.iter_mut().zip(self.0.iter().zip(other.0)) {
+            *exponent = f(*a, b)?;
+        }
+        Some(Dimension(exponents))
+    }
+
+    /// Raises the dimension to `num / den`, None if an exponent doesn't stay whole, like the
+    /// square root of a metre
+    pub fn pow(&self, num: i64, den: i64) -> Option<Dimension> {
+        let mut exponents = [0; 7];
+        for (exponent, base) in exponents.iter_mut().zip(self.0) {
+            let scaled = base as i64 * num;
+            if scaled % den != 0 {
+                return None;
+            }
+            *exponent = i8::try_from(scaled / den).ok()?;
+        }
+        Some(Dimension(exponents))
+    }
+}
+
+/// Writes the dimension in base units like `kg*m/s^2`, a dimensionless value is `1`
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units = |positive: bool| {
+            // Mass goes first, the way forces and energies are usually written
+            [1, 0, 2, 3, 4, 5, 6]
+                .into_iter()
+                .filter(|i| (self.0[*i] > 0) == positive && self.0[*i] != 0)
+                .map(|i| match self.0[i].abs() {
+                    1 => BASE_UNITS[i].to_string(),
+                    e => format!("{}^{}", BASE_UNITS[i], e),
+                })
+                .collect::<Vec<_>>()
+        };
+        let (numerator, denominator) = (units(true), units(false));
+
+        if numerator.is_empty() {
+            write!(f, "1")?;
+        } else {
+            write!(f, "{}", numerator.join("*"))?;
+        }
+        match denominator.len() {
+            0 => Ok(()),
+            1 => write!(f, "/{}", denominator[0]),
+            _ => write!(f, "/({})", denominator.join("*")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LENGTH: Dimension = Dimension::new([1, 0, 0, 0, 0, 0, 0]);
+    const TIME: Dimension = Dimension::new([0, 0, 1, 0, 0, 0, 0]);
+
+    #[test]
+    fn test_display() {
+        let velocity = LENGTH.div(&TIME).unwrap();
+        assert_eq!(velocity.to_string(), "m/s");
+        let force = Dimension::new([1, 1, -2, 0, 0, 0, 0]);
+        assert_eq!(force.to_string(), "kg*m/s^2");
+        assert_eq!(
+            Dimension::dimensionless().div(&TIME).unwrap().to_string(),
+            "1/s"
+        );
+        let molar = Dimension::new([0, 0, 0, 0, -1, -1, 0]);
+        assert_eq!(molar.to_string(), "1/(K*mol)");
+        assert_eq!(Dimension::dimensionless().to_string(), "1");
+    }
+
+    #[test]
+    fn test_powers() {
+        let area = LENGTH.pow(2, 1).unwrap();
+        assert_eq!(area.pow(1, 2), Some(LENGTH));
+        assert_eq!(LENGTH.pow(1, 2), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let large = LENGTH.pow(100, 1).unwrap();
+        assert_eq!(large.mul(&large), None);
+        let small = Dimension::dimensionless().div(&large).unwrap();
+        assert_eq!(small.div(&large), None);
+    }
+}
@@ -0,0 +1,352 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::eval::EvalError;
+use crate::integer;
+use crate::lexer::{tokenize, LexerError, LexerToken};
+use crate::parser::{parse as parse_tokens, Node, ParserError};
+use crate::{Number, NumberError, NumberFormat};
+
+mod dimension;
+mod table;
+
+pub use dimension::Dimension;
+
+#[derive(Debug, Error)]
+pub enum UnitError {
+    #[error("Unknown unit: {0}")]
+    UnknownUnit(String),
+    #[error("Incompatible units: {0} and {1}")]
+    Incompatible(Dimension, Dimension),
+    #[error("Expected a dimensionless value, found {0}")]
+    NotDimensionless(Dimension),
+    #[error(transparent)]
+    Lexer(#[from] LexerError),
+    #[error(transparent)]
+    Parser(#[from] ParserError),
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+}
+
+impl From<NumberError> for UnitError {
+    fn from(error: NumberError) -> UnitError {
+        UnitError::Eval(error.into())
+    }
+}
+
+/// A value with a unit, like `6 m` or `21.6 km/h`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    value: f64,
+    unit: String,
+    /// The size of the unit in SI base units
+    factor: f64,
+    dimension: Dimension,
+}
+
+impl Quantity {
+    fn si(value: f64, dimension: Dimension) -> Quantity {
+        Quantity {
+            value,
+            unit: dimension.to_string(),
+            factor: 1.0,
+            dimension,
+        }
+    }
+
+    fn dimensionless(value: f64) -> Quantity {
+        Quantity::si(value, Dimension::dimensionless())
+    }
+
+    /// The value in [`Quantity::unit`]
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The unit the value is written in, SI base units unless the quantity was converted
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        self.dimension
+    }
+
+    /// Converts the quantity to a unit expression like `km/h`, which must have the same dimension
+    pub fn to(&self, unit: &str) -> Result<Quantity, UnitError> {
+        let target = evaluate(&parse(unit)?)?;
+        if target.dimension != self.dimension {
+            return Err(UnitError::Incompatible(self.dimension, target.dimension));
+        }
+        let value = Number::checked_div(
+            &Number::real(self.value * self.factor),
+            &Number::real(target.value),
+        )?;
+        Ok(Quantity {
+            value: value.value(),
+            unit: unit.trim().to_string(),
+            factor: target.value,
+            dimension: self.dimension,
+        })
+    }
+
+    fn require_dimensionless(&self) -> Result<f64, UnitError> {
+        if !self.dimension.is_dimensionless() {
+            return Err(UnitError::NotDimensionless(self.dimension));
+        }
+        Ok(self.value)
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = NumberFormat::default().format_real(self.value);
+        if self.dimension.is_dimensionless() {
+            write!(f, "{}", value)
+        } else {
+            write!(f, "{} {}", value, self.unit)
+        }
+    }
+}
+
+/// Parses an expression with units, a number followed by units is one quantity so `6 m / 2 s`
+/// is `(6*m) / (2*s)`
+///
+/// Units are written like variables, with `*`, `/` and integer powers between them, or next to
+/// each other for a product like `N m`.
+pub fn parse(input: &str) -> Result<Node, UnitError> {
+    Ok(parse_tokens(&with_products(tokenize(input)?))?)
+}
+
+/// Where the unit expression that starts at `start` ends, `m/s^2` in `9.81 m/s^2 * x`.
+///
+/// Units after a division only take the units next to them, `/ s / s` divides by each s on its
+/// own rather than by `s / s`.
+fn units_end(tokens: &[LexerToken], start: usize, divisor: bool) -> usize {
+    let is_unit = |i: usize| matches!(tokens.get(i), Some(LexerToken::Variable(_)));
+    let mut end = start;
+    while is_unit(end) {
+        end += 1;
+        // An integer power like `s^2` or `s^-1`
+        if tokens.get(end) == Some(&LexerToken::PowOperator) {
+            let sign = usize::from(tokens.get(end + 1) == Some(&LexerToken::SubOperator));
+            if matches!(tokens.get(end + 1 + sign), Some(LexerToken::Number(_))) {
+                end += 2 + sign;
+            }
+        }
+        let is_operator = matches!(
+            tokens.get(end),
+            Some(LexerToken::MulOperator | LexerToken::DivOperator)
+        );
+        if is_operator && !divisor && is_unit(end + 1) {
+            end += 1;
+        }
+    }
+    end
+}
+
+/// Inserts the multiplications that are implied between numbers and units, and groups each
+/// quantity in parentheses
+fn with_products(tokens: Vec<LexerToken>) -> Vec<LexerToken> {
+    let is_unit = |i: usize| matches!(tokens.get(i), Some(LexerToken::Variable(_)));
+    let mut result = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_number = matches!(tokens[i], LexerToken::Number(_));
+        let starts_quantity = is_unit(i) || (is_number && is_unit(i + 1));
+        if !starts_quantity {
+            result.push(tokens[i].clone());
+            if tokens[i] == LexerToken::RightParenthesis && is_unit(i + 1) {
+                result.push(LexerToken::MulOperator);
+            }
+            i += 1;
+            continue;
+        }
+
+        let divisor = result.last() == Some(&LexerToken::DivOperator);
+        result.push(LexerToken::LeftParenthesis);
+        if is_number {
+            result.extend([tokens[i].clone(), LexerToken::MulOperator]);
+            i += 1;
+        }
+        let end = units_end(&tokens, i, divisor);
+        for (j, token) in tokens.iter().enumerate().take(end).skip(i) {
+            result.push(token.clone());
+            if j + 1 < end && is_unit(j) && is_unit(j + 1) {
+                result.push(LexerToken::MulOperator);
+            }
+        }
+        result.push(LexerToken::RightParenthesis);
+        i = end;
+    }
+    result
+}
+
+/// Evaluates an expression with units to a quantity in SI base units, every variable is a unit
+///
+/// Sums and differences need the same dimension on both sides, and exponents, logarithms and
+/// trigonometric functions need dimensionless arguments.
+pub fn evaluate(node: &Node) -> Result<Quantity, UnitError> {
+    let dimensionless = |n: &Node| evaluate(n)?.require_dimensionless();
+    let real = |f: fn(&Number) -> Number, n: &Node| -> Result<Quantity, UnitError> {
+        let value = f(&Number::real(dimensionless(n)?)).checked()?;
+        if !value.is_real() {
+            return Err(EvalError::DomainError("complex number").into());
+        }
+        Ok(Quantity::dimensionless(value.value()))
+    };
+    let checked = |f: fn(&Number, &Number) -> Result<Number, NumberError>, l: f64, r: f64| {
+        Ok::<f64, UnitError>(f(&Number::real(l), &Number::real(r))?.value())
+    };
+
+    Ok(match node {
+        Node::Number(number) => {
+            if !number.is_real() {
+                return Err(EvalError::DomainError("complex number").into());
+            }
+            Quantity::dimensionless(number.value())
+        }
+        Node::Variable(name) => {
+            let unit = table::lookup(name).ok_or_else(|| UnitError::UnknownUnit(name.clone()))?;
+            Quantity::si(unit.factor, unit.dimension)
+        }
+        Node::PiConstant => Quantity::dimensionless(std::f64::consts::PI),
+        Node::EConstant => Quantity::dimensionless(std::f64::consts::E),
+        Node::IConstant => return Err(EvalError::DomainError("complex number").into()),
+        Node::Add(l, r) | Node::Sub(l, r) => {
+            let (l, r) = (evaluate(l)?, evaluate(r)?);
+            if l.dimension != r.dimension {
+                return Err(UnitError::Incompatible(l.dimension, r.dimension));
+            }
+            let value = match node {
+                Node::Add(..) => checked(Number::checked_add, l.value, r.value)?,
+                _ => checked(Number::checked_sub, l.value, r.value)?,
+            };
+            Quantity::si(value, l.dimension)
+        }
+        Node::Mul(l, r) => {
+            let (l, r) = (evaluate(l)?, evaluate(r)?);
+            let value = checked(Number::checked_mul, l.value, r.value)?;
+            let dimension = l.dimension.mul(&r.dimension).ok_or(EvalError::Overflow)?;
+            Quantity::si(value, dimension)
+        }
+        Node::Div(l, r) => {
+            let (l, r) = (evaluate(l)?, evaluate(r)?);
+            let value = checked(Number::checked_div, l.value, r.value)?;
+            let dimension = l.dimension.div(&r.dimension).ok_or(EvalError::Overflow)?;
+            Quantity::si(value, dimension)
+        }
+        Node::Pow(l, r) => power(evaluate(l)?, dimensionless(r)?)?,
+        Node::Sqrt(n) => power(evaluate(n)?, 0.5)?,
+        Node::Neg(n) => {
+            let quantity = evaluate(n)?;
+            Quantity::si(-quantity.value, quantity.dimension)
+        }
+        Node::Exp(n) => real(Number::exp, n)?,
+        Node::Log(n) => {
+            let value = dimensionless(n)?;
+            if value < 0.0 {
+                return Err(EvalError::DomainError("ln of a negative number").into());
+            }
+            Quantity::dimensionless(Number::checked_ln(&Number::real(value))?.value())
+        }
+        Node::Sin(n) => real(Number::sin, n)?,
+        Node::Cos(n) => real(Number::cos, n)?,
+        Node::Tan(n) => real(Number::tan, n)?,
+        Node::Function(function, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(dimensionless)
+                .collect::<Result<Vec<_>, UnitError>>()?;
+            Quantity::dimensionless(integer::evaluate_real(*function, &arguments)?)
+        }
+    })
+}
+
+/// Raises a quantity to a power, a fractional power needs whole exponents in the result like
+/// `sqrt(m^2)`
+fn power(base: Quantity, exponent: f64) -> Result<Quantity, UnitError> {
+    let value = Number::checked_pow(&Number::real(base.value), &Number::real(exponent))?.value();
+    if base.dimension.is_dimensionless() {
+        return Ok(Quantity::dimensionless(value));
+    }
+
+    let dimension = (1..=6)
+        .find(|den| (exponent * *den as f64).fract() == 0.0)
+        .and_then(|den| base.dimension.pow((exponent * den as f64) as i64, den))
+        .ok_or(UnitError::NotDimensionless(base.dimension))?;
+    Ok(Quantity::si(value, dimension))
+}
+
+/// Evaluates an expression with units, optionally followed by a conversion like `to km/h`
+pub fn calculate(input: &str) -> Result<Quantity, UnitError> {
+    match input.split_once(" to ") {
+        Some((expression, unit)) => evaluate(&parse(expression)?)?.to(unit),
+        None => evaluate(&parse(input)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculated(input: &str) -> String {
+        calculate(input).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_quantities() {
+        assert_eq!(calculated("3 m/s * 2 s"), "6 m");
+        assert_eq!(calculated("6 m / 2 s"), "3 m/s");
+        assert_eq!(calculated("2 kg * 9.81 m/s^2"), "19.62 kg*m/s^2");
+        assert_eq!(calculated("1 km + 500 m"), "1500 m");
+        assert_eq!(calculated("sqrt(16 m^2)"), "4 m");
+        assert_eq!(calculated("10 N m / 5 J"), "2");
+        assert_eq!(calculated("1 / s / s"), "1 1/s^2");
+        assert_eq!(calculated("1 / s * s"), "1");
+        assert_eq!(calculated("6 m / 2 s / s"), "3 m/s^2");
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(calculated("6 m/s to km/h"), "21.6 km/h");
+        assert_eq!(calculated("1 kWh to J"), "3600000 J");
+        assert_eq!(calculated("90 min to h"), "1.5 h");
+        let quantity = calculate("2 mi to km").unwrap();
+        assert!((quantity.value() - 3.218688).abs() < 1e-12);
+        assert_eq!(quantity.unit(), "km");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            calculate("1 m + 2 s"),
+            Err(UnitError::Incompatible(_, _))
+        ));
+        assert_eq!(
+            calculate("1 m + 2 s").unwrap_err().to_string(),
+            "Incompatible units: m and s"
+        );
+        assert!(matches!(
+            calculate("3 m to s"),
+            Err(UnitError::Incompatible(_, _))
+        ));
+        assert!(matches!(
+            calculate("2 parsec"),
+            Err(UnitError::UnknownUnit(_))
+        ));
+        assert!(matches!(
+            calculate("sin(2 m)"),
+            Err(UnitError::NotDimensionless(_))
+        ));
+        assert!(matches!(
+            calculate("sqrt(2 m)"),
+            Err(UnitError::NotDimensionless(_))
+        ));
+        assert!(matches!(
+            calculate("m^100 * m^100"),
+            Err(UnitError::Eval(EvalError::Overflow))
+        ));
+    }
+}
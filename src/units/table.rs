@@ -0,0 +1,122 @@
+use super::Dimension;
+
+/// A named unit as a multiple of the SI base units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Unit {
+    pub factor: f64,
+    pub dimension: Dimension,
+}
+
+/// Name, size in SI base units, dimension and whether it takes a metric prefix
+type Entry = (&'static str, f64, Dimension, bool);
+
+// Exponents of m, kg, s, A, K, mol and cd
+const LENGTH: Dimension = Dimension::new([1, 0, 0, 0, 0, 0, 0]);
+const MASS: Dimension = Dimension::new([0, 1, 0, 0, 0, 0, 0]);
+const TIME: Dimension = Dimension::new([0, 0, 1, 0, 0, 0, 0]);
+const CURRENT: Dimension = Dimension::new([0, 0, 0, 1, 0, 0, 0]);
+const TEMPERATURE: Dimension = Dimension::new([0, 0, 0, 0, 1, 0, 0]);
+const AMOUNT: Dimension = Dimension::new([0, 0, 0, 0, 0, 1, 0]);
+const LUMINOUS_INTENSITY: Dimension = Dimension::new([0, 0, 0, 0, 0, 0, 1]);
+const VOLUME: Dimension = Dimension::new([3, 0, 0, 0, 0, 0, 0]);
+const FREQUENCY: Dimension = Dimension::new([0, 0, -1, 0, 0, 0, 0]);
+const FORCE: Dimension = Dimension::new([1, 1, -2, 0, 0, 0, 0]);
+const PRESSURE: Dimension = Dimension::new([-1, 1, -2, 0, 0, 0, 0]);
+const ENERGY: Dimension = Dimension::new([2, 1, -2, 0, 0, 0, 0]);
+const POWER: Dimension = Dimension::new([2, 1, -3, 0, 0, 0, 0]);
+const CHARGE: Dimension = Dimension::new([0, 0, 1, 1, 0, 0, 0]);
+const VOLTAGE: Dimension = Dimension::new([2, 1, -3, -1, 0, 0, 0]);
+const RESISTANCE: Dimension = Dimension::new([2, 1, -3, -2, 0, 0, 0]);
+
+const UNITS: [Entry; 31] = [
+    // SI base units, the kilogram is a prefixed gram
+    ("m", 1.0, LENGTH, true),
+    ("g", 1e-3, MASS, true),
+    ("s", 1.0, TIME, true),
+    ("A", 1.0, CURRENT, true),
+    ("K", 1.0, TEMPERATURE, true),
+    ("mol", 1.0, AMOUNT, true),
+    ("cd", 1.0, LUMINOUS_INTENSITY, true),
+    // Derived units
+    ("L", 1e-3, VOLUME, true),
+    ("Hz", 1.0, FREQUENCY, true),
+    ("N", 1.0, FORCE, true),
+    ("Pa", 1.0, PRESSURE, true),
+    ("J", 1.0, ENERGY, true),
+    ("W", 1.0, POWER, true),
+    ("C", 1.0, CHARGE, true),
+    ("V", 1.0, VOLTAGE, true),
+    ("Ohm", 1.0, RESISTANCE, true),
+    ("Wh", 3600.0, ENERGY, true),
+    ("eV", 1.602176634e-19, ENERGY, true),
+    ("bar", 1e5, PRESSURE, true),
+    ("cal", 4.184, ENERGY, true),
+    // Units that aren't used with prefixes
+    ("min", 60.0, TIME, false),
+    ("h", 3600.0, TIME, false),
+    ("day", 86400.0, TIME, false),
+    ("t", 1000.0, MASS, false),
+    ("in", 0.0254, LENGTH, false),
+    ("ft", 0.3048, LENGTH, false),
+    ("yd", 0.9144, LENGTH, false),
+    ("mi", 1609.344, LENGTH, false),
+    ("lb", 0.45359237, MASS, false),
+    ("atm", 101325.0, PRESSURE, false),
+    ("psi", 6894.757293168361, PRESSURE, false),
+];
+
+const PREFIXES: [(&str, f64); 12] = [
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("μ", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+];
+
+fn unit(entry: &Entry) -> Unit {
+    Unit {
+        factor: entry.1,
+        dimension: entry.2,
+    }
+}
+
+/// Looks up a unit symbol like `m`, `km` or `kWh`, exact names win over prefixes so `min` is a
+/// minute and not a milli-inch
+pub(super) fn lookup(name: &str) -> Option<Unit> {
+    if let Some(entry) = UNITS.iter().find(|entry| entry.0 == name) {
+        return Some(unit(entry));
+    }
+
+    PREFIXES.iter().find_map(|(prefix, scale)| {
+        let rest = name.strip_prefix(prefix)?;
+        let entry = UNITS.iter().find(|entry| entry.3 && entry.0 == rest)?;
+        let unit = unit(entry);
+        Some(Unit {
+            factor: scale * unit.factor,
+            ..unit
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("km").unwrap().factor, 1000.0);
+        assert_eq!(lookup("kg").unwrap().factor, 1.0);
+        assert_eq!(lookup("min").unwrap().factor, 60.0);
+        assert_eq!(lookup("mm").unwrap().dimension, LENGTH);
+        assert_eq!(lookup("kWh").unwrap().factor, 3.6e6);
+        assert!(lookup("kmin").is_none());
+        assert!(lookup("x").is_none());
+    }
+}